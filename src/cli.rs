@@ -1,4 +1,5 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use memoesu::{Canonicalizer, DefaultCanonicalizer, Shard};
 
 #[derive(Parser, Debug)]
 pub struct Cli {
@@ -18,14 +19,37 @@ pub enum Mode {
         #[arg(short, long)]
         output: Option<String>,
 
-        /// Size of the subgraphs to find in the input graph
-        #[arg(short, long)]
+        /// Size of the subgraphs to find in the input graph (the largest size when
+        /// enumerating a range of sizes)
+        #[arg(short, long, visible_alias = "max-size")]
         subgraph_size: usize,
 
+        /// Smallest size of the subgraphs to find in the input graph. All sizes from
+        /// this up to the subgraph size are enumerated in a single pass and written
+        /// with an additional leading `k` column [default: subgraph size]
+        #[arg(long)]
+        min_size: Option<usize>,
+
+        /// Report non-induced counts (i.e. every occurrence of a subgraph, also as part
+        /// of denser subgraphs on the same nodes) instead of induced counts [default: false]
+        #[arg(long)]
+        non_induced: bool,
+
         /// Number of threads to use [default: 1]
         #[arg(short, long)]
         threads: Option<usize>,
 
+        /// File path to the node colors (white space separated node and numeric color).
+        /// Subgraphs are then only isomorphic if the node colors also match [default: uncolored]
+        #[arg(short, long)]
+        colors: Option<String>,
+
+        /// The input graph has a third column with numeric edge types (e.g. activation
+        /// and repression). Subgraphs are then only isomorphic if the edge types also match
+        /// [default: false]
+        #[arg(short, long)]
+        edge_types: bool,
+
         /// Include edges with loops (i.e. a node connects to itself) [default: false]
         #[arg(short = 'l', long)]
         include_loops: bool,
@@ -33,6 +57,55 @@ pub enum Mode {
         /// Assume undirected graph (i.e. edges are bidirectional) [default: false]
         #[arg(short, long)]
         undirected: bool,
+
+        /// Write a header line above the counts, which sampled counts always have
+        /// [default: false]
+        #[arg(long)]
+        header: bool,
+
+        /// Per-depth child retention probabilities for RAND-ESU sampling, one per
+        /// subgraph node (e.g. 1,1,0.5,0.1) [default: full enumeration]
+        #[arg(long, value_delimiter = ',')]
+        sample_probs: Option<Vec<f64>>,
+
+        /// Seed for the random number generator used for sampling
+        #[arg(short = 'S', long)]
+        seed: Option<usize>,
+
+        /// Report the progress (finished roots, throughput, and ETA) to stderr
+        /// every given number of seconds
+        #[arg(short = 'P', long)]
+        progress: Option<f64>,
+
+        /// Periodically write the counts of the finished roots to this checkpoint file
+        #[arg(long)]
+        checkpoint: Option<String>,
+
+        /// Number of seconds between checkpoints
+        #[arg(long, default_value = "600")]
+        checkpoint_interval: f64,
+
+        /// Resume an interrupted enumeration from a checkpoint file (which is also
+        /// updated unless another checkpoint file is given)
+        #[arg(long)]
+        resume: Option<String>,
+
+        /// Only explore the roots of the i-th of N shards (e.g. 2/8), so that a run can
+        /// be split over independent jobs whose outputs are summed with `merge`
+        #[arg(long, conflicts_with = "sample_probs")]
+        shard: Option<Shard>,
+
+        /// File path to a cache of the canonical labels of earlier runs with the same
+        /// subgraph size, direction, and loops, which is extended with the labels of
+        /// this run (and created if it does not exist)
+        #[arg(long)]
+        memo_cache: Option<String>,
+
+        /// Backend computing the canonical labels of the subgraphs. Labels of different
+        /// backends are not comparable, and the default backend is required for
+        /// non-induced counts [default: dense-nauty, or exhaustive without nauty]
+        #[arg(long, value_enum)]
+        canonicalizer: Option<Backend>,
     },
 
     /// Report all subgraph membership and orbit position for each node in a graph.
@@ -49,6 +122,89 @@ pub enum Mode {
         #[arg(short, long)]
         subgraph_size: usize,
 
+        /// Number of threads to use [default: 1]
+        #[arg(short, long)]
+        threads: Option<usize>,
+
+        /// File path to the node colors (white space separated node and numeric color).
+        /// Subgraphs are then only isomorphic if the node colors also match [default: uncolored]
+        #[arg(short, long)]
+        colors: Option<String>,
+
+        /// The input graph has a third column with numeric edge types (e.g. activation
+        /// and repression). Subgraphs are then only isomorphic if the edge types also match
+        /// [default: false]
+        #[arg(short, long)]
+        edge_types: bool,
+
+        /// Include edges with loops (i.e. a node connects to itself) [default: false]
+        #[arg(short = 'l', long)]
+        include_loops: bool,
+
+        /// Assume undirected graph (i.e. edges are bidirectional) [default: false]
+        #[arg(short, long)]
+        undirected: bool,
+
+        /// Remove header from output [default: false]
+        #[arg(short, long)]
+        no_header: bool,
+
+        /// Per-depth child retention probabilities for RAND-ESU sampling, one per
+        /// subgraph node (e.g. 1,1,0.5,0.1) [default: full enumeration]
+        #[arg(long, value_delimiter = ',')]
+        sample_probs: Option<Vec<f64>>,
+
+        /// Seed for the random number generator used for sampling
+        #[arg(short = 'S', long)]
+        seed: Option<usize>,
+
+        /// Only explore the roots of the i-th of N shards (e.g. 2/8), so that a run can
+        /// be split over independent jobs whose outputs are summed with `merge`
+        #[arg(long, conflicts_with = "sample_probs")]
+        shard: Option<Shard>,
+
+        /// File path to a cache of the canonical labels of earlier runs with the same
+        /// subgraph size, direction, and loops, which is extended with the labels of
+        /// this run (and created if it does not exist)
+        #[arg(long)]
+        memo_cache: Option<String>,
+    },
+
+    /// Stream every subgraph occurrence with its nodes and canonical label.
+    Instances {
+        /// File path to the input graph (white space separated edgelist)
+        #[arg(short, long)]
+        input: String,
+
+        /// Output file path to write results to (default: stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Size of the subgraphs to find in the input graph
+        #[arg(short, long)]
+        subgraph_size: usize,
+
+        /// Only write the instances of these motifs (comma separated graph6 strings
+        /// as in the `canon` column) [default: all motifs]
+        #[arg(short, long, value_delimiter = ',')]
+        motifs: Option<Vec<String>>,
+
+        /// Number of threads to use. The order of the instances is then not
+        /// deterministic [default: 1]
+        #[arg(short, long)]
+        threads: Option<usize>,
+
+        /// File path to the node colors (white space separated node and numeric color).
+        /// Subgraphs are then only isomorphic if the node colors also match [default: uncolored]
+        #[arg(short, long)]
+        colors: Option<String>,
+
+        /// The input graph has a third column with numeric edge types (e.g. activation
+        /// and repression). Subgraphs are then only isomorphic if the edge types also match
+        /// [default: false]
+        #[arg(short, long)]
+        edge_types: bool,
+
         /// Include edges with loops (i.e. a node connects to itself) [default: false]
         #[arg(short = 'l', long)]
         include_loops: bool,
@@ -62,6 +218,116 @@ pub enum Mode {
         no_header: bool,
     },
 
+    /// Count (and optionally locate) only a set of target motifs.
+    Query {
+        /// File path to the input graph (white space separated edgelist)
+        #[arg(short, long)]
+        input: String,
+
+        /// Output file path to write the motif counts to (default: stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// The target motifs as comma separated graph6 (undirected) or digraph6
+        /// (directed) strings, which may be of different sizes (e.g. '&BP_,&C?Bw')
+        #[arg(short, long, value_delimiter = ',', required = true)]
+        motifs: Vec<String>,
+
+        /// Output file path to stream the instances of the target motifs to
+        /// (same format as the `instances` subcommand) [default: counts only]
+        #[arg(long)]
+        instances: Option<String>,
+
+        /// Number of threads to use [default: 1]
+        #[arg(short, long)]
+        threads: Option<usize>,
+
+        /// Include edges with loops (i.e. a node connects to itself) [default: false]
+        #[arg(short = 'l', long)]
+        include_loops: bool,
+
+        /// Assume undirected graph (i.e. edges are bidirectional) [default: false]
+        #[arg(short, long)]
+        undirected: bool,
+    },
+
+    /// Count the graphlet degree vector (GDV) of every node of an undirected graph,
+    /// i.e. how often it touches each orbit of the graphlets with 2 up to k nodes
+    /// (orbits 0-72 for k = 5).
+    Gdv {
+        /// File path to the input graph (white space separated edgelist)
+        #[arg(short, long)]
+        input: String,
+
+        /// Output file path to write results to, written as a NumPy matrix if it
+        /// ends with `.npy` (default: stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Largest graphlet size (between 2 and 5)
+        #[arg(short, long, default_value = "5")]
+        subgraph_size: usize,
+
+        /// Number of threads to use [default: 1]
+        #[arg(short, long)]
+        threads: Option<usize>,
+
+        /// Remove header from output [default: false]
+        #[arg(short, long)]
+        no_header: bool,
+
+        /// Only explore the roots of the i-th of N shards (e.g. 2/8), so that a run can
+        /// be split over independent jobs whose outputs are summed with `merge`
+        #[arg(long)]
+        shard: Option<Shard>,
+    },
+
+    /// Count how often every edge appears in each edge orbit (i.e. each edge
+    /// position up to automorphism) of the subgraphs with 2 up to k nodes.
+    EdgeOrbits {
+        /// File path to the input graph (white space separated edgelist)
+        #[arg(short, long)]
+        input: String,
+
+        /// Output file path to write results to, written as a NumPy matrix if it
+        /// ends with `.npy` (default: stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Largest subgraph size
+        #[arg(short, long, default_value = "4")]
+        subgraph_size: usize,
+
+        /// Number of threads to use [default: 1]
+        #[arg(short, long)]
+        threads: Option<usize>,
+
+        /// Include edges with loops (i.e. a node connects to itself) [default: false]
+        #[arg(short = 'l', long)]
+        include_loops: bool,
+
+        /// Assume undirected graph (i.e. edges are bidirectional) [default: false]
+        #[arg(short, long)]
+        undirected: bool,
+
+        /// Remove header from output [default: false]
+        #[arg(short, long)]
+        no_header: bool,
+    },
+
+    /// Sum the outputs of all shards of an `enumerate`, `groups`, or `gdv` run
+    /// into the output of the full run.
+    Merge {
+        /// File paths to the outputs of the shards (every shard of the run exactly once)
+        #[arg(short, long, num_args = 1.., required = true)]
+        input: Vec<String>,
+
+        /// Output file path to write results to, written as a NumPy matrix for graphlet
+        /// degree vectors if it ends with `.npy` (default: stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
     /// Formats an input graph into a usable format for `memoesu`
     Format {
         /// File path to the input graph (white space separated edgelist)
@@ -95,6 +361,11 @@ pub enum Mode {
         /// Seed for the random number generator
         #[arg(short = 'S', long)]
         seed: Option<usize>,
+
+        /// Assume undirected graph (i.e. edges are bidirectional) and switch with
+        /// double edge swaps [default: false]
+        #[arg(short, long)]
+        undirected: bool,
     },
 
     /// Performs enumeration on a graph and then performs a random
@@ -131,5 +402,51 @@ pub enum Mode {
         /// Number of threads to use [default: 1]
         #[arg(short, long)]
         threads: Option<usize>,
+
+        /// Assume undirected graph (i.e. edges are bidirectional) [default: false]
+        #[arg(short, long)]
+        undirected: bool,
+
+        /// Per-depth child retention probabilities for RAND-ESU sampling, one per
+        /// subgraph node (e.g. 1,1,0.5,0.1) [default: full enumeration]
+        #[arg(long, value_delimiter = ',')]
+        sample_probs: Option<Vec<f64>>,
+
+        /// Also enumerate every random graph on all threads (the random graphs are
+        /// always generated and enumerated in parallel) [default: false]
+        #[arg(long)]
+        parallel_enumeration: bool,
+
+        /// File path to a cache of the canonical labels of earlier runs with the same
+        /// subgraph size, direction, and loops, which is extended with the labels of
+        /// this run and all random graphs (and created if it does not exist)
+        #[arg(long)]
+        memo_cache: Option<String>,
     },
 }
+
+/// The canonicalization backends of the enumeration.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// The dense nauty routine
+    #[cfg(feature = "nauty")]
+    DenseNauty,
+
+    /// The sparse nauty routine
+    #[cfg(feature = "nauty")]
+    SparseNauty,
+
+    /// Traces (undirected graphs only)
+    #[cfg(feature = "nauty")]
+    Traces,
+
+    /// The pure Rust search without pruning
+    Exhaustive,
+}
+impl Backend {
+    /// Whether the backend is the default canonicalizer of the enumeration.
+    pub fn is_default(&self) -> bool {
+        self.to_possible_value()
+            .is_some_and(|value| value.get_name() == DefaultCanonicalizer::NAME)
+    }
+}
@@ -0,0 +1,401 @@
+use crate::enumerate::{
+    has_label_arc, label_arcs, BitGraph, Esu, Instance, Label, ParEsu, UnionFind,
+};
+use anyhow::{bail, Result};
+use hashbrown::HashMap;
+use ndarray::Array2;
+use petgraph::{Directed, EdgeType, Undirected};
+use rayon::ThreadPool;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+/// The arcs of a canonical label with the edge orbit (column) of each arc.
+type OrbitArcs = Arc<[(usize, usize, usize)]>;
+
+/// The edge orbit counts of a graph, i.e. how often every edge appears in
+/// each edge orbit of the subgraphs with 2 up to `k` nodes.
+#[derive(Debug, Clone)]
+pub struct EdgeOrbitCounts {
+    is_directed: bool,
+    edges: Vec<(usize, usize)>,
+    orbits: Vec<(Label, usize)>,
+    matrix: Array2<u64>,
+}
+impl EdgeOrbitCounts {
+    /// Whether the edges are directed arcs.
+    pub fn is_directed(&self) -> bool {
+        self.is_directed
+    }
+
+    /// The edges of the graph (rows of the matrix), where undirected edges
+    /// are given with their smaller node first.
+    pub fn edges(&self) -> &[(usize, usize)] {
+        &self.edges
+    }
+
+    /// The canonical label of each subgraph and the index of the edge orbit
+    /// within it (columns of the matrix), sorted by subgraph size and label.
+    pub fn orbits(&self) -> &[(Label, usize)] {
+        &self.orbits
+    }
+
+    /// The edge by edge orbit matrix.
+    pub fn matrix(&self) -> &Array2<u64> {
+        &self.matrix
+    }
+}
+
+/// The edge orbits of every canonical label found during an enumeration,
+/// where the orbits are assigned to columns in order of discovery.
+#[derive(Default)]
+struct OrbitTable {
+    memo: flurry::HashMap<Label, OrbitArcs>,
+    columns: Mutex<Vec<(Label, usize)>>,
+}
+impl OrbitTable {
+    /// The arcs of the label and their columns.
+    fn get(&self, label: &Label, is_directed: bool) -> OrbitArcs {
+        let guard = self.memo.guard();
+        if let Some(arcs) = self.memo.get(label, &guard) {
+            return arcs.clone();
+        }
+
+        // Assign the columns while holding the lock so that each label is only added once
+        let mut columns = self
+            .columns
+            .lock()
+            .expect("the orbit table is not poisoned");
+        if let Some(arcs) = self.memo.get(label, &guard) {
+            return arcs.clone();
+        }
+        let offset = columns.len();
+        let orbits = edge_orbits(&label.words(), label.len(), is_directed);
+        let num_orbits = orbits.iter().map(|&(_, _, o)| o + 1).max().unwrap_or(0);
+        columns.extend((0..num_orbits).map(|orbit| (label.clone(), orbit)));
+        let arcs: OrbitArcs = orbits
+            .into_iter()
+            .map(|(u, v, orbit)| (u, v, offset + orbit))
+            .collect();
+        self.memo.insert(label.clone(), arcs.clone(), &guard);
+        arcs
+    }
+}
+
+/// The automorphism orbits of the arcs of an (uncolored and untyped)
+/// canonical label with `k` nodes.
+///
+/// Every arc `(u, v)` of the canonical positions is returned with its orbit,
+/// numbered in order of the first arc of each orbit. Undirected edges are
+/// only returned once with `u < v`.
+pub fn edge_orbits(label: &[u64], k: usize, is_directed: bool) -> Vec<(usize, usize, usize)> {
+    let arcs = label_arcs(label, k, is_directed);
+    let index = arcs
+        .iter()
+        .enumerate()
+        .map(|(idx, &arc)| (arc, idx))
+        .collect::<HashMap<_, _>>();
+
+    // Merge every arc with its image under each automorphism
+    let mut orbit_sets = UnionFind::new(arcs.len());
+    for perm in automorphisms(k, |u, v| has_label_arc(label, k, u, v)) {
+        for (idx, &(u, v)) in arcs.iter().enumerate() {
+            let (pu, pv) = (perm[u], perm[v]);
+            let image = if is_directed {
+                (pu, pv)
+            } else {
+                (pu.min(pv), pu.max(pv))
+            };
+            orbit_sets.union(idx, index[&image]);
+        }
+    }
+
+    let mut orbits = HashMap::<usize, usize>::new();
+    arcs.iter()
+        .enumerate()
+        .map(|(idx, &(u, v))| {
+            let root = orbit_sets.find(idx);
+            let next = orbits.len();
+            (u, v, *orbits.entry(root).or_insert(next))
+        })
+        .collect()
+}
+
+/// All permutations of the `k` nodes that preserve the arcs, found by
+/// backtracking over partial assignments.
+fn automorphisms<F: Fn(usize, usize) -> bool>(k: usize, has_arc: F) -> Vec<Vec<usize>> {
+    fn extend<F: Fn(usize, usize) -> bool>(
+        perm: &mut Vec<usize>,
+        used: &mut [bool],
+        has_arc: &F,
+        found: &mut Vec<Vec<usize>>,
+    ) {
+        let u = perm.len();
+        if u == used.len() {
+            found.push(perm.clone());
+            return;
+        }
+        for pu in 0..used.len() {
+            if used[pu] {
+                continue;
+            }
+            let consistent = perm.iter().enumerate().all(|(v, &pv)| {
+                has_arc(u, v) == has_arc(pu, pv) && has_arc(v, u) == has_arc(pv, pu)
+            });
+            if consistent {
+                used[pu] = true;
+                perm.push(pu);
+                extend(perm, used, has_arc, found);
+                perm.pop();
+                used[pu] = false;
+            }
+        }
+    }
+    let mut found = Vec::new();
+    extend(
+        &mut Vec::with_capacity(k),
+        &mut vec![false; k],
+        &has_arc,
+        &mut found,
+    );
+    found
+}
+
+/// Count how often every edge of a graph appears in each edge orbit of the
+/// subgraphs with 2 up to `k` nodes.
+///
+/// The result has a row per edge (each arc for directed graphs) and a column
+/// per edge orbit of the subgraphs found, and is computed in parallel on the
+/// thread pool if one is given.
+pub fn edge_orbit_counts(
+    graph: BitGraph,
+    k: usize,
+    pool: Option<Arc<ThreadPool>>,
+) -> Result<EdgeOrbitCounts> {
+    if k < 2 {
+        bail!("ERROR: Edge orbits require a subgraph size of at least 2.");
+    }
+    if graph.is_typed() {
+        bail!("ERROR: Edge orbits are not supported for graphs with edge types.");
+    }
+    let is_directed = graph.is_directed;
+    let edges = (0..graph.node_count())
+        .flat_map(|u| {
+            let neighbors = if is_directed {
+                graph.neighbors_directed(u)
+            } else {
+                graph.neighbors(u)
+            };
+            neighbors
+                .filter(move |&v| v != u && (is_directed || u < v))
+                .map(move |v| (u, v))
+        })
+        .collect::<Vec<_>>();
+    let rows = Arc::new(
+        edges
+            .iter()
+            .enumerate()
+            .map(|(idx, &edge)| (edge, idx))
+            .collect::<HashMap<_, _>>(),
+    );
+    let table = Arc::new(OrbitTable::default());
+    let counts = Arc::new(flurry::HashMap::<(usize, usize), AtomicU64>::new());
+
+    // Every instance adds its edges to the orbits of their canonical positions
+    let callback = {
+        let table = table.clone();
+        let counts = counts.clone();
+        move |instance: &Instance| {
+            let nodes = instance.canonical_nodes().collect::<Vec<_>>();
+            let guard = counts.guard();
+            for &(u, v, column) in table.get(instance.label(), is_directed).iter() {
+                let (a, b) = (nodes[u], nodes[v]);
+                let edge = if is_directed {
+                    (a, b)
+                } else {
+                    (a.min(b), a.max(b))
+                };
+                let key = (rows[&edge], column);
+                let count = match counts.get(&key, &guard) {
+                    Some(count) => count,
+                    None => match counts.try_insert(key, AtomicU64::new(0), &guard) {
+                        Ok(count) => count,
+                        Err(error) => error.current,
+                    },
+                };
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    };
+    if is_directed {
+        enumerate::<Directed, _>(graph, k, pool, callback)?;
+    } else {
+        enumerate::<Undirected, _>(graph, k, pool, callback)?;
+    }
+
+    // Sort the columns by subgraph size and label
+    let table = Arc::into_inner(table).expect("the enumerator has been dropped");
+    let columns = table
+        .columns
+        .into_inner()
+        .expect("the orbit table is not poisoned");
+    let mut order = (0..columns.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        let (label_a, orbit_a) = &columns[a];
+        let (label_b, orbit_b) = &columns[b];
+        (label_a.len(), label_a, orbit_a).cmp(&(label_b.len(), label_b, orbit_b))
+    });
+    let mut position = vec![0; columns.len()];
+    for (pos, &column) in order.iter().enumerate() {
+        position[column] = pos;
+    }
+
+    let mut matrix = Array2::zeros((edges.len(), columns.len()));
+    let counts = Arc::into_inner(counts).expect("the enumerator has been dropped");
+    for (&(row, column), count) in counts.pin().iter() {
+        matrix[[row, position[column]]] = count.load(Ordering::Relaxed);
+    }
+    let orbits = order
+        .into_iter()
+        .map(|column| columns[column].clone())
+        .collect();
+    Ok(EdgeOrbitCounts {
+        is_directed,
+        edges,
+        orbits,
+        matrix,
+    })
+}
+
+/// Enumerate the subgraphs with 2 up to `k` nodes and pass them to the callback.
+fn enumerate<Ty, F>(
+    graph: BitGraph,
+    k: usize,
+    pool: Option<Arc<ThreadPool>>,
+    callback: F,
+) -> Result<()>
+where
+    Ty: EdgeType + Sync,
+    F: Fn(&Instance) + Send + Sync + 'static,
+{
+    if let Some(pool) = pool {
+        let mut esu = ParEsu::<Ty>::from_bitgraph(k, graph)?;
+        esu.set_min_size(2)?;
+        esu.set_instances(callback);
+        esu.set_thread_pool(pool);
+        esu.enumerate();
+    } else {
+        let mut esu = Esu::<Ty>::from_bitgraph(k, graph)?;
+        esu.set_min_size(2)?;
+        esu.set_instances(callback);
+        esu.enumerate();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use crate::{
+        enumerate::{canon, enumerate_subgraph_sizes},
+        io::load_numeric_graph,
+    };
+    use hashbrown::HashSet;
+    use petgraph::Graph;
+
+    fn num_orbits(label: &Label, k: usize, is_directed: bool) -> usize {
+        edge_orbits(&label.words(), k, is_directed)
+            .into_iter()
+            .map(|(_, _, orbit)| orbit)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    #[test]
+    fn orbits_of_small_graphs() {
+        let path = canon(3, &[(0, 1), (1, 2)], false);
+        assert_eq!(edge_orbits(&path.words(), 3, false).len(), 2);
+        assert_eq!(num_orbits(&path, 3, false), 1);
+
+        // The paw splits into the pendant, the edges at the pendant, and the opposite edge
+        let paw = canon(4, &[(0, 1), (1, 2), (2, 0), (0, 3)], false);
+        assert_eq!(num_orbits(&paw, 4, false), 3);
+
+        let clique = canon(4, &[(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)], false);
+        assert_eq!(num_orbits(&clique, 4, false), 1);
+
+        // Every arc of the feed-forward loop is distinct but the cycle is symmetric
+        let ffl = canon(3, &[(0, 1), (1, 2), (0, 2)], true);
+        assert_eq!(num_orbits(&ffl, 3, true), 3);
+        let cycle = canon(3, &[(0, 1), (1, 2), (2, 0)], true);
+        assert_eq!(num_orbits(&cycle, 3, true), 1);
+        let mutual = canon(2, &[(0, 1), (1, 0)], true);
+        assert_eq!(edge_orbits(&mutual.words(), 2, true).len(), 2);
+        assert_eq!(num_orbits(&mutual, 2, true), 1);
+    }
+
+    #[test]
+    fn undir_ecoli_edge_orbits() {
+        let graph: Graph<(), (), Undirected> =
+            load_numeric_graph("example/ecoli.txt", false).unwrap();
+        let bitgraph = BitGraph::from_graph(&graph);
+        let result = edge_orbit_counts(BitGraph::from_graph(&graph), 3, None).unwrap();
+        assert!(!result.is_directed());
+        assert_eq!(result.orbits().len(), 3);
+        let (edge, path, triangle) = (0, 1, 2);
+
+        // Every edge is in a single edge graphlet, its triangles, and its paths
+        for (row, &(u, v)) in result.edges().iter().enumerate() {
+            let neighbors = |w: usize| bitgraph.neighbors(w).filter(move |&x| x != w);
+            let common = neighbors(u)
+                .filter(|&w| bitgraph.is_connected(v, w))
+                .count() as u64;
+            let degrees = (neighbors(u).count() + neighbors(v).count() - 2) as u64;
+            assert_eq!(result.matrix()[[row, edge]], 1);
+            assert_eq!(result.matrix()[[row, triangle]], common);
+            assert_eq!(result.matrix()[[row, path]], degrees - 2 * common);
+        }
+    }
+
+    #[test]
+    fn dir_yeast_edge_orbits() {
+        let graph: Graph<(), (), Directed> =
+            load_numeric_graph("example/yeast.txt", false).unwrap();
+        let serial = edge_orbit_counts(BitGraph::from_graph(&graph), 4, None).unwrap();
+        let pool = Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(4)
+                .build()
+                .unwrap(),
+        );
+        let parallel = edge_orbit_counts(BitGraph::from_graph(&graph), 4, Some(pool)).unwrap();
+        assert_eq!(serial.edges(), parallel.edges());
+        assert_eq!(serial.orbits(), parallel.orbits());
+        assert_eq!(serial.matrix(), parallel.matrix());
+
+        // Every subgraph is counted once for each of its arcs
+        let results = enumerate_subgraph_sizes(&graph, 2, 4).unwrap();
+        for (size, result) in results.iter() {
+            for (label, &count) in result.counts().iter() {
+                let total = serial
+                    .orbits()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (other, _))| other == label)
+                    .map(|(column, _)| serial.matrix().column(column).sum())
+                    .sum::<u64>();
+                let arcs = edge_orbits(&label.words(), *size, true).len() as u64;
+                assert_eq!(total, count as u64 * arcs);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_edge_orbits() {
+        let graph = BitGraph::from_edges(2, &[(0, 1)], false);
+        assert!(edge_orbit_counts(graph, 1, None).is_err());
+        let typed = BitGraph::from_typed_edges(2, &[(0, 1)], &[1], false);
+        assert!(edge_orbit_counts(typed, 3, None).is_err());
+    }
+}
@@ -1,71 +1,255 @@
 use crate::{
-    enumerate::{enumerate_subgraphs, EnumResult, Label},
+    enumerate::{EnumResult, Esu, Label, MemoCache, ParEsu, Sampling},
     switching::switching,
 };
+use anyhow::{bail, Result};
 use hashbrown::HashMap;
 use ndarray::Array1;
-use petgraph::{Directed, Graph};
+use petgraph::{EdgeType, Graph};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaChaRng;
+use rayon::{prelude::*, ThreadPool};
+use std::sync::Arc;
 
+/// The enrichment of every subgraph of a graph over random graphs.
+///
+/// Abundances are (estimated) subgraph counts, and concentrations are the
+/// abundances divided by the total abundance of all subgraphs of the same
+/// graph, which can be compared between graphs of different sizes.
 pub struct EnrichResult {
     pub subgraphs: Vec<Vec<u64>>,
-    pub abundances: Vec<usize>,
-    pub frequencies: Vec<f64>,
-    pub mean_random_frequency: Vec<f64>,
-    pub std_random_frequency: Vec<f64>,
+    pub abundances: Vec<f64>,
+    pub concentrations: Vec<f64>,
+    pub mean_random_abundance: Vec<f64>,
+    pub std_random_abundance: Vec<f64>,
+    pub mean_random_concentration: Vec<f64>,
+    pub std_random_concentration: Vec<f64>,
+    /// The z-scores of the abundances (zero if the random abundances do not vary).
     pub zscores: Vec<f64>,
+    /// The significance profile of Milo et al., i.e. the z-scores normalized
+    /// to unit length, which can be compared between graphs.
+    pub significance_profile: Vec<f64>,
+    /// The log2 fold change of the abundance over the mean random abundance
+    /// (with a pseudocount of 1).
+    pub log2_fold_changes: Vec<f64>,
+    /// The empirical p-values of over-representation.
+    pub p_over: Vec<f64>,
+    /// The empirical p-values of under-representation.
+    pub p_under: Vec<f64>,
+    /// The Benjamini-Hochberg q-values of over-representation across subgraphs.
+    pub q_over: Vec<f64>,
+    /// The Benjamini-Hochberg q-values of under-representation across subgraphs.
+    pub q_under: Vec<f64>,
 }
 impl EnrichResult {
     pub fn len(&self) -> usize {
         self.subgraphs.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.subgraphs.is_empty()
+    }
 }
 
-pub fn enrichment(
-    graph: &Graph<(), (), Directed>,
+/// Calculate the enrichment of all subgraphs of a given size in a (directed
+/// or undirected) graph against a background of degree-preserving random graphs.
+///
+/// Every graph draws its random numbers from its own stream of the seed, so
+/// the random graphs (and sampled counts) do not depend on the number of
+/// threads or the order in which the graphs are processed.
+///
+/// # Arguments
+/// * `graph` - The graph to calculate enrichment for.
+/// * `k` - The size of the subgraphs.
+/// * `num_random_graphs` - The number of random graphs to generate.
+/// * `q` - The number of switches per edge for each random graph.
+/// * `seed` - The seed for the random number generator.
+/// * `sample_probs` - Per-depth RAND-ESU probabilities to estimate the
+///   subgraph counts of every graph instead of enumerating them fully.
+/// * `pool` - The thread pool to generate and enumerate the random graphs in
+///   parallel on (serially if none is given). The original graph is
+///   enumerated with [`ParEsu`] on the pool.
+/// * `parallel_enumeration` - Whether every random graph is also enumerated
+///   with [`ParEsu`] on the pool (instead of [`Esu`] on a single thread),
+///   which balances the threads when there are fewer random graphs than threads.
+/// * `memo_cache` - The canonical labels memoized by earlier runs, which is
+///   extended with the labels of all graphs. The labels are shared between
+///   the graphs even if no cache is given.
+#[allow(clippy::too_many_arguments)]
+pub fn enrichment<Ty: EdgeType + Sync>(
+    graph: &Graph<(), (), Ty>,
     k: usize,
     num_random_graphs: usize,
     q: usize,
     seed: Option<usize>,
-) -> EnrichResult {
-    let original_results = enumerate_subgraphs(graph, k);
-    let mut rng = ChaChaRng::seed_from_u64(seed.unwrap_or(rand::random()) as u64);
+    sample_probs: Option<&[f64]>,
+    pool: Option<Arc<ThreadPool>>,
+    parallel_enumeration: bool,
+    memo_cache: Option<&mut MemoCache>,
+) -> Result<EnrichResult> {
+    if num_random_graphs == 0 {
+        bail!("ERROR: At least one random graph is required for the enrichment.");
+    }
+    let mut local_cache = MemoCache::default();
+    let memo = memo_cache.unwrap_or(&mut local_cache);
+    let seed = seed.unwrap_or_else(rand::random) as u64;
+    let mut rng = graph_rng(seed, 0);
+    let original_results = count_subgraphs(graph, k, sample_probs, pool.as_ref(), memo, &mut rng)?;
+    let keys = original_results.counts().keys().collect::<Vec<_>>();
+
+    let inner_pool = pool.as_ref().filter(|_| parallel_enumeration);
+    let random_abundances = |memo: &mut MemoCache, idx: usize| -> Result<(Vec<f64>, f64)> {
+        let mut rng = graph_rng(seed, idx as u64 + 1);
+        let random_graph = switching(graph, q, rng.gen())?;
+        let random_results =
+            count_subgraphs(&random_graph, k, sample_probs, inner_pool, memo, &mut rng)?;
+        let abundances = keys
+            .iter()
+            .map(|key| abundance(&random_results, key))
+            .collect();
+        Ok((abundances, total_abundance(&random_results)))
+    };
+
+    // Every split of the random graphs starts from the labels of the original
+    // graph, and the labels of all splits are merged afterwards.
+    let (abundances, random_memo) = match pool.as_ref() {
+        None => {
+            let mut memo = std::mem::take(memo);
+            let abundances = (0..num_random_graphs)
+                .map(|idx| Ok((idx, random_abundances(&mut memo, idx)?)))
+                .collect::<Result<Vec<_>>>()?;
+            (abundances, memo)
+        }
+        Some(pool) => {
+            let original_memo = std::mem::take(memo);
+            pool.install(|| {
+                (0..num_random_graphs)
+                    .into_par_iter()
+                    .try_fold(
+                        || (Vec::new(), original_memo.clone()),
+                        |(mut abundances, mut memo), idx| {
+                            abundances.push((idx, random_abundances(&mut memo, idx)?));
+                            Ok::<_, anyhow::Error>((abundances, memo))
+                        },
+                    )
+                    .try_reduce(
+                        || (Vec::new(), MemoCache::default()),
+                        |(mut abundances, mut memo), (other_abundances, other_memo)| {
+                            abundances.extend(other_abundances);
+                            memo.extend(other_memo);
+                            Ok((abundances, memo))
+                        },
+                    )
+            })?
+        }
+    };
+    *memo = random_memo;
+
     let mut null_map = initialize_null_map(&original_results, num_random_graphs);
+    let mut random_totals = Array1::zeros(num_random_graphs);
+    for (idx, (values, total)) in abundances {
+        for (key, value) in keys.iter().zip(values) {
+            null_map.get_mut(*key).unwrap()[idx] = value;
+        }
+        random_totals[idx] = total;
+    }
+
+    Ok(assemble_results(
+        &original_results,
+        null_map,
+        &random_totals,
+    ))
+}
+
+/// The random number generator of a graph of the enrichment, which is the
+/// original graph for index 0 and a random graph otherwise.
+fn graph_rng(seed: u64, index: u64) -> ChaChaRng {
+    let mut rng = ChaChaRng::seed_from_u64(seed);
+    rng.set_stream(index);
+    rng
+}
 
-    for idx in 0..num_random_graphs {
-        let random_seed = rng.gen();
-        let random_graph = switching(graph, q, random_seed);
-        let random_results = enumerate_subgraphs(&random_graph, k);
-        for key in original_results.counts().keys() {
-            if let Some(v) = random_results.counts().get(key) {
-                null_map.get_mut(key).unwrap()[idx] = *v as f64;
-            } else {
-                null_map.get_mut(key).unwrap()[idx] = 0.;
+/// Enumerate (or sample if probabilities are provided) all subgraphs of a given size.
+///
+/// The enumeration starts from the memoized labels, which are replaced by
+/// the memo of the enumeration afterwards.
+fn count_subgraphs<Ty: EdgeType + Sync>(
+    graph: &Graph<(), (), Ty>,
+    k: usize,
+    sample_probs: Option<&[f64]>,
+    pool: Option<&Arc<ThreadPool>>,
+    memo: &mut MemoCache,
+    rng: &mut ChaChaRng,
+) -> Result<EnumResult> {
+    let sampling = sample_probs
+        .map(|probs| Sampling::new(probs.to_vec(), rng.gen()))
+        .transpose()?;
+    match pool {
+        Some(pool) => {
+            let mut esu = ParEsu::new(k, graph)?;
+            esu.set_thread_pool(pool.clone());
+            if let Some(sampling) = sampling {
+                esu.set_sampling(sampling)?;
             }
+            esu.set_memo_cache(std::mem::take(memo))?;
+            esu.enumerate();
+            *memo = esu.memo_cache();
+            Ok(esu.result())
         }
+        None => {
+            let mut esu = Esu::new(k, graph)?;
+            if let Some(sampling) = sampling {
+                esu.set_sampling(sampling)?;
+            }
+            esu.set_memo_cache(std::mem::take(memo))?;
+            esu.enumerate();
+            *memo = esu.memo_cache();
+            Ok(esu.result())
+        }
+    }
+}
+
+/// The (estimated) abundance of a subgraph.
+fn abundance(results: &EnumResult, key: &Label) -> f64 {
+    match results.estimates() {
+        Some(estimates) => estimates.counts().get(key).map_or(0., |e| e.value),
+        None => results.counts().get(key).map_or(0., |&v| v as f64),
     }
+}
 
-    assemble_results(&original_results, null_map)
+/// The (estimated) total number of subgraphs.
+fn total_abundance(results: &EnumResult) -> f64 {
+    match results.estimates() {
+        Some(estimates) => estimates.total().value,
+        None => results.total_subgraphs() as f64,
+    }
 }
 
 fn assemble_results(
     original_results: &EnumResult,
     null_map: HashMap<&Label, Array1<f64>>,
+    random_totals: &Array1<f64>,
 ) -> EnrichResult {
-    let num_subgraphs = original_results.total_subgraphs();
+    let num_subgraphs = total_abundance(original_results);
     let num_unique = original_results.unique_subgraphs();
 
     let mut subgraphs = Vec::with_capacity(num_unique);
     let mut abundances = Vec::with_capacity(num_unique);
-    let mut frequencies = Vec::with_capacity(num_unique);
-    let mut mean_random_frequency = Vec::with_capacity(num_unique);
-    let mut std_random_frequency = Vec::with_capacity(num_unique);
+    let mut concentrations = Vec::with_capacity(num_unique);
+    let mut mean_random_abundance = Vec::with_capacity(num_unique);
+    let mut std_random_abundance = Vec::with_capacity(num_unique);
+    let mut mean_random_concentration = Vec::with_capacity(num_unique);
+    let mut std_random_concentration = Vec::with_capacity(num_unique);
     let mut zscores = Vec::with_capacity(num_unique);
+    let mut log2_fold_changes = Vec::with_capacity(num_unique);
+    let mut p_over = Vec::with_capacity(num_unique);
+    let mut p_under = Vec::with_capacity(num_unique);
+
+    for key in original_results.counts().keys() {
+        let abundance = abundance(original_results, key);
 
-    for (key, abundance) in original_results.counts() {
-        // Calculate the frequency of this subgraph in the original graph
-        let frequency = *abundance as f64 / num_subgraphs as f64;
+        // Calculate the concentration of this subgraph in the original graph
+        let concentration = abundance / num_subgraphs;
 
         // Get the null values for this subgraph
         let null_values = null_map.get(key).unwrap();
@@ -74,30 +258,99 @@ fn assemble_results(
         let mean = null_values.mean().unwrap();
         let std = null_values.std(0.0);
 
-        // Calculate the zscore and adjust to zero if infinite
-        let mut zscore = (*abundance as f64 - mean) / std;
-        if zscore.is_infinite() {
+        // Calculate the mean and std of the null concentrations
+        let null_concentrations = null_values
+            .iter()
+            .zip(random_totals.iter())
+            .map(|(&value, &total)| if total > 0. { value / total } else { 0. })
+            .collect::<Array1<f64>>();
+
+        // Calculate the zscore and adjust to zero if undefined
+        let mut zscore = (abundance - mean) / std;
+        if !zscore.is_finite() {
             zscore = 0.;
         }
 
-        subgraphs.push(key.to_vec());
-        abundances.push(*abundance);
-        frequencies.push(frequency);
+        // Calculate the one-sided empirical p-values
+        let (over, under) = empirical_p_values(abundance, null_values);
+
+        subgraphs.push(key.words().into_owned());
+        abundances.push(abundance);
+        concentrations.push(concentration);
         zscores.push(zscore);
-        mean_random_frequency.push(mean);
-        std_random_frequency.push(std);
+        mean_random_abundance.push(mean);
+        std_random_abundance.push(std);
+        mean_random_concentration.push(null_concentrations.mean().unwrap());
+        std_random_concentration.push(null_concentrations.std(0.0));
+        log2_fold_changes.push(((abundance + 1.) / (mean + 1.)).log2());
+        p_over.push(over);
+        p_under.push(under);
     }
 
+    let significance_profile = significance_profile(&zscores);
+    let q_over = benjamini_hochberg(&p_over);
+    let q_under = benjamini_hochberg(&p_under);
     EnrichResult {
         subgraphs,
         abundances,
-        frequencies,
-        mean_random_frequency,
-        std_random_frequency,
+        concentrations,
+        mean_random_abundance,
+        std_random_abundance,
+        mean_random_concentration,
+        std_random_concentration,
         zscores,
+        significance_profile,
+        log2_fold_changes,
+        p_over,
+        p_under,
+        q_over,
+        q_under,
     }
 }
 
+/// The z-scores normalized to unit length (or all zero if every z-score is).
+fn significance_profile(zscores: &[f64]) -> Vec<f64> {
+    let norm = zscores.iter().map(|z| z * z).sum::<f64>().sqrt();
+    if norm > 0. {
+        zscores.iter().map(|z| z / norm).collect()
+    } else {
+        vec![0.; zscores.len()]
+    }
+}
+
+/// The empirical p-values of over- and under-representation of an abundance,
+/// i.e. the fraction of random graphs with at least (or at most) the
+/// abundance.
+///
+/// The observed graph is counted as one of the random graphs, so that a
+/// p-value is never zero (and at least `1 / (n + 1)` for `n` random graphs).
+fn empirical_p_values(abundance: f64, null_values: &Array1<f64>) -> (f64, f64) {
+    let num_random = null_values.len() as f64;
+    let over = null_values.iter().filter(|&&v| v >= abundance).count() as f64;
+    let under = null_values.iter().filter(|&&v| v <= abundance).count() as f64;
+    (
+        (over + 1.) / (num_random + 1.),
+        (under + 1.) / (num_random + 1.),
+    )
+}
+
+/// The Benjamini-Hochberg q-values of p-values, which control the false
+/// discovery rate when all q-values below a threshold are reported.
+fn benjamini_hochberg(p_values: &[f64]) -> Vec<f64> {
+    let m = p_values.len() as f64;
+    let mut order = (0..p_values.len()).collect::<Vec<_>>();
+    order.sort_by(|&i, &j| p_values[i].total_cmp(&p_values[j]));
+
+    // The q-value of a rank is the smallest adjusted p-value of all larger ranks
+    let mut q_values = vec![0.; p_values.len()];
+    let mut running_min = 1f64;
+    for (rank, &idx) in order.iter().enumerate().rev() {
+        running_min = running_min.min(p_values[idx] * m / (rank + 1) as f64);
+        q_values[idx] = running_min;
+    }
+    q_values
+}
+
 fn initialize_null_map(
     results: &EnumResult,
 
@@ -109,3 +362,168 @@ fn initialize_null_map(
     }
     null_map
 }
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use crate::io::load_numeric_graph;
+    use petgraph::{Directed, Undirected};
+    use std::collections::BTreeMap;
+
+    type Summary = BTreeMap<Vec<u64>, (f64, f64, f64)>;
+
+    fn summary(results: EnrichResult) -> Summary {
+        (0..results.len())
+            .map(|i| {
+                let stats = (
+                    results.abundances[i],
+                    results.mean_random_abundance[i],
+                    results.std_random_abundance[i],
+                );
+                (results.subgraphs[i].clone(), stats)
+            })
+            .collect()
+    }
+
+    fn run<Ty: EdgeType + Sync>(
+        graph: &Graph<(), (), Ty>,
+        sample_probs: Option<&[f64]>,
+        threads: Option<usize>,
+        parallel_enumeration: bool,
+    ) -> Summary {
+        let pool = threads.map(|threads| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build();
+            Arc::new(pool.unwrap())
+        });
+        let results = enrichment(
+            graph,
+            3,
+            6,
+            1,
+            Some(7),
+            sample_probs,
+            pool,
+            parallel_enumeration,
+            None,
+        );
+        summary(results.unwrap())
+    }
+
+    #[test]
+    fn enrichment_independent_of_threads() {
+        let graph = load_numeric_graph::<Directed>("example/ecoli.txt", false).unwrap();
+        for sample_probs in [None, Some([1.0, 0.8, 0.5].as_slice())] {
+            let expected = run(&graph, sample_probs, None, false);
+            assert!(expected.values().any(|&(_, _, std)| std > 0.0));
+            assert_eq!(run(&graph, sample_probs, Some(1), false), expected);
+            assert_eq!(run(&graph, sample_probs, Some(3), false), expected);
+            assert_eq!(run(&graph, sample_probs, Some(3), true), expected);
+        }
+    }
+
+    #[test]
+    fn undirected_enrichment() {
+        let graph = load_numeric_graph::<Undirected>("example/ecoli.txt", false).unwrap();
+        let expected = run(&graph, None, None, false);
+        assert_eq!(run(&graph, None, Some(2), false), expected);
+
+        // The three nodes are a path or a triangle
+        assert_eq!(expected.len(), 2);
+        let total = expected
+            .values()
+            .map(|&(abundance, _, _)| abundance)
+            .sum::<f64>();
+        let mut esu = crate::Esu::new(3, &graph).unwrap();
+        esu.enumerate();
+        assert_eq!(total, esu.result().total_subgraphs() as f64);
+    }
+
+    #[test]
+    fn empirical_p_values_with_correction() {
+        let null_values = Array1::from(vec![0., 1., 1., 2., 5.]);
+        assert_eq!(empirical_p_values(1., &null_values), (5. / 6., 4. / 6.));
+        assert_eq!(empirical_p_values(6., &null_values), (1. / 6., 1.));
+        assert_eq!(empirical_p_values(-1., &null_values), (1., 1. / 6.));
+    }
+
+    #[test]
+    fn enrichment_concentrations() {
+        let graph = load_numeric_graph::<Directed>("example/ecoli.txt", false).unwrap();
+        let results = enrichment(&graph, 3, 4, 1, Some(3), None, None, false, None).unwrap();
+        let total = results.abundances.iter().sum::<f64>();
+        for i in 0..results.len() {
+            assert_eq!(results.concentrations[i], results.abundances[i] / total);
+            assert!(results.mean_random_concentration[i] <= 1.0);
+        }
+        let concentrations = results.concentrations.iter().sum::<f64>();
+        assert!((concentrations - 1.0).abs() < 1e-12);
+        let norm = results
+            .significance_profile
+            .iter()
+            .map(|sp| sp * sp)
+            .sum::<f64>();
+        assert!((norm - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn significance_profile_unit_length() {
+        let sp = significance_profile(&[3., -4., 0.]);
+        assert_eq!(sp, vec![0.6, -0.8, 0.]);
+        assert_eq!(significance_profile(&[0., 0.]), vec![0., 0.]);
+    }
+
+    #[test]
+    fn benjamini_hochberg_q_values() {
+        let q = benjamini_hochberg(&[0.01, 0.04, 0.03, 0.5]);
+        let expected = [0.04, 0.16 / 3., 0.16 / 3., 0.5];
+        for (q, expected) in q.iter().zip(expected) {
+            assert!((q - expected).abs() < 1e-12);
+        }
+        // The q-values are capped at 1
+        assert_eq!(benjamini_hochberg(&[1., 0.9]), vec![1., 1.]);
+        assert!(benjamini_hochberg(&[]).is_empty());
+    }
+
+    #[test]
+    fn enrichment_keeps_memo_cache() {
+        let graph = load_numeric_graph::<Directed>("example/ecoli.txt", false).unwrap();
+        let pool = Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(2)
+                .build()
+                .unwrap(),
+        );
+        let mut memo = MemoCache::default();
+        let results = enrichment(
+            &graph,
+            5,
+            3,
+            1,
+            Some(7),
+            None,
+            Some(pool),
+            false,
+            Some(&mut memo),
+        );
+        assert!(results.is_ok());
+        let size = memo.len();
+        assert!(size > 0);
+
+        // The labels of the random graphs are merged into the cache
+        let mut original = MemoCache::default();
+        enrichment(
+            &graph,
+            5,
+            1,
+            0,
+            Some(7),
+            None,
+            None,
+            false,
+            Some(&mut original),
+        )
+        .unwrap();
+        assert!(original.len() < size);
+        assert!(enrichment(&graph, 5, 0, 1, Some(7), None, None, false, None).is_err());
+    }
+}
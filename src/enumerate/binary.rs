@@ -0,0 +1,177 @@
+use super::Label;
+use anyhow::{bail, Result};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Write a binary file (such as a memo cache or checkpoint).
+///
+/// The file is first written to a temporary file which then replaces the
+/// file, so that an interrupted write keeps the previous file intact.
+pub(crate) fn save<F>(path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut BinaryWriter<BufWriter<File>>) -> Result<()>,
+{
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut writer = BinaryWriter::new(File::create(&tmp).map(BufWriter::new)?);
+    write(&mut writer)?;
+    writer.into_inner().into_inner()?.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Open a binary file for reading.
+pub(crate) fn open(path: &Path) -> std::io::Result<BinaryReader<BufReader<File>>> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    Ok(BinaryReader::new(BufReader::new(file), len))
+}
+
+/// Writes the little-endian words of a binary file.
+pub(crate) struct BinaryWriter<W: Write> {
+    inner: W,
+}
+impl<W: Write> BinaryWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    pub(crate) fn into_inner(self) -> W {
+        self.inner
+    }
+
+    pub(crate) fn put_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.inner.write_all(bytes)?;
+        Ok(())
+    }
+
+    pub(crate) fn put(&mut self, value: u64) -> Result<()> {
+        self.put_bytes(&value.to_le_bytes())
+    }
+
+    /// Write a string preceded by its length.
+    pub(crate) fn put_str(&mut self, value: &str) -> Result<()> {
+        self.put(value.len() as u64)?;
+        self.put_bytes(value.as_bytes())
+    }
+
+    /// Write the words of a label preceded by their number.
+    pub(crate) fn put_label(&mut self, label: &Label) -> Result<()> {
+        self.put(label.len() as u64)?;
+        for &word in label.words().iter() {
+            self.put(word)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the little-endian words of a binary file.
+///
+/// Every length read from the file is bounded by the number of remaining
+/// bytes, so that a corrupted file fails to read instead of allocating an
+/// arbitrary amount of memory.
+pub(crate) struct BinaryReader<R: Read> {
+    inner: R,
+    remaining: u64,
+}
+impl<R: Read> BinaryReader<R> {
+    /// A reader of the given number of bytes.
+    pub(crate) fn new(inner: R, len: u64) -> Self {
+        Self {
+            inner,
+            remaining: len,
+        }
+    }
+
+    fn get_bytes(&mut self, bytes: &mut [u8]) -> Result<()> {
+        if bytes.len() as u64 > self.remaining {
+            bail!("ERROR: Unexpected end of file.");
+        }
+        self.inner.read_exact(bytes)?;
+        self.remaining -= bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Read the first bytes of a file, which identify its format.
+    pub(crate) fn get_magic(&mut self) -> Result<[u8; 8]> {
+        let mut magic = [0; 8];
+        self.get_bytes(&mut magic)?;
+        Ok(magic)
+    }
+
+    pub(crate) fn get(&mut self) -> Result<u64> {
+        let mut bytes = [0; 8];
+        self.get_bytes(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// Read the number of the following items, each taking at least `size` bytes.
+    pub(crate) fn get_len(&mut self, size: u64) -> Result<usize> {
+        let len = self.get()?;
+        if len.saturating_mul(size) > self.remaining {
+            bail!("ERROR: The length {len} exceeds the remaining size of the file.");
+        }
+        Ok(len as usize)
+    }
+
+    /// Read a string preceded by its length.
+    pub(crate) fn get_string(&mut self) -> Result<String> {
+        let mut bytes = vec![0; self.get_len(1)?];
+        self.get_bytes(&mut bytes)?;
+        Ok(String::from_utf8(bytes)?)
+    }
+
+    /// Read the words of a label preceded by their number.
+    pub(crate) fn get_label(&mut self) -> Result<Label> {
+        let len = self.get_len(8)?;
+        (0..len).map(|_| self.get()).collect()
+    }
+}
+impl<'a> From<&'a [u8]> for BinaryReader<&'a [u8]> {
+    fn from(bytes: &'a [u8]) -> Self {
+        Self::new(bytes, bytes.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+
+    #[test]
+    fn binary_roundtrip() {
+        let label: Label = vec![1, u64::MAX].into();
+        let mut writer = BinaryWriter::new(Vec::new());
+        writer.put_bytes(b"MAGICNUM").unwrap();
+        writer.put(7).unwrap();
+        writer.put_str("dense-nauty").unwrap();
+        writer.put_label(&label).unwrap();
+        let bytes = writer.into_inner();
+
+        let mut reader = BinaryReader::from(bytes.as_slice());
+        assert_eq!(&reader.get_magic().unwrap(), b"MAGICNUM");
+        assert_eq!(reader.get().unwrap(), 7);
+        assert_eq!(reader.get_string().unwrap(), "dense-nauty");
+        assert_eq!(reader.get_label().unwrap(), label);
+        assert!(reader.get().is_err());
+    }
+
+    #[test]
+    fn bounded_lengths() {
+        // A length beyond the end of the file is rejected before allocating
+        let mut bytes = u64::MAX.to_le_bytes().to_vec();
+        bytes.extend(b"abc");
+        assert!(BinaryReader::from(bytes.as_slice()).get_string().is_err());
+        assert!(BinaryReader::from(bytes.as_slice()).get_label().is_err());
+        let mut bytes = 3u64.to_le_bytes().to_vec();
+        bytes.extend(b"abc");
+        assert_eq!(
+            BinaryReader::from(bytes.as_slice()).get_string().unwrap(),
+            "abc"
+        );
+        assert!(BinaryReader::from(&b"MEMO"[..]).get_magic().is_err());
+    }
+}
@@ -1,89 +1,317 @@
+use super::EdgeColor;
+use ahash::HashMap;
 use fixedbitset::FixedBitSet;
 use ndarray::Array2;
-use petgraph::{graph::NodeIndex, EdgeType, Graph};
+use petgraph::{visit::EdgeRef, EdgeType, Graph};
+
+/// Graphs with at most this many nodes always use the dense representation.
+const DENSE_MIN_NODES: usize = 2048;
+
+/// Graphs with more than this many nodes always use the sparse representation.
+const DENSE_MAX_NODES: usize = 8192;
+
+/// The minimum density (edges / nodes^2) for which larger graphs use the dense representation.
+const DENSE_MIN_DENSITY: f64 = 0.01;
+
+/// The memory layout of the adjacency of a [`BitGraph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Representation {
+    /// Bitset adjacency lists and an `n x n` adjacency matrix.
+    Dense,
+
+    /// Compressed sparse rows with sorted neighbor arrays.
+    Sparse,
+}
+impl Representation {
+    /// Choose a representation based on the number of nodes and the density of the graph.
+    pub fn auto(n: usize, num_edges: usize) -> Self {
+        let density = num_edges as f64 / (n as f64 * n as f64);
+        if n <= DENSE_MIN_NODES || (n <= DENSE_MAX_NODES && density >= DENSE_MIN_DENSITY) {
+            Self::Dense
+        } else {
+            Self::Sparse
+        }
+    }
+}
 
 /// A graph represented as a set of adjacency lists
 #[derive(Debug)]
 pub struct BitGraph {
-    /// Adjacency list for undirected (all) edges
-    u_adj: Vec<FixedBitSet>,
-
-    /// Adjacency list for directed (outgoing) edges
-    d_adj: Vec<FixedBitSet>,
-
-    /// Adjacency matrix
-    adj: Array2<bool>,
+    /// The adjacency of the graph
+    adjacency: Adjacency,
 
     /// Number of nodes in the graph
     pub n: usize,
 
+    /// Number of (unique) edges in the graph
+    pub e: usize,
+
     /// Is directed
     pub is_directed: bool,
+
+    /// The type (color) of every edge, if the edges are typed
+    edge_colors: Option<EdgeColors>,
 }
 impl BitGraph {
     pub fn from_graph<N, E, Ty: EdgeType>(graph: &Graph<N, E, Ty>) -> Self {
-        let n = graph.node_count();
-        let mut u_adj = Vec::with_capacity(n);
-        let mut d_adj = Vec::with_capacity(n);
-        let mut adj = Array2::from_elem((n, n), false);
-        for v in 0..n {
-            let v_index = NodeIndex::new(v);
-            let u_neighbors = undirected_neighbors(graph, v_index);
-            let d_neighbors = directed_neighbors(graph, v_index);
+        let edges = graph
+            .edge_references()
+            .map(|e| (e.source().index() as u32, e.target().index() as u32))
+            .collect::<Vec<_>>();
+        Self::from_edges(graph.node_count(), &edges, Ty::is_directed())
+    }
 
-            for n in d_neighbors.ones() {
-                adj[[v, n]] = true;
-            }
+    /// Build a graph from a 0-indexed edgelist choosing the representation
+    /// automatically by density.
+    pub fn from_edges(n: usize, edges: &[(u32, u32)], is_directed: bool) -> Self {
+        let representation = Representation::auto(n, edges.len());
+        Self::from_edges_with(n, edges, is_directed, representation)
+    }
 
-            u_adj.push(u_neighbors);
-            d_adj.push(d_neighbors);
-        }
+    /// Build a graph from a 0-indexed edgelist with a specific representation.
+    pub fn from_edges_with(
+        n: usize,
+        edges: &[(u32, u32)],
+        is_directed: bool,
+        representation: Representation,
+    ) -> Self {
+        let arcs = directed_arcs(edges, is_directed);
+        let e = if is_directed {
+            arcs.len()
+        } else {
+            arcs.iter().filter(|(u, v)| u <= v).count()
+        };
+        let adjacency = match representation {
+            Representation::Dense => Adjacency::Dense(DenseAdjacency::new(n, &arcs)),
+            Representation::Sparse => Adjacency::Sparse(SparseAdjacency::new(n, &arcs)),
+        };
         Self {
-            u_adj,
-            d_adj,
-            adj,
+            adjacency,
             n,
-            is_directed: Ty::is_directed(),
+            e,
+            is_directed,
+            edge_colors: None,
         }
     }
 
-    pub fn neighbors(&self, v: usize) -> &FixedBitSet {
-        &self.u_adj[v]
+    /// Build a graph from a 0-indexed edgelist where every edge has a type (color),
+    /// choosing the representation automatically by density.
+    ///
+    /// If an edge is given multiple times the last type is used.
+    pub fn from_typed_edges(
+        n: usize,
+        edges: &[(u32, u32)],
+        colors: &[EdgeColor],
+        is_directed: bool,
+    ) -> Self {
+        let mut graph = Self::from_edges(n, edges, is_directed);
+        let edge_colors = edges
+            .iter()
+            .zip(colors)
+            .map(|(&(u, v), &color)| (edge_key(u, v, is_directed), color))
+            .collect::<HashMap<_, _>>();
+        let max_color = edge_colors.values().copied().max().unwrap_or(0);
+        graph.edge_colors = Some(EdgeColors {
+            colors: edge_colors,
+            max_color,
+        });
+        graph
+    }
+
+    /// Whether the edges of the graph have types (colors).
+    pub fn is_typed(&self) -> bool {
+        self.edge_colors.is_some()
     }
 
-    #[allow(dead_code)]
-    pub fn neighbors_directed(&self, v: usize) -> &FixedBitSet {
-        &self.d_adj[v]
+    /// The type (color) of the edge between two nodes (from `u` to `v` for directed graphs).
+    pub fn edge_color(&self, u: usize, v: usize) -> Option<EdgeColor> {
+        let edge_colors = self.edge_colors.as_ref()?;
+        let key = edge_key(u as u32, v as u32, self.is_directed);
+        edge_colors.colors.get(&key).copied()
     }
 
-    #[allow(dead_code)]
-    pub fn neighbors_directed_unchecked(&self, v: usize) -> &FixedBitSet {
-        unsafe { self.d_adj.get_unchecked(v) }
+    /// The largest type (color) of any edge in the graph.
+    pub fn max_edge_color(&self) -> Option<EdgeColor> {
+        self.edge_colors.as_ref().map(|c| c.max_color)
+    }
+
+    /// Whether any node of the graph connects to itself.
+    pub fn has_loops(&self) -> bool {
+        (0..self.n).any(|v| self.is_connected_directed(v, v))
+    }
+
+    pub fn representation(&self) -> Representation {
+        match self.adjacency {
+            Adjacency::Dense(_) => Representation::Dense,
+            Adjacency::Sparse(_) => Representation::Sparse,
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.n
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.e
+    }
+
+    /// Iterate over the undirected (all) neighbors of a node in ascending order.
+    pub fn neighbors(&self, v: usize) -> Neighbors<'_> {
+        match &self.adjacency {
+            Adjacency::Dense(adj) => Neighbors::Dense(adj.u_adj[v].ones()),
+            Adjacency::Sparse(adj) => Neighbors::Sparse(adj.u_adj.row(v).iter()),
+        }
+    }
+
+    /// Iterate over the directed (outgoing) neighbors of a node in ascending order.
+    pub fn neighbors_directed(&self, v: usize) -> Neighbors<'_> {
+        match &self.adjacency {
+            Adjacency::Dense(adj) => Neighbors::Dense(adj.d_adj[v].ones()),
+            Adjacency::Sparse(adj) => Neighbors::Sparse(adj.d_adj.row(v).iter()),
+        }
     }
 
     pub fn is_connected(&self, u: usize, v: usize) -> bool {
-        unsafe { *self.adj.uget((u, v)) || *self.adj.uget((v, u)) }
+        match &self.adjacency {
+            Adjacency::Dense(adj) => unsafe { *adj.adj.uget((u, v)) || *adj.adj.uget((v, u)) },
+            Adjacency::Sparse(adj) => adj.u_adj.contains(u, v),
+        }
     }
 
     pub fn is_connected_directed(&self, u: usize, v: usize) -> bool {
-        unsafe { *self.adj.uget((u, v)) }
+        match &self.adjacency {
+            Adjacency::Dense(adj) => unsafe { *adj.adj.uget((u, v)) },
+            Adjacency::Sparse(adj) => adj.d_adj.contains(u, v),
+        }
     }
 }
 
-fn undirected_neighbors<N, E, Ty: EdgeType>(graph: &Graph<N, E, Ty>, v: NodeIndex) -> FixedBitSet {
-    let mut bv = FixedBitSet::with_capacity(graph.node_count());
-    for neighbor in graph.neighbors_undirected(v) {
-        bv.insert(neighbor.index());
+/// The types (colors) of the edges of a graph.
+#[derive(Debug)]
+struct EdgeColors {
+    colors: HashMap<(u32, u32), EdgeColor>,
+    max_color: EdgeColor,
+}
+
+/// The lookup key of an edge, which is independent of the edge direction for undirected graphs.
+fn edge_key(u: u32, v: u32, is_directed: bool) -> (u32, u32) {
+    if is_directed {
+        (u, v)
+    } else {
+        (u.min(v), u.max(v))
     }
-    bv
 }
 
-fn directed_neighbors<N, E, Ty: EdgeType>(graph: &Graph<N, E, Ty>, v: NodeIndex) -> FixedBitSet {
-    let mut bv = FixedBitSet::with_capacity(graph.node_count());
-    for neighbor in graph.neighbors(v) {
-        bv.insert(neighbor.index());
+#[derive(Debug)]
+enum Adjacency {
+    Dense(DenseAdjacency),
+    Sparse(SparseAdjacency),
+}
+
+#[derive(Debug)]
+struct DenseAdjacency {
+    /// Adjacency list for undirected (all) edges
+    u_adj: Vec<FixedBitSet>,
+
+    /// Adjacency list for directed (outgoing) edges
+    d_adj: Vec<FixedBitSet>,
+
+    /// Adjacency matrix
+    adj: Array2<bool>,
+}
+impl DenseAdjacency {
+    fn new(n: usize, arcs: &[(u32, u32)]) -> Self {
+        let mut u_adj = vec![FixedBitSet::with_capacity(n); n];
+        let mut d_adj = vec![FixedBitSet::with_capacity(n); n];
+        let mut adj = Array2::from_elem((n, n), false);
+        for &(u, v) in arcs {
+            let (u, v) = (u as usize, v as usize);
+            d_adj[u].insert(v);
+            u_adj[u].insert(v);
+            u_adj[v].insert(u);
+            adj[[u, v]] = true;
+        }
+        Self { u_adj, d_adj, adj }
+    }
+}
+
+#[derive(Debug)]
+struct SparseAdjacency {
+    /// Adjacency list for undirected (all) edges
+    u_adj: Csr,
+
+    /// Adjacency list for directed (outgoing) edges
+    d_adj: Csr,
+}
+impl SparseAdjacency {
+    fn new(n: usize, arcs: &[(u32, u32)]) -> Self {
+        let reversed = arcs.iter().map(|&(u, v)| (v, u));
+        let undirected = arcs.iter().copied().chain(reversed).collect::<Vec<_>>();
+        Self {
+            u_adj: Csr::new(n, undirected),
+            d_adj: Csr::new(n, arcs.to_vec()),
+        }
+    }
+}
+
+/// Compressed sparse row adjacency with sorted and deduplicated rows.
+#[derive(Debug)]
+struct Csr {
+    offsets: Vec<usize>,
+    targets: Vec<u32>,
+}
+impl Csr {
+    fn new(n: usize, mut arcs: Vec<(u32, u32)>) -> Self {
+        arcs.sort_unstable();
+        arcs.dedup();
+        let mut offsets = vec![0; n + 1];
+        for &(u, _) in arcs.iter() {
+            offsets[u as usize + 1] += 1;
+        }
+        for i in 0..n {
+            offsets[i + 1] += offsets[i];
+        }
+        let targets = arcs.into_iter().map(|(_, v)| v).collect();
+        Self { offsets, targets }
+    }
+
+    fn row(&self, v: usize) -> &[u32] {
+        &self.targets[self.offsets[v]..self.offsets[v + 1]]
+    }
+
+    fn contains(&self, u: usize, v: usize) -> bool {
+        self.row(u).binary_search(&(v as u32)).is_ok()
+    }
+}
+
+/// An iterator over the neighbors of a node in ascending order.
+pub enum Neighbors<'a> {
+    Dense(fixedbitset::Ones<'a>),
+    Sparse(std::slice::Iter<'a, u32>),
+}
+impl Iterator for Neighbors<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        match self {
+            Self::Dense(iter) => iter.next(),
+            Self::Sparse(iter) => iter.next().map(|&v| v as usize),
+        }
+    }
+}
+
+/// Deduplicate the edges as directed arcs (adding the reverse arc for undirected graphs).
+fn directed_arcs(edges: &[(u32, u32)], is_directed: bool) -> Vec<(u32, u32)> {
+    let mut arcs = Vec::with_capacity(if is_directed { 1 } else { 2 } * edges.len());
+    for &(u, v) in edges {
+        arcs.push((u, v));
+        if !is_directed && u != v {
+            arcs.push((v, u));
+        }
     }
-    bv
+    arcs.sort_unstable();
+    arcs.dedup();
+    arcs
 }
 
 #[cfg(test)]
@@ -94,34 +322,52 @@ mod testing {
 
     fn build_directed_graph() -> Graph<(), (), Directed> {
         let edges = [(0, 1), (0, 2), (0, 3), (1, 2), (2, 3)];
-        Graph::from_edges(&edges)
+        Graph::from_edges(edges)
     }
 
     fn build_undirected_graph() -> Graph<(), (), Undirected> {
         let edges = [(0, 1), (0, 2), (0, 3), (1, 2), (2, 3)];
-        Graph::from_edges(&edges)
+        Graph::from_edges(edges)
+    }
+
+    fn representations() -> [Representation; 2] {
+        [Representation::Dense, Representation::Sparse]
     }
 
     #[test]
     fn test_undirected_neighbors() {
-        let graph = build_undirected_graph();
-        let v = NodeIndex::new(1);
-        let neighbors = undirected_neighbors(&graph, v);
-        assert_eq!(neighbors.len(), 4);
-        assert_eq!(neighbors.count_ones(..), 2);
-        assert!(neighbors.contains(0));
-        assert!(neighbors.contains(2));
+        let edges = [(0, 1), (0, 2), (0, 3), (1, 2), (2, 3)];
+        for representation in representations() {
+            let bitgraph = BitGraph::from_edges_with(4, &edges, false, representation);
+            let neighbors = bitgraph.neighbors(1).collect::<Vec<_>>();
+            assert_eq!(neighbors, vec![0, 2]);
+        }
     }
 
     #[test]
     fn test_directed_neighbors() {
-        let graph = build_directed_graph();
-        let v = NodeIndex::new(1);
-        let neighbors = directed_neighbors(&graph, v);
-        assert_eq!(neighbors.len(), 4);
-        assert_eq!(neighbors.count_ones(..), 1);
-        assert!(!neighbors.contains(0));
-        assert!(neighbors.contains(2));
+        let edges = [(0, 1), (0, 2), (0, 3), (1, 2), (2, 3)];
+        for representation in representations() {
+            let bitgraph = BitGraph::from_edges_with(4, &edges, true, representation);
+            let neighbors = bitgraph.neighbors_directed(1).collect::<Vec<_>>();
+            assert_eq!(neighbors, vec![2]);
+            let neighbors = bitgraph.neighbors(1).collect::<Vec<_>>();
+            assert_eq!(neighbors, vec![0, 2]);
+        }
+    }
+
+    #[test]
+    fn test_has_loops() {
+        for representation in representations() {
+            for is_directed in [true, false] {
+                let edges = [(0, 1), (1, 2)];
+                let bitgraph = BitGraph::from_edges_with(3, &edges, is_directed, representation);
+                assert!(!bitgraph.has_loops());
+                let edges = [(0, 1), (2, 2)];
+                let bitgraph = BitGraph::from_edges_with(3, &edges, is_directed, representation);
+                assert!(bitgraph.has_loops());
+            }
+        }
     }
 
     #[test]
@@ -129,9 +375,12 @@ mod testing {
         let graph = build_directed_graph();
         let bitgraph = BitGraph::from_graph(&graph);
         assert_eq!(bitgraph.n, 4);
-        assert_eq!(bitgraph.u_adj.len(), 4);
-        assert_eq!(bitgraph.d_adj.len(), 4);
-        assert_ne!(bitgraph.neighbors(1), bitgraph.neighbors_directed(1));
+        assert_eq!(bitgraph.edge_count(), 5);
+        assert_eq!(bitgraph.representation(), Representation::Dense);
+        assert_ne!(
+            bitgraph.neighbors(1).collect::<Vec<_>>(),
+            bitgraph.neighbors_directed(1).collect::<Vec<_>>()
+        );
     }
 
     #[test]
@@ -139,8 +388,58 @@ mod testing {
         let graph = build_undirected_graph();
         let bitgraph = BitGraph::from_graph(&graph);
         assert_eq!(bitgraph.n, 4);
-        assert_eq!(bitgraph.u_adj.len(), 4);
-        assert_eq!(bitgraph.d_adj.len(), 4);
-        assert_eq!(bitgraph.neighbors(1), bitgraph.neighbors_directed(1));
+        assert_eq!(bitgraph.edge_count(), 5);
+        assert_eq!(
+            bitgraph.neighbors(1).collect::<Vec<_>>(),
+            bitgraph.neighbors_directed(1).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_representations_agree() {
+        let edges = [
+            (0, 1),
+            (1, 0),
+            (0, 2),
+            (3, 0),
+            (1, 2),
+            (2, 3),
+            (3, 3),
+            (1, 2),
+        ];
+        for is_directed in [true, false] {
+            let dense = BitGraph::from_edges_with(5, &edges, is_directed, Representation::Dense);
+            let sparse = BitGraph::from_edges_with(5, &edges, is_directed, Representation::Sparse);
+            assert_eq!(dense.edge_count(), sparse.edge_count());
+            for u in 0..5 {
+                assert_eq!(
+                    dense.neighbors(u).collect::<Vec<_>>(),
+                    sparse.neighbors(u).collect::<Vec<_>>()
+                );
+                assert_eq!(
+                    dense.neighbors_directed(u).collect::<Vec<_>>(),
+                    sparse.neighbors_directed(u).collect::<Vec<_>>()
+                );
+                for v in 0..5 {
+                    assert_eq!(dense.is_connected(u, v), sparse.is_connected(u, v));
+                    assert_eq!(
+                        dense.is_connected_directed(u, v),
+                        sparse.is_connected_directed(u, v)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_auto_representation() {
+        assert_eq!(Representation::auto(100, 10), Representation::Dense);
+        assert_eq!(Representation::auto(2000, 10), Representation::Dense);
+        assert_eq!(Representation::auto(5000, 1000), Representation::Sparse);
+        assert_eq!(Representation::auto(5000, 500_000), Representation::Dense);
+        assert_eq!(
+            Representation::auto(500_000, 10_000_000),
+            Representation::Sparse
+        );
     }
 }
@@ -0,0 +1,271 @@
+use super::Nodes;
+use std::any::TypeId;
+
+/// The canonicalizer used unless another backend is chosen.
+#[cfg(feature = "nauty")]
+pub type DefaultCanonicalizer = super::nauty::DenseNauty;
+
+/// The canonicalizer used unless another backend is chosen.
+#[cfg(not(feature = "nauty"))]
+pub type DefaultCanonicalizer = Exhaustive;
+
+/// A backend that computes the canonical labeling of a [`NautyGraph`](super::NautyGraph).
+///
+/// The graph is given in the dense format of nauty: `m` words per vertex
+/// whose bits (starting from the most significant bit) mark the out-neighbors
+/// of the vertex. If `partition` is set, the `lab` and `ptn` of the nodes
+/// hold the ordered initial partition of the vertices, otherwise all vertices
+/// start in a single cell.
+///
+/// After canonicalization `lab` holds the vertex at every canonical position,
+/// `orbits` the smallest vertex in the orbit of every vertex under the
+/// automorphism group, and `canon` the graph relabeled by `lab`.
+///
+/// The canonical forms of different backends are not interchangeable, so
+/// every label of an enumeration must come from the same backend.
+pub trait Canonicalizer: Default + Send + 'static {
+    /// The name of the backend.
+    const NAME: &'static str;
+
+    /// Whether the backend supports directed graphs.
+    const SUPPORTS_DIRECTED: bool = true;
+
+    /// Canonicalize the graph and return the size of its automorphism group.
+    fn canonicalize(
+        &mut self,
+        graph: &mut [u64],
+        m: usize,
+        is_directed: bool,
+        partition: bool,
+        nodes: &mut Nodes,
+        canon: &mut [u64],
+    ) -> f64;
+}
+
+/// Whether the backend is the default canonicalizer, whose labels are shared
+/// with queries, lookup tables, and inclusion matrices.
+pub(crate) fn is_default<C: Canonicalizer>() -> bool {
+    TypeId::of::<C>() == TypeId::of::<DefaultCanonicalizer>()
+}
+
+/// A pure Rust canonicalizer, for builds without the nauty C library.
+///
+/// The vertices are individualized one at a time (followed by color
+/// refinement) until the partition is discrete, and the canonical labeling is
+/// the leaf of the search tree with the largest relabeled graph. As the
+/// search tree is not pruned by automorphisms, it is only practical for the
+/// small graphs of subgraph enumeration.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Exhaustive;
+
+impl Canonicalizer for Exhaustive {
+    const NAME: &'static str = "exhaustive";
+
+    fn canonicalize(
+        &mut self,
+        graph: &mut [u64],
+        m: usize,
+        _is_directed: bool,
+        partition: bool,
+        nodes: &mut Nodes,
+        canon: &mut [u64],
+    ) -> f64 {
+        let n = nodes.lab.len();
+        let mut cells = Vec::new();
+        if partition {
+            let mut cell = Vec::new();
+            for (&v, &ptn) in nodes.lab.iter().zip(nodes.ptn.iter()) {
+                cell.push(v as usize);
+                if ptn == 0 {
+                    cells.push(std::mem::take(&mut cell));
+                }
+            }
+        } else if n > 0 {
+            cells.push((0..n).collect());
+        }
+
+        let mut search = Search {
+            graph,
+            m,
+            best: None,
+            group_size: 0.0,
+            parent: (0..n).collect(),
+        };
+        let cells = search.refine(cells);
+        search.descend(cells);
+
+        let (words, lab) = search.best.take().unwrap_or_default();
+        canon.copy_from_slice(&words);
+        for (position, v) in lab.into_iter().enumerate() {
+            nodes.lab[position] = v as i32;
+            nodes.ptn[position] = 0;
+        }
+        for v in 0..n {
+            nodes.orbits[v] = search.find(v) as i32;
+        }
+        search.group_size.max(1.0)
+    }
+}
+
+/// The state of the search for the canonical labeling of [`Exhaustive`].
+struct Search<'a> {
+    graph: &'a [u64],
+    m: usize,
+    /// The largest relabeled graph found so far and its labeling.
+    best: Option<(Vec<u64>, Vec<usize>)>,
+    /// The number of leaves with the largest relabeled graph.
+    group_size: f64,
+    /// The union-find forest of the orbits, rooted at the smallest vertex.
+    parent: Vec<usize>,
+}
+impl Search<'_> {
+    fn has_arc(&self, u: usize, v: usize) -> bool {
+        self.graph[u * self.m + v / 64] >> (63 - v % 64) & 1 == 1
+    }
+
+    /// Split the cells by the number of out- and in-neighbors of every vertex
+    /// in every cell until the partition is equitable.
+    fn refine(&self, mut cells: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+        let n = self.parent.len();
+        let mut cell_of = vec![0; n];
+        loop {
+            for (idx, cell) in cells.iter().enumerate() {
+                cell.iter().for_each(|&v| cell_of[v] = idx);
+            }
+            let signature = |v: usize| {
+                let mut counts = vec![0usize; 2 * cells.len()];
+                for u in 0..n {
+                    if self.has_arc(v, u) {
+                        counts[2 * cell_of[u]] += 1;
+                    }
+                    if self.has_arc(u, v) {
+                        counts[2 * cell_of[u] + 1] += 1;
+                    }
+                }
+                counts
+            };
+            let mut refined = Vec::with_capacity(cells.len());
+            for cell in cells.iter() {
+                if cell.len() == 1 {
+                    refined.push(cell.clone());
+                    continue;
+                }
+                let mut keyed = cell.iter().map(|&v| (signature(v), v)).collect::<Vec<_>>();
+                keyed.sort_unstable();
+                for (idx, (key, v)) in keyed.iter().enumerate() {
+                    if idx == 0 || keyed[idx - 1].0 != *key {
+                        refined.push(Vec::new());
+                    }
+                    refined.last_mut().unwrap().push(*v);
+                }
+            }
+            if refined.len() == cells.len() {
+                return refined;
+            }
+            cells = refined;
+        }
+    }
+
+    /// Individualize every vertex of the first non-trivial cell in turn.
+    fn descend(&mut self, cells: Vec<Vec<usize>>) {
+        let Some(target) = cells.iter().position(|cell| cell.len() > 1) else {
+            let lab = cells.into_iter().flatten().collect::<Vec<_>>();
+            self.leaf(lab);
+            return;
+        };
+        for &v in cells[target].iter() {
+            let mut individualized = Vec::with_capacity(cells.len() + 1);
+            individualized.extend(cells[..target].iter().cloned());
+            individualized.push(vec![v]);
+            individualized.push(cells[target].iter().copied().filter(|&u| u != v).collect());
+            individualized.extend(cells[target + 1..].iter().cloned());
+            let refined = self.refine(individualized);
+            self.descend(refined);
+        }
+    }
+
+    /// Compare the graph relabeled by a discrete partition to the best so far.
+    fn leaf(&mut self, lab: Vec<usize>) {
+        let n = lab.len();
+        let mut words = vec![0; self.m * n];
+        for (i, &u) in lab.iter().enumerate() {
+            for (j, &v) in lab.iter().enumerate() {
+                if self.has_arc(u, v) {
+                    words[i * self.m + j / 64] |= 1 << (63 - j % 64);
+                }
+            }
+        }
+        match &self.best {
+            Some((best, _)) if *best > words => {}
+
+            // Labelings with the same relabeled graph differ by an automorphism
+            Some((best, best_lab)) if *best == words => {
+                let automorphism = best_lab.iter().zip(lab.iter());
+                let pairs = automorphism.map(|(&u, &v)| (u, v)).collect::<Vec<_>>();
+                pairs.into_iter().for_each(|(u, v)| self.union(u, v));
+                self.group_size += 1.0;
+            }
+            _ => {
+                self.best = Some((words, lab));
+                self.group_size = 1.0;
+            }
+        }
+    }
+
+    fn find(&mut self, v: usize) -> usize {
+        let mut root = v;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        self.parent[v] = root;
+        root
+    }
+
+    fn union(&mut self, u: usize, v: usize) {
+        let (u, v) = (self.find(u), self.find(v));
+        self.parent[u.max(v)] = u.min(v);
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use crate::enumerate::arcs_graph;
+
+    #[test]
+    fn exhaustive_isomorphic_graphs() {
+        // Two labelings of a directed path with a loop
+        let a = arcs_graph::<Exhaustive>(4, &[(0, 1), (1, 2), (2, 3), (3, 3)], true);
+        let b = arcs_graph::<Exhaustive>(4, &[(3, 2), (2, 0), (0, 1), (1, 1)], true);
+        assert_eq!(a.canon(), b.canon());
+        let reversed = arcs_graph::<Exhaustive>(4, &[(1, 0), (2, 1), (3, 2), (3, 3)], true);
+        assert_ne!(a.canon(), reversed.canon());
+
+        // The canonical graph is the graph relabeled by the canonical labeling
+        let lab = a.nodes.lab.iter().map(|&v| v as usize).collect::<Vec<_>>();
+        let arcs = [(0, 1), (1, 2), (2, 3), (3, 3)];
+        for i in 0..4 {
+            for j in 0..4 {
+                let arc = a.canon()[i] >> (63 - j) & 1 == 1;
+                assert_eq!(arc, arcs.contains(&(lab[i], lab[j])));
+            }
+        }
+    }
+
+    #[test]
+    fn exhaustive_orbits() {
+        // The cycle is vertex transitive with the dihedral group
+        let cycle = arcs_graph::<Exhaustive>(5, &[(0, 1), (1, 2), (2, 3), (3, 4), (4, 0)], false);
+        assert_eq!(cycle.group_size, 10.0);
+        assert!(cycle.nodes.orbits.iter().all(|&orbit| orbit == 0));
+
+        // The star has the center and the leaves as orbits
+        let star = arcs_graph::<Exhaustive>(4, &[(1, 0), (1, 2), (1, 3)], false);
+        assert_eq!(star.group_size, 6.0);
+        assert_eq!(star.nodes.orbits, vec![0, 1, 0, 0]);
+
+        // The directed cycle only has the rotations
+        let directed = arcs_graph::<Exhaustive>(3, &[(0, 1), (1, 2), (2, 0)], true);
+        assert_eq!(directed.group_size, 3.0);
+    }
+}
@@ -0,0 +1,358 @@
+use super::{
+    binary::{self, BinaryReader, BinaryWriter},
+    BitGraph, Color, Counts, MemoKey, Shard,
+};
+use anyhow::{bail, Context, Result};
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// The first bytes of a checkpoint file.
+const MAGIC: &[u8; 8] = b"MEMOESU\0";
+
+/// The version of the checkpoint format.
+const VERSION: u64 = 4;
+
+/// The partial counts of an enumeration and the roots of the ESU tree that
+/// are already finished, so that an interrupted enumeration can be resumed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    key: MemoKey,
+    min_size: usize,
+    nodes: usize,
+    edges: usize,
+    shard: Option<Shard>,
+    colors: u64,
+    done: Vec<bool>,
+    counts: Vec<Counts>,
+    totals: Vec<usize>,
+}
+impl Checkpoint {
+    /// An empty checkpoint of an enumeration of the given graph, minimum
+    /// size, shard, and node colors, whose other parameters are those that
+    /// also determine its memoized labels.
+    pub(crate) fn new(
+        graph: &BitGraph,
+        min_size: usize,
+        key: MemoKey,
+        shard: Option<Shard>,
+        colors: Option<&[Color]>,
+    ) -> Self {
+        let motif_size = key.motif_size();
+        Self {
+            key,
+            min_size,
+            nodes: graph.n,
+            edges: graph.e,
+            shard,
+            colors: hash_colors(colors),
+            done: vec![false; graph.n],
+            counts: vec![Counts::default(); motif_size],
+            totals: vec![0; motif_size],
+        }
+    }
+
+    /// The number of roots that are finished.
+    pub fn roots_done(&self) -> usize {
+        self.done.iter().filter(|&&done| done).count()
+    }
+
+    /// The number of subgraphs counted in the finished roots.
+    pub fn total_subgraphs(&self) -> usize {
+        self.totals.iter().sum()
+    }
+
+    /// Validate that the checkpoint belongs to an enumeration with the same
+    /// parameters.
+    pub(crate) fn validate(
+        &self,
+        graph: &BitGraph,
+        min_size: usize,
+        key: &MemoKey,
+        shard: Option<Shard>,
+        colors: Option<&[Color]>,
+    ) -> Result<()> {
+        if self.nodes != graph.n || self.edges != graph.e {
+            bail!(
+                "ERROR: The checkpoint was written for a different graph ({} nodes and {} edges).",
+                self.nodes,
+                self.edges
+            );
+        }
+        if self.min_size != min_size || self.key.motif_size() != key.motif_size() {
+            bail!(
+                "ERROR: The checkpoint was written for subgraph sizes {} to {}.",
+                self.min_size,
+                self.key.motif_size()
+            );
+        }
+        if self.shard != shard {
+            bail!(
+                "ERROR: The checkpoint was written for {}.",
+                self.shard
+                    .map_or_else(|| "all roots".to_string(), |s| format!("shard {s}"))
+            );
+        }
+        if &self.key != key {
+            bail!(
+                "ERROR: The checkpoint was written for {} but the enumeration is for {key}.",
+                self.key
+            );
+        }
+        if self.colors != hash_colors(colors) {
+            bail!("ERROR: The checkpoint was written for different node colors.");
+        }
+        Ok(())
+    }
+
+    /// Take the partial counts and totals of every size.
+    pub(crate) fn into_parts(self) -> (Vec<bool>, Vec<Counts>, Vec<usize>) {
+        (self.done, self.counts, self.totals)
+    }
+
+    /// Record the finished roots along with the counts and totals of all
+    /// finished roots so far.
+    pub(crate) fn update(&mut self, done: &[bool], counts: &[Counts], totals: &[usize]) {
+        self.done.copy_from_slice(done);
+        self.counts = counts.to_vec();
+        self.totals = totals.to_vec();
+    }
+
+    /// Load a checkpoint from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut reader = binary::open(path)
+            .with_context(|| format!("ERROR: Unable to open checkpoint: {}", path.display()))?;
+        Self::read(&mut reader)
+            .with_context(|| format!("ERROR: Unable to read checkpoint: {}", path.display()))
+    }
+
+    /// Write the checkpoint to a file (through a temporary file).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        binary::save(path.as_ref(), |writer| self.write(writer))
+    }
+
+    fn write<W: Write>(&self, writer: &mut BinaryWriter<W>) -> Result<()> {
+        writer.put_bytes(MAGIC)?;
+        writer.put(VERSION)?;
+        self.key.write(writer)?;
+        for value in [
+            self.min_size,
+            self.nodes,
+            self.edges,
+            self.shard.map_or(0, |s| s.index()),
+            self.shard.map_or(0, |s| s.count()),
+        ] {
+            writer.put(value as u64)?;
+        }
+        writer.put(self.colors)?;
+        for word in self.done.chunks(64) {
+            let bits = word
+                .iter()
+                .enumerate()
+                .fold(0u64, |bits, (idx, &done)| bits | (done as u64) << idx);
+            writer.put(bits)?;
+        }
+        for (counts, &total) in self.counts.iter().zip(self.totals.iter()) {
+            writer.put(total as u64)?;
+            writer.put(counts.len() as u64)?;
+            for (label, &count) in counts.iter() {
+                writer.put_label(label)?;
+                writer.put(count as u64)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(reader: &mut BinaryReader<R>) -> Result<Self> {
+        if &reader.get_magic()? != MAGIC {
+            bail!("ERROR: Not a memoesu checkpoint.");
+        }
+        let version = reader.get()?;
+        if version != VERSION {
+            bail!("ERROR: Unsupported checkpoint version {version}.");
+        }
+        let key = MemoKey::read(reader)?;
+        let min_size = reader.get()? as usize;
+        let nodes = reader.get()? as usize;
+        let edges = reader.get()? as usize;
+        let shard = match (reader.get()? as usize, reader.get()? as usize) {
+            (0, 0) => None,
+            (index, count) => Some(Shard::new(index, count)?),
+        };
+        let colors = reader.get()?;
+        let mut done = Vec::new();
+        for _ in 0..nodes.div_ceil(64) {
+            let bits = reader.get()?;
+            let remaining = (nodes - done.len()).min(64);
+            done.extend((0..remaining).map(|idx| bits >> idx & 1 == 1));
+        }
+        let mut counts = Vec::new();
+        let mut totals = Vec::new();
+        for _ in 0..key.motif_size() {
+            totals.push(reader.get()? as usize);
+            // Every count takes at least the length of its label and the count
+            let num_labels = reader.get_len(16)?;
+            let mut size_counts = Counts::default();
+            for _ in 0..num_labels {
+                let label = reader.get_label()?;
+                size_counts.insert(label, reader.get()? as usize);
+            }
+            counts.push(size_counts);
+        }
+        Ok(Self {
+            key,
+            min_size,
+            nodes,
+            edges,
+            shard,
+            colors,
+            done,
+            counts,
+            totals,
+        })
+    }
+}
+
+/// A hash of the node colors (if any) that is stable across runs.
+fn hash_colors(colors: Option<&[Color]>) -> u64 {
+    // 64-bit FNV-1a over the little-endian bytes of the colors
+    colors.map_or(0, |colors| {
+        colors
+            .iter()
+            .flat_map(|color| color.to_le_bytes())
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+    })
+}
+
+/// Periodically writes the checkpoint of an enumeration to a file.
+pub(crate) struct Checkpointer {
+    path: PathBuf,
+    interval: Duration,
+    last: Instant,
+    checkpoint: Checkpoint,
+}
+impl Checkpointer {
+    pub(crate) fn new(path: PathBuf, interval: Duration, checkpoint: Checkpoint) -> Self {
+        Self {
+            path,
+            interval,
+            last: Instant::now(),
+            checkpoint,
+        }
+    }
+
+    /// Whether the interval since the last checkpoint has passed.
+    pub(crate) fn is_due(&self) -> bool {
+        self.last.elapsed() >= self.interval
+    }
+
+    /// Write the finished roots and their counts to the checkpoint file.
+    pub(crate) fn save(&mut self, done: &[bool], counts: &[Counts], totals: &[usize]) {
+        self.checkpoint.update(done, counts, totals);
+        if let Err(error) = self.checkpoint.save(&self.path) {
+            eprintln!(
+                ">> Unable to write checkpoint to {}: {error}",
+                self.path.display()
+            );
+        }
+        self.last = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use crate::enumerate::{Canonicalizer, DefaultCanonicalizer, Label};
+
+    const CANON: &str = DefaultCanonicalizer::NAME;
+
+    #[test]
+    fn checkpoint_roundtrip() {
+        let graph = BitGraph::from_edges(70, &[(0, 1), (1, 2), (2, 69)], true);
+        let shard = Shard::new(2, 3).ok();
+        let key = MemoKey::new(3, &graph, false, CANON);
+        let mut checkpoint = Checkpoint::new(&graph, 2, key.clone(), shard, None);
+        let mut done = vec![false; 70];
+        done[0] = true;
+        done[65] = true;
+        let mut counts = vec![Counts::default(); 3];
+        let label: Label = vec![1, 2, u64::MAX].into();
+        counts[2].insert(label, 5);
+        checkpoint.update(&done, &counts, &[0, 3, 5]);
+
+        let mut writer = BinaryWriter::new(Vec::new());
+        checkpoint.write(&mut writer).unwrap();
+        let buffer = writer.into_inner();
+        let loaded = Checkpoint::read(&mut buffer.as_slice().into()).unwrap();
+        assert_eq!(loaded, checkpoint);
+        assert_eq!(loaded.roots_done(), 2);
+        assert_eq!(loaded.total_subgraphs(), 8);
+        assert!(loaded.validate(&graph, 2, &key, shard, None).is_ok());
+        assert!(loaded.validate(&graph, 3, &key, shard, None).is_err());
+        assert!(loaded.validate(&graph, 2, &key, None, None).is_err());
+        let other = MemoKey::new(4, &graph, false, CANON);
+        assert!(loaded.validate(&graph, 2, &other, shard, None).is_err());
+        let other = MemoKey::new(3, &graph, false, "other");
+        assert!(loaded.validate(&graph, 2, &other, shard, None).is_err());
+        let other = BitGraph::from_edges(70, &[(0, 1)], true);
+        assert!(loaded.validate(&other, 2, &key, shard, None).is_err());
+    }
+
+    #[test]
+    fn checkpoint_colors() {
+        let graph = BitGraph::from_edges(3, &[(0, 1), (1, 2)], true);
+        let key = MemoKey::new(3, &graph, true, CANON);
+        let colors = [0, 1, 0];
+        let checkpoint = Checkpoint::new(&graph, 3, key.clone(), None, Some(&colors));
+        let mut writer = BinaryWriter::new(Vec::new());
+        checkpoint.write(&mut writer).unwrap();
+        let buffer = writer.into_inner();
+        let loaded = Checkpoint::read(&mut buffer.as_slice().into()).unwrap();
+        assert!(loaded
+            .validate(&graph, 3, &key, None, Some(&colors))
+            .is_ok());
+
+        // Resuming without colors or with other colors
+        let uncolored = MemoKey::new(3, &graph, false, CANON);
+        assert!(loaded.validate(&graph, 3, &uncolored, None, None).is_err());
+        assert!(loaded
+            .validate(&graph, 3, &key, None, Some(&[1, 0, 1]))
+            .is_err());
+        assert!(loaded
+            .validate(&graph, 3, &key, None, Some(&[0, 0, 1]))
+            .is_err());
+    }
+
+    #[test]
+    fn checkpoint_edge_types() {
+        let typed = BitGraph::from_typed_edges(3, &[(0, 1), (1, 2)], &[0, 1], true);
+        let key = MemoKey::new(3, &typed, false, CANON);
+        let checkpoint = Checkpoint::new(&typed, 3, key.clone(), None, None);
+        assert!(checkpoint.validate(&typed, 3, &key, None, None).is_ok());
+
+        // Resuming without edge types, or with loops
+        let untyped = BitGraph::from_edges(3, &[(0, 1), (1, 2)], true);
+        let other = MemoKey::new(3, &untyped, false, CANON);
+        assert!(checkpoint
+            .validate(&untyped, 3, &other, None, None)
+            .is_err());
+        let checkpoint = Checkpoint::new(&untyped, 3, other.clone(), None, None);
+        let loops = BitGraph::from_edges(3, &[(0, 1), (1, 1)], true);
+        let other = MemoKey::new(3, &loops, false, CANON);
+        assert!(checkpoint.validate(&loops, 3, &other, None, None).is_err());
+    }
+
+    #[test]
+    fn invalid_checkpoint() {
+        assert!(Checkpoint::read(&mut b"MEMOESU".as_slice().into()).is_err());
+        assert!(Checkpoint::read(&mut b"NOTESU\0\0".as_slice().into()).is_err());
+        let mut file = MAGIC.to_vec();
+        file.extend(2u64.to_le_bytes());
+        assert!(Checkpoint::read(&mut file.as_slice().into()).is_err());
+    }
+}
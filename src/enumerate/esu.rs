@@ -1,55 +1,332 @@
-use super::{result::GroupResult, Counts, Groups, Label};
-use crate::enumerate::{BitGraph, EnumResult, NautyGraph};
+use super::{
+    nauty_graph, positions, result::GroupResult, sampling::SampleState, update_groups,
+    validate_canonicalizer, validate_colors, validate_edge_colors, validate_edge_type,
+    validate_motif_size, validate_size_range, Canonicalizer, Checkpoint, Checkpointer, Color,
+    Counts, Groups, Instance, Label, LookupTable, MemoCache, MemoKey, Positions, Progress,
+    ProgressTracker, Query, Shard, SizeResults,
+};
+use crate::enumerate::{
+    is_default, BitGraph, DefaultCanonicalizer, EnumResult, NautyGraph, Sampling,
+};
 use ahash::HashMap;
+use anyhow::{bail, Result};
 use petgraph::{EdgeType, Graph};
-use std::{cell::RefCell, marker::PhantomData};
+use std::{cell::RefCell, marker::PhantomData, path::PathBuf, time::Duration};
 
 type Memo = HashMap<Label, Label>;
+type PositionMemo = HashMap<Label, Positions>;
+type InstanceCallback = Box<dyn FnMut(&Instance)>;
 
-pub struct Esu<Ty: EdgeType> {
+pub struct Esu<Ty: EdgeType, C: Canonicalizer = DefaultCanonicalizer> {
     motif_size: usize,
+    min_size: usize,
     current: Vec<usize>,
     graph: BitGraph,
-    ngraph: NautyGraph,
-    counts: RefCell<Counts>,
+    ngraphs: Vec<NautyGraph<C>>,
+    lookup: Vec<Option<&'static LookupTable>>,
+    counts: RefCell<Vec<Counts>>,
     memo: Memo,
+    position_memo: PositionMemo,
     groups: RefCell<Groups>,
-    total: usize,
+    totals: Vec<usize>,
     is_directed: bool,
     identify_groups: bool,
+    colors: Option<Vec<Color>>,
+    sampler: Option<SampleState>,
+    instances: Option<InstanceCallback>,
+    query: Option<Query>,
+    progress: Option<ProgressTracker>,
+    checkpoint: Option<(PathBuf, Duration)>,
+    resume: Option<Checkpoint>,
+    shard: Option<Shard>,
     phantom: PhantomData<Ty>,
 }
 
 impl<Ty: EdgeType> Esu<Ty> {
-    pub fn new(motif_size: usize, petgraph: &Graph<(), (), Ty>) -> Self {
-        let is_directed = petgraph.is_directed();
-        let graph = BitGraph::from_graph(petgraph);
+    pub fn new(motif_size: usize, petgraph: &Graph<(), (), Ty>) -> Result<Self> {
+        Self::from_bitgraph(motif_size, BitGraph::from_graph(petgraph))
+    }
+
+    /// Create the enumerator directly from a [`BitGraph`].
+    pub fn from_bitgraph(motif_size: usize, graph: BitGraph) -> Result<Self> {
+        Self::with_canonicalizer(motif_size, graph)
+    }
+}
+
+impl<Ty: EdgeType, C: Canonicalizer> Esu<Ty, C> {
+    /// Create the enumerator of a [`BitGraph`] whose subgraphs are
+    /// canonicalized by the backend `C`.
+    pub fn with_canonicalizer(motif_size: usize, graph: BitGraph) -> Result<Self> {
+        validate_motif_size(motif_size)?;
+        validate_edge_type::<Ty>(&graph)?;
+        validate_canonicalizer::<C>(&graph)?;
+        validate_edge_colors(motif_size, &graph)?;
+        let is_directed = graph.is_directed;
+        let min_size = motif_size;
         let current = vec![0; motif_size];
-        let ngraph = NautyGraph::new(motif_size, is_directed);
-        let counts = Counts::default().into();
+        let ngraphs = (1..=motif_size)
+            .map(|size| nauty_graph(size, &graph, false))
+            .collect();
+        let lookup = LookupTable::for_sizes::<C>(motif_size, &graph, false);
+        let counts = vec![Counts::default(); motif_size].into();
         let memo = Memo::default();
+        let position_memo = PositionMemo::default();
         let groups = Groups::default().into();
-        let total = 0;
+        let totals = vec![0; motif_size];
         let phantom = PhantomData;
         let identify_groups = false;
-        Self {
+        let colors = None;
+        let sampler = None;
+        let instances = None;
+        let query = None;
+        let progress = None;
+        let checkpoint = None;
+        let resume = None;
+        let shard = None;
+        Ok(Self {
             motif_size,
+            min_size,
             current,
             graph,
-            ngraph,
+            ngraphs,
+            lookup,
             counts,
             memo,
+            position_memo,
             groups,
-            total,
+            totals,
             is_directed,
             identify_groups,
+            colors,
+            sampler,
+            instances,
+            query,
+            progress,
+            checkpoint,
+            resume,
+            shard,
             phantom,
+        })
+    }
+
+    /// Color the nodes of the graph so that subgraphs are only considered
+    /// isomorphic if the isomorphism also preserves the node colors.
+    pub fn set_colors(&mut self, colors: Vec<Color>) -> Result<()> {
+        validate_colors(&colors, &self.graph)?;
+        if self.query.is_some() {
+            bail!("ERROR: Queries are not supported for colored graphs.");
+        }
+        if !self.memo.is_empty() {
+            bail!("ERROR: Node colors must be set before loading a memo cache.");
+        }
+        if self.resume.is_some() {
+            bail!("ERROR: Node colors must be set before resuming from a checkpoint.");
+        }
+        self.ngraphs = (1..=self.motif_size)
+            .map(|size| nauty_graph(size, &self.graph, true))
+            .collect();
+        self.lookup = LookupTable::for_sizes::<C>(self.motif_size, &self.graph, true);
+        self.colors = Some(colors);
+        Ok(())
+    }
+
+    /// Also count all subgraphs from `min_size` up to the motif size
+    /// within the same traversal of the ESU tree.
+    pub fn set_min_size(&mut self, min_size: usize) -> Result<()> {
+        validate_size_range(min_size, self.motif_size)?;
+        if self.sampler.is_some() && min_size != self.motif_size {
+            bail!("ERROR: Sampling is not supported when enumerating a range of subgraph sizes.");
+        }
+        self.min_size = min_size;
+        Ok(())
+    }
+
+    /// Use RAND-ESU to sample the ESU tree instead of exploring it fully.
+    pub fn set_sampling(&mut self, sampling: Sampling) -> Result<()> {
+        sampling.validate(self.motif_size)?;
+        if self.checkpoint.is_some() || self.resume.is_some() {
+            bail!("ERROR: Checkpoints are not supported when sampling.");
+        }
+        if self.min_size != self.motif_size {
+            bail!("ERROR: Sampling is not supported when enumerating a range of subgraph sizes.");
+        }
+        self.sampler = Some(sampling.state());
+        Ok(())
+    }
+
+    /// Only count the target motifs of the query, pruning the branches of
+    /// the ESU tree that cannot grow into one of them.
+    ///
+    /// The sizes from the smallest to the largest target are enumerated, so
+    /// the largest target must have the motif size.
+    pub fn set_query(&mut self, mut query: Query) -> Result<()> {
+        if query.max_size() != self.motif_size {
+            bail!(
+                "ERROR: The largest target motif has {} nodes but the subgraph size is {}.",
+                query.max_size(),
+                self.motif_size
+            );
+        }
+        if self.colors.is_some() {
+            bail!("ERROR: Queries are not supported for colored graphs.");
+        }
+        if !is_default::<C>() {
+            bail!("ERROR: Queries are only supported with the default canonicalizer.");
+        }
+        query.bind(&self.graph)?;
+        self.set_min_size(query.min_size())?;
+        self.query = Some(query);
+        Ok(())
+    }
+
+    /// Whether the node can be part of a target motif of the query.
+    fn admits(&self, v: usize) -> bool {
+        self.query.as_ref().is_none_or(|query| query.admits(v))
+    }
+
+    /// Only explore the ESU trees of the roots in the shard, so that the
+    /// enumeration can be split over independent processes whose counts
+    /// sum to the full counts.
+    pub fn set_shard(&mut self, shard: Shard) {
+        self.shard = Some(shard);
+    }
+
+    /// Whether the root belongs to the shard of the enumeration.
+    fn in_shard(&self, root: usize) -> bool {
+        self.shard.is_none_or(|shard| shard.contains(root))
+    }
+
+    /// Pass every subgraph occurrence to the callback as soon as it is
+    /// enumerated (e.g. to stream the instances to a file).
+    pub fn set_instances<F: FnMut(&Instance) + 'static>(&mut self, callback: F) {
+        self.instances = Some(Box::new(callback));
+    }
+
+    /// Pass the progress of the enumeration (in finished root vertices) to
+    /// the callback at most once per `interval`, and once when it is finished.
+    pub fn set_progress<F: Fn(&Progress) + Send + Sync + 'static>(
+        &mut self,
+        interval: Duration,
+        callback: F,
+    ) {
+        self.progress = Some(ProgressTracker::new(interval, callback));
+    }
+
+    /// Periodically write the counts of the finished roots to a checkpoint
+    /// file (and once the enumeration is finished), from which an
+    /// interrupted enumeration can be resumed.
+    pub fn set_checkpoint<P: Into<PathBuf>>(&mut self, path: P, interval: Duration) -> Result<()> {
+        if self.sampler.is_some() {
+            bail!("ERROR: Checkpoints are not supported when sampling.");
+        }
+        self.checkpoint = Some((path.into(), interval));
+        Ok(())
+    }
+
+    /// Resume an interrupted enumeration from a checkpoint, skipping its
+    /// finished roots and starting from its counts.
+    ///
+    /// The node colors, the minimum size, and the shard must be set before
+    /// resuming.
+    pub fn resume(&mut self, checkpoint: Checkpoint) -> Result<()> {
+        if self.sampler.is_some() {
+            bail!("ERROR: Checkpoints are not supported when sampling.");
         }
+        checkpoint.validate(
+            &self.graph,
+            self.min_size,
+            &self.memo_key(),
+            self.shard,
+            self.colors.as_deref(),
+        )?;
+        self.resume = Some(checkpoint);
+        Ok(())
+    }
+
+    /// Start from the canonical labels memoized by an earlier enumeration
+    /// with the same subgraph size, direction, and loops, so that nauty only
+    /// runs for subgraphs that were not seen before.
+    ///
+    /// Node colors must be set before loading a memo cache.
+    pub fn set_memo_cache(&mut self, cache: MemoCache) -> Result<()> {
+        let labels = cache.into_labels(self.memo_key())?;
+        self.memo.extend(labels);
+        Ok(())
+    }
+
+    /// The canonical labels memoized so far, to be reused by later enumerations.
+    pub fn memo_cache(&self) -> MemoCache {
+        MemoCache::new(self.memo_key(), self.memo.clone())
+    }
+
+    fn memo_key(&self) -> MemoKey {
+        MemoKey::new(self.motif_size, &self.graph, self.colors.is_some(), C::NAME)
+    }
+
+    /// Whether the canonical positions of the subgraphs are needed.
+    fn track_positions(&self) -> bool {
+        self.identify_groups || self.instances.is_some()
     }
 
     pub fn enumerate(&mut self) {
         let ext = vec![0; self.graph.n];
-        (0..self.graph.n).for_each(|i| self.go(i, 0, 0, &ext));
+
+        // Start from the counts of the finished roots of a checkpoint
+        let mut done = vec![false; self.graph.n];
+        if let Some(checkpoint) = self.resume.take() {
+            let (finished, counts, totals) = checkpoint.into_parts();
+            done = finished;
+            *self.counts.borrow_mut() = counts;
+            self.totals = totals;
+        }
+        let mut checkpointer = self.checkpoint.clone().map(|(path, interval)| {
+            let checkpoint = Checkpoint::new(
+                &self.graph,
+                self.min_size,
+                self.memo_key(),
+                self.shard,
+                self.colors.as_deref(),
+            );
+            Checkpointer::new(path, interval, checkpoint)
+        });
+        let roots = (0..self.graph.n)
+            .filter(|&i| !done[i] && self.in_shard(i))
+            .collect::<Vec<_>>();
+        if let Some(progress) = self.progress.as_mut() {
+            progress.start(roots.len());
+        }
+
+        for i in roots {
+            let before = self.totals.iter().sum::<usize>();
+            self.root(i, &ext);
+            done[i] = true;
+            if let Some(progress) = &self.progress {
+                progress.root_done(self.totals.iter().sum::<usize>() - before);
+            }
+            if let Some(checkpointer) = checkpointer.as_mut().filter(|c| c.is_due()) {
+                checkpointer.save(&done, &self.counts.borrow(), &self.totals);
+            }
+        }
+        if let Some(progress) = &self.progress {
+            progress.finish();
+        }
+        if let Some(checkpointer) = checkpointer.as_mut() {
+            checkpointer.save(&done, &self.counts.borrow(), &self.totals);
+        }
+    }
+
+    /// Explore the ESU tree of a single root.
+    fn root(&mut self, i: usize, ext: &[usize]) {
+        if !self.admits(i) {
+            return;
+        }
+        if let Some(sampler) = self.sampler.as_mut() {
+            sampler.start_root(i);
+            if !sampler.keep(0) {
+                return;
+            }
+        }
+        self.go(i, 0, 0, ext)
     }
 
     pub fn identify_groups(&mut self) {
@@ -57,48 +334,67 @@ impl<Ty: EdgeType> Esu<Ty> {
         self.enumerate();
     }
 
-    pub fn build_nauty(&mut self) {
+    /// Build the nauty graph of the first `size` nodes of the current subgraph.
+    pub fn build_nauty(&mut self, size: usize) {
         if self.is_directed {
-            self.build_nauty_dir();
+            self.build_nauty_dir(size);
         } else {
-            self.build_nauty_undir();
+            self.build_nauty_undir(size);
+        }
+        if let Some(colors) = &self.colors {
+            let current = &self.current;
+            self.ngraphs[size - 1].set_colors(|i| colors[current[i]]);
         }
     }
 
-    pub fn build_nauty_dir(&mut self) {
-        self.current.iter().enumerate().for_each(|(i, &u)| {
-            self.current.iter().enumerate().for_each(|(j, &v)| {
+    pub fn build_nauty_dir(&mut self, size: usize) {
+        let ngraph = &mut self.ngraphs[size - 1];
+        self.current[..size].iter().enumerate().for_each(|(i, &u)| {
+            self.current[..size].iter().enumerate().for_each(|(j, &v)| {
                 if self.graph.is_connected_directed(u, v) {
-                    self.ngraph.add_arc(i, j);
+                    match self.graph.edge_color(u, v) {
+                        Some(color) => ngraph.add_typed_arc(i, j, color),
+                        None => ngraph.add_arc(i, j),
+                    }
                 }
             })
         });
     }
 
-    pub fn build_nauty_undir(&mut self) {
-        self.current.iter().enumerate().for_each(|(i, &u)| {
-            self.current
+    pub fn build_nauty_undir(&mut self, size: usize) {
+        let ngraph = &mut self.ngraphs[size - 1];
+        self.current[..size].iter().enumerate().for_each(|(i, &u)| {
+            self.current[..size]
                 .iter()
                 .enumerate()
                 .skip(i + 1)
                 .for_each(|(j, &v)| {
                     if self.graph.is_connected(u, v) {
-                        self.ngraph.add_arc(i, j);
-                        self.ngraph.add_arc(j, i);
+                        match self.graph.edge_color(u, v) {
+                            Some(color) => {
+                                ngraph.add_typed_arc(i, j, color);
+                                ngraph.add_typed_arc(j, i, color);
+                            }
+                            None => {
+                                ngraph.add_arc(i, j);
+                                ngraph.add_arc(j, i);
+                            }
+                        }
                     }
                 })
         });
     }
 
-    pub fn run_nauty(&mut self) {
-        self.ngraph.run();
+    pub fn run_nauty(&mut self, size: usize) {
+        self.ngraphs[size - 1].run();
     }
 
     /// Increment the count of the given label.
     ///
     /// Implemented with a RefCell to allow for interior mutability.
-    fn increment_label(&self, label: &Label) {
+    fn increment_label(&self, size: usize, label: &Label) {
         let mut counts_internal = self.counts.borrow_mut();
+        let counts_internal = &mut counts_internal[size - 1];
         if let Some(count) = counts_internal.get_mut(label) {
             *count += 1;
         } else {
@@ -110,17 +406,88 @@ impl<Ty: EdgeType> Esu<Ty> {
     /// nodes in the current subgraph.
     ///
     /// Implemented with a RefCell to allow for interior mutability.
-    fn update_groups(&self, label: &Label) {
-        let mut groups_internal = self.groups.borrow_mut();
-        for idx in 0..self.motif_size {
-            let node_idx = self.current[idx];
-            let orbit = self.ngraph.nodes.orbits[idx];
-            let node_label = self.ngraph.nodes.lab[idx];
-            let group = groups_internal
-                .entry(node_idx)
-                .or_insert_with(HashMap::default);
-            let group_info = (label.clone(), node_label, orbit);
-            *group.entry(group_info).or_insert(0) += 1;
+    fn update_groups(&self, size: usize, label: &Label, positions: &Positions) {
+        update_groups(
+            &mut self.groups.borrow_mut(),
+            &self.current[..size],
+            label,
+            positions,
+        );
+    }
+
+    /// Canonicalize the first `size` nodes of the current subgraph and
+    /// count it.
+    fn count_subgraph(&mut self, size: usize) {
+        // Small uncolored subgraphs are looked up by their adjacency matrix
+        if let Some(table) = self.lookup[size - 1] {
+            let (label, positions) = table.lookup(&self.graph, &self.current[..size]);
+            self.record_subgraph(size, label, Some(positions));
+            return;
+        }
+
+        self.build_nauty(size);
+        let original = Label::from_words(self.ngraphs[size - 1].graph());
+
+        // Check if the subgraph is isomorphic to a subgraph that has already been enumerated.
+        // (orbits are only memoized when grouping, so they may be missing from an earlier run)
+        let track_positions = self.track_positions();
+        let memoized = self
+            .memo
+            .get(&original)
+            .filter(|_| !track_positions || self.position_memo.contains_key(&original));
+        let label = if let Some(label) = memoized {
+            label.clone()
+
+        // Otherwise run nauty to find the canonical label of the subgraph.
+        } else {
+            self.run_nauty(size);
+            let ngraph = &mut self.ngraphs[size - 1];
+            let label = Label::from_words(ngraph.canon());
+            // The orbits are only valid for the nauty run of this exact subgraph
+            if track_positions {
+                self.position_memo
+                    .insert(original.clone(), positions(ngraph));
+            }
+            self.memo.insert(original.clone(), label.clone());
+            ngraph.clear_canon();
+            label
+        };
+        let positions = track_positions.then(|| self.position_memo[&original].clone());
+        self.ngraphs[size - 1].clear_graph();
+        self.record_subgraph(size, &label, positions.as_ref());
+    }
+
+    /// Count the first `size` nodes of the current subgraph with its
+    /// canonical label and positions.
+    fn record_subgraph(&mut self, size: usize, label: &Label, positions: Option<&Positions>) {
+        // Queries only count the target motifs
+        if self
+            .query
+            .as_ref()
+            .is_some_and(|query| !query.contains(label))
+        {
+            return;
+        }
+
+        // Increment the count of the subgraph with the given label.
+        self.totals[size - 1] += 1;
+        self.increment_label(size, label);
+
+        // Track the sampled subgraph for the RAND-ESU variance estimate
+        if let Some(sampler) = self.sampler.as_mut() {
+            sampler.record(label);
+        }
+
+        if let Some(positions) = positions {
+            // Add the subgraph label and orbit to the node group membership
+            if self.identify_groups {
+                self.update_groups(size, label, positions);
+            }
+
+            // Stream the occurrence with its nodes in canonical order
+            if let Some(callback) = self.instances.as_mut() {
+                callback(&Instance::new(&self.current[..size], label, positions));
+            }
         }
     }
 
@@ -134,50 +501,37 @@ impl<Ty: EdgeType> Esu<Ty> {
     /// * `size` - The current size of the subgraph.
     /// * `next` - The next node to be added to the subgraph.
     /// * `ext` - The extension of the subgraph.
-    pub fn go(&mut self, n: usize, size: usize, next: usize, ext: &Vec<usize>) {
+    pub fn go(&mut self, n: usize, size: usize, next: usize, ext: &[usize]) {
         self.current[size] = n;
+        let depth = size;
         let size = size + 1;
 
-        if size == self.motif_size {
-            self.total += 1;
-            self.build_nauty();
-
-            // Check if the subgraph is isomorphic to a subgraph that has already been enumerated.
-            let label = if let Some(label) = self.memo.get(self.ngraph.graph()) {
-                label
-
-            // Otherwise run nauty to find the canonical label of the subgraph.
-            } else {
-                self.run_nauty();
-                let original = self.ngraph.graph().to_vec();
-                let label = self.ngraph.canon().to_vec();
-                self.memo.insert(original.into(), label.into());
-                self.ngraph.clear_canon();
-                self.memo.get(self.ngraph.graph()).unwrap()
-            };
-
-            // Increment the count of the subgraph with the given label.
-            self.increment_label(label);
+        // Every node of the ESU tree is a connected subgraph of its depth,
+        // so all sizes in the range are counted in the same traversal.
+        // Queries only count (and extend) subgraphs that can be a target motif
+        let (count, extend) = match &self.query {
+            Some(query) => query.check(&self.graph, &self.current[..size]),
+            None => (true, true),
+        };
 
-            // Add the subgraph label and orbit to the node group membership
-            if self.identify_groups {
-                self.update_groups(label);
-            }
+        if count && size >= self.min_size {
+            self.count_subgraph(size);
+        }
 
-            self.ngraph.clear_graph();
-        } else {
+        if extend && size < self.motif_size {
             let mut next2 = next;
 
             // Copy the list of nodes in the extension.
-            let mut ext2 = ext.clone();
+            let mut ext2 = ext.to_vec();
 
             // Get the neighbors of the last node in the current subgraph
-            let neighbors = self.graph.neighbors(self.current[size - 1]).ones();
+            let neighbors = self.graph.neighbors(self.current[size - 1]);
 
             // Iterate over the neighbors of the last node in the current subgraph
             for v in neighbors {
                 // If the neighbor is smaller than the first node in the current subgraph, skip it
-                if v <= self.current[0] {
+                // (or if it cannot be part of a target motif of a query)
+                if v <= self.current[0] || !self.admits(v) {
                     continue;
                 }
 
@@ -197,56 +551,121 @@ impl<Ty: EdgeType> Esu<Ty> {
             }
 
             // Recursively call the function for each node in the extension
+            // (which are only explored with the depth probability when sampling)
             while next2 > 0 {
                 next2 -= 1;
-                self.go(ext2[next2], size, next2, &ext2);
+                if self.sampler.as_mut().is_none_or(|s| s.keep(size)) {
+                    self.go(ext2[next2], size, next2, &ext2);
+                }
+            }
+
+            if let Some(sampler) = self.sampler.as_mut() {
+                sampler.exit(depth);
             }
         }
     }
 
+    /// The enumeration result for the motif size.
     pub fn result(self) -> EnumResult {
-        EnumResult::new(self.counts.into_inner(), self.total)
+        let motif_size = self.motif_size;
+        let mut counts = self.counts.into_inner();
+        let counts = std::mem::take(&mut counts[motif_size - 1]);
+        let total = self.totals[motif_size - 1];
+        let estimates = self.sampler.map(|s| s.estimates(&counts, total));
+        let result = EnumResult::new(counts, total);
+        match estimates {
+            Some(estimates) => result.with_estimates(estimates),
+            None => result,
+        }
+    }
+
+    /// The enumeration results for every subgraph size from the minimum
+    /// size up to the motif size.
+    pub fn size_results(self) -> SizeResults {
+        let counts = self.counts.into_inner();
+        // Sampling is only supported for a single size
+        let mut estimates = self.sampler.map(|s| {
+            let idx = self.motif_size - 1;
+            s.estimates(&counts[idx], self.totals[idx])
+        });
+        counts
+            .into_iter()
+            .zip(self.totals)
+            .enumerate()
+            .skip(self.min_size - 1)
+            .map(|(idx, (counts, total))| {
+                let result = EnumResult::new(counts, total);
+                match estimates.take() {
+                    Some(estimates) => (idx + 1, result.with_estimates(estimates)),
+                    None => (idx + 1, result),
+                }
+            })
+            .collect()
     }
 
     pub fn group_results(self) -> GroupResult {
-        GroupResult::new(
-            self.groups.into_inner(),
-            self.total,
-            self.counts.into_inner().len(),
-        )
+        let motif_size = self.motif_size;
+        let mut counts = self.counts.into_inner();
+        let counts = std::mem::take(&mut counts[motif_size - 1]);
+        let total = self.totals[motif_size - 1];
+        let estimates = self.sampler.map(|s| s.estimates(&counts, total));
+        let result = GroupResult::new(self.groups.into_inner(), total, counts.len());
+        match estimates {
+            Some(estimates) => result.with_estimates(estimates),
+            None => result,
+        }
     }
 }
 
+/// Enumerate all subgraphs of a given size in a graph.
 pub fn enumerate_subgraphs<Ty: EdgeType>(
     petgraph: &Graph<(), (), Ty>,
     motif_size: usize,
-) -> EnumResult {
-    let mut esu = Esu::new(motif_size, petgraph);
+) -> Result<EnumResult> {
+    let mut esu = Esu::new(motif_size, petgraph)?;
     esu.enumerate();
-    esu.result()
+    Ok(esu.result())
 }
 
+/// Enumerate all subgraphs of every size from `min_size` to `max_size`
+/// in a graph within a single traversal.
+pub fn enumerate_subgraph_sizes<Ty: EdgeType>(
+    petgraph: &Graph<(), (), Ty>,
+    min_size: usize,
+    max_size: usize,
+) -> Result<SizeResults> {
+    let mut esu = Esu::new(max_size, petgraph)?;
+    esu.set_min_size(min_size)?;
+    esu.enumerate();
+    Ok(esu.size_results())
+}
+
+/// Enumerate all subgraphs of a given size in a graph and record the
+/// subgraph membership and orbit position of every node.
 pub fn group_subgraphs<Ty: EdgeType>(
     petgraph: &Graph<(), (), Ty>,
     motif_size: usize,
-) -> GroupResult {
-    let mut esu = Esu::new(motif_size, petgraph);
+) -> Result<GroupResult> {
+    let mut esu = Esu::new(motif_size, petgraph)?;
     esu.identify_groups();
-    esu.group_results()
+    Ok(esu.group_results())
 }
 
 #[cfg(test)]
 mod testing {
 
     use super::*;
-    use crate::io::load_numeric_graph;
+    use crate::{
+        enumerate::{EdgeColor, Query, Representation},
+        io::load_numeric_graph,
+    };
     use petgraph::{Directed, Undirected};
 
     #[test]
     fn dir_example_s3() {
         let filepath = "example/example.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = enumerate_subgraphs(&graph, 3);
+        let result = enumerate_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 16);
         assert_eq!(result.unique_subgraphs(), 4);
 
@@ -264,7 +683,7 @@ mod testing {
     fn undir_example_s3() {
         let filepath = "example/example.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = enumerate_subgraphs(&graph, 3);
+        let result = enumerate_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 16);
         assert_eq!(result.unique_subgraphs(), 2);
 
@@ -280,7 +699,7 @@ mod testing {
     fn dir_example_s4() {
         let filepath = "example/example.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = enumerate_subgraphs(&graph, 4);
+        let result = enumerate_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 24);
         assert_eq!(result.unique_subgraphs(), 8);
 
@@ -302,7 +721,7 @@ mod testing {
     fn undir_example_s4() {
         let filepath = "example/example.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = enumerate_subgraphs(&graph, 4);
+        let result = enumerate_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 24);
         assert_eq!(result.unique_subgraphs(), 3);
 
@@ -319,7 +738,7 @@ mod testing {
     fn dir_ecoli_s3() {
         let filepath = "example/ecoli.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = enumerate_subgraphs(&graph, 3);
+        let result = enumerate_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 674);
         assert_eq!(result.unique_subgraphs(), 4);
 
@@ -337,7 +756,7 @@ mod testing {
     fn undir_ecoli_s3() {
         let filepath = "example/ecoli.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = enumerate_subgraphs(&graph, 3);
+        let result = enumerate_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 674);
         assert_eq!(result.unique_subgraphs(), 2);
 
@@ -353,7 +772,7 @@ mod testing {
     fn dir_ecoli_s4() {
         let filepath = "example/ecoli.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = enumerate_subgraphs(&graph, 4);
+        let result = enumerate_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 2531);
         assert_eq!(result.unique_subgraphs(), 24);
 
@@ -412,7 +831,7 @@ mod testing {
     fn undir_ecoli_s4() {
         let filepath = "example/ecoli.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = enumerate_subgraphs(&graph, 4);
+        let result = enumerate_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 2531);
         assert_eq!(result.unique_subgraphs(), 6);
 
@@ -437,7 +856,7 @@ mod testing {
     fn dir_yeast_s3() {
         let filepath = "example/yeast.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = enumerate_subgraphs(&graph, 3);
+        let result = enumerate_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 13150);
         assert_eq!(result.unique_subgraphs(), 7);
 
@@ -463,7 +882,7 @@ mod testing {
     fn undir_yeast_s3() {
         let filepath = "example/yeast.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = enumerate_subgraphs(&graph, 3);
+        let result = enumerate_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 13150);
         assert_eq!(result.unique_subgraphs(), 2);
 
@@ -479,7 +898,7 @@ mod testing {
     fn dir_yeast_s4() {
         let filepath = "example/yeast.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = enumerate_subgraphs(&graph, 4);
+        let result = enumerate_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 183174);
         assert_eq!(result.unique_subgraphs(), 34);
 
@@ -551,7 +970,7 @@ mod testing {
     fn undir_yeast_s4() {
         let filepath = "example/yeast.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = enumerate_subgraphs(&graph, 4);
+        let result = enumerate_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 183174);
         assert_eq!(result.unique_subgraphs(), 6);
 
@@ -571,7 +990,7 @@ mod testing {
     fn dir_example_s3_groups() {
         let filepath = "example/example.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = group_subgraphs(&graph, 3);
+        let result = group_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 16);
         assert_eq!(result.unique_subgraphs(), 4);
         (0..graph.node_count()).for_each(|i| {
@@ -583,7 +1002,7 @@ mod testing {
     fn dir_example_s4_groups() {
         let filepath = "example/example.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = group_subgraphs(&graph, 4);
+        let result = group_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 24);
         assert_eq!(result.unique_subgraphs(), 8);
         (0..graph.node_count()).for_each(|i| {
@@ -595,7 +1014,7 @@ mod testing {
     fn undir_example_s3_groups() {
         let filepath = "example/example.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = group_subgraphs(&graph, 3);
+        let result = group_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 16);
         assert_eq!(result.unique_subgraphs(), 2);
         (0..graph.node_count()).for_each(|i| {
@@ -607,11 +1026,649 @@ mod testing {
     fn undir_example_s4_groups() {
         let filepath = "example/example.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = group_subgraphs(&graph, 4);
+        let result = group_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 24);
         assert_eq!(result.unique_subgraphs(), 3);
         (0..graph.node_count()).for_each(|i| {
             assert!(result.groups().contains_key(&i));
         })
     }
+
+    #[test]
+    fn dir_yeast_s4_sampled_exhaustive() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let mut esu = Esu::new(4, &graph).unwrap();
+        esu.set_sampling(Sampling::new(vec![1.0; 4], 0).unwrap())
+            .unwrap();
+        esu.enumerate();
+        let result = esu.result();
+        let exact = enumerate_subgraphs(&graph, 4).unwrap();
+        let estimates = result.estimates().unwrap();
+        assert_eq!(result.counts(), exact.counts());
+        assert_eq!(estimates.total().value, 183174.0);
+        assert_eq!(estimates.total().variance, 0.0);
+    }
+
+    #[test]
+    fn dir_yeast_s4_sampled() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let sample = |seed| {
+            let mut esu = Esu::new(4, &graph).unwrap();
+            esu.set_sampling(Sampling::new(vec![1.0, 1.0, 0.5, 0.5], seed).unwrap())
+                .unwrap();
+            esu.enumerate();
+            esu.result()
+        };
+        let result = sample(42);
+        let estimates = result.estimates().unwrap();
+        assert!(result.total_subgraphs() < 183174);
+        assert_eq!(estimates.inclusion_probability(), 0.25);
+        assert_eq!(
+            estimates.total().value,
+            result.total_subgraphs() as f64 * 4.0
+        );
+        let (lower, upper) = estimates.total().confidence_interval();
+        assert!(lower < upper);
+        assert!(estimates.total().variance > 0.0);
+
+        // Sampling is deterministic for a given seed
+        assert_eq!(result.counts(), sample(42).counts());
+    }
+
+    #[test]
+    fn sampled_wrong_depth() {
+        let filepath = "example/example.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let mut esu = Esu::new(3, &graph).unwrap();
+        assert!(esu
+            .set_sampling(Sampling::new(vec![1.0, 0.5], 0).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn empty_motif_size() {
+        let filepath = "example/example.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        assert!(enumerate_subgraphs(&graph, 0).is_err());
+        assert!(group_subgraphs(&graph, 0).is_err());
+    }
+
+    #[test]
+    fn sparse_matches_dense() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let edges = graph
+            .edge_indices()
+            .map(|e| graph.edge_endpoints(e).unwrap())
+            .map(|(u, v)| (u.index() as u32, v.index() as u32))
+            .collect::<Vec<_>>();
+        let results = [Representation::Dense, Representation::Sparse].map(|repr| {
+            let bitgraph = BitGraph::from_edges_with(graph.node_count(), &edges, true, repr);
+            assert_eq!(bitgraph.representation(), repr);
+            let mut esu = Esu::<Directed>::from_bitgraph(4, bitgraph).unwrap();
+            esu.enumerate();
+            esu.result()
+        });
+        assert_eq!(results[0].total_subgraphs(), 183174);
+        assert_eq!(results[0].total_subgraphs(), results[1].total_subgraphs());
+        assert_eq!(results[0].counts(), results[1].counts());
+    }
+
+    #[test]
+    fn undir_path_s3_groups_orbits() {
+        // Two paths (0 - 1 - 2 and 6 - 7 - 8) separated by a triangle (3, 4, 5),
+        // so the second path is a memo hit after nauty has run on the triangle.
+        let graph = Graph::<(), (), Undirected>::from_edges([
+            (0, 1),
+            (1, 2),
+            (3, 4),
+            (4, 5),
+            (5, 3),
+            (6, 7),
+            (7, 8),
+        ]);
+        let result = group_subgraphs(&graph, 3).unwrap();
+        let orbit = |node: usize| {
+            let group = &result.groups()[&node];
+            assert_eq!(group.len(), 1);
+            group.keys().next().unwrap().2
+        };
+        assert_eq!(orbit(0), orbit(6));
+        assert_eq!(orbit(1), orbit(7));
+        assert_eq!(orbit(2), orbit(8));
+        assert_ne!(orbit(6), orbit(7));
+    }
+
+    #[test]
+    fn dir_yeast_size_range() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let results = enumerate_subgraph_sizes(&graph, 2, 4).unwrap();
+        assert_eq!(
+            results.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+        for (k, result) in results.iter() {
+            let single = enumerate_subgraphs(&graph, *k).unwrap();
+            assert_eq!(result.total_subgraphs(), single.total_subgraphs());
+            assert_eq!(result.counts(), single.counts());
+        }
+        assert_eq!(results[2].1.total_subgraphs(), 183174);
+    }
+
+    #[test]
+    fn undir_example_size_range() {
+        let filepath = "example/example.txt";
+        let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
+        let results = enumerate_subgraph_sizes(&graph, 1, 4).unwrap();
+        assert_eq!(results[0].1.total_subgraphs(), graph.node_count());
+        assert_eq!(results[0].1.unique_subgraphs(), 1);
+        assert_eq!(results[2].1.total_subgraphs(), 16);
+        assert_eq!(results[3].1.total_subgraphs(), 24);
+    }
+
+    #[test]
+    fn invalid_size_range() {
+        let filepath = "example/example.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        assert!(enumerate_subgraph_sizes(&graph, 0, 3).is_err());
+        assert!(enumerate_subgraph_sizes(&graph, 4, 3).is_err());
+
+        let sampling = Sampling::new(vec![1.0, 1.0, 0.5], 0).unwrap();
+        let mut esu = Esu::new(3, &graph).unwrap();
+        esu.set_min_size(2).unwrap();
+        assert!(esu.set_sampling(sampling.clone()).is_err());
+
+        let mut esu = Esu::new(3, &graph).unwrap();
+        esu.set_sampling(sampling).unwrap();
+        assert!(esu.set_min_size(2).is_err());
+        assert!(esu.set_min_size(3).is_ok());
+    }
+
+    #[test]
+    fn undir_colored_paths() {
+        // Three paths where the first and last are colored identically
+        // (up to isomorphism) and the second has a differently colored center.
+        let graph = Graph::<(), (), Undirected>::from_edges([
+            (0, 1),
+            (1, 2),
+            (3, 4),
+            (4, 5),
+            (8, 7),
+            (7, 6),
+        ]);
+        let colors = vec![0, 1, 0, 0, 0, 1, 0, 1, 0];
+        let mut esu = Esu::new(3, &graph).unwrap();
+        esu.set_colors(colors).unwrap();
+        esu.enumerate();
+        let result = esu.result();
+        assert_eq!(result.total_subgraphs(), 3);
+        assert_eq!(result.unique_subgraphs(), 2);
+        let mut counts = result.counts().values().copied().collect::<Vec<_>>();
+        counts.sort_unstable();
+        assert_eq!(counts, vec![1, 2]);
+
+        // The color vector of every label is in canonical (sorted) order
+        result.counts().keys().for_each(|label| {
+            assert_eq!(&label.words()[3..], &[0, 0, 1]);
+        });
+    }
+
+    #[test]
+    fn dir_yeast_s3_uniform_colors() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let uncolored = enumerate_subgraphs(&graph, 3).unwrap();
+        let mut esu = Esu::new(3, &graph).unwrap();
+        esu.set_colors(vec![7; graph.node_count()]).unwrap();
+        esu.enumerate();
+        let colored = esu.result();
+        assert_eq!(colored.total_subgraphs(), uncolored.total_subgraphs());
+        assert_eq!(colored.unique_subgraphs(), uncolored.unique_subgraphs());
+        let mut a = colored.counts().values().collect::<Vec<_>>();
+        let mut b = uncolored.counts().values().collect::<Vec<_>>();
+        a.sort_unstable();
+        b.sort_unstable();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn invalid_colors() {
+        let filepath = "example/example.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let mut esu = Esu::new(3, &graph).unwrap();
+        assert!(esu.set_colors(vec![0; graph.node_count() + 1]).is_err());
+    }
+
+    /// Runs the enumeration on a graph with typed edges.
+    fn typed_result<Ty: EdgeType>(
+        edges: &[(u32, u32, EdgeColor)],
+        k: usize,
+        colors: Option<Vec<Color>>,
+    ) -> EnumResult {
+        let n = edges
+            .iter()
+            .map(|&(u, v, _)| u.max(v) as usize + 1)
+            .max()
+            .unwrap();
+        let (pairs, types): (Vec<_>, Vec<_>) = edges.iter().map(|&(u, v, t)| ((u, v), t)).unzip();
+        let graph = BitGraph::from_typed_edges(n, &pairs, &types, Ty::is_directed());
+        let mut esu = Esu::<Ty>::from_bitgraph(k, graph).unwrap();
+        if let Some(colors) = colors {
+            esu.set_colors(colors).unwrap();
+        }
+        esu.enumerate();
+        esu.result()
+    }
+
+    #[test]
+    fn dir_typed_feed_forward_loops() {
+        // A coherent feed-forward loop and two (relabeled) incoherent ones
+        let edges = [
+            (0, 1, 0),
+            (1, 2, 0),
+            (0, 2, 0),
+            (3, 4, 0),
+            (4, 5, 0),
+            (3, 5, 1),
+            (8, 7, 0),
+            (7, 6, 0),
+            (8, 6, 1),
+        ];
+        let result = typed_result::<Directed>(&edges, 3, None);
+        assert_eq!(result.total_subgraphs(), 3);
+        assert_eq!(result.unique_subgraphs(), 2);
+        let mut counts = result.counts().values().copied().collect::<Vec<_>>();
+        counts.sort_unstable();
+        assert_eq!(counts, vec![1, 2]);
+
+        // Coloring the target of only one incoherent loop splits them
+        let colors = vec![0, 0, 0, 0, 0, 1, 0, 0, 0];
+        let result = typed_result::<Directed>(&edges, 3, Some(colors));
+        assert_eq!(result.unique_subgraphs(), 3);
+    }
+
+    #[test]
+    fn undir_typed_paths() {
+        // Paths whose edge types are mirror images of each other are isomorphic
+        let edges = [
+            (0, 1, 0),
+            (1, 2, 2),
+            (3, 4, 2),
+            (4, 5, 0),
+            (6, 7, 2),
+            (7, 8, 2),
+        ];
+        let result = typed_result::<Undirected>(&edges, 3, None);
+        assert_eq!(result.total_subgraphs(), 3);
+        assert_eq!(result.unique_subgraphs(), 2);
+    }
+
+    #[test]
+    fn dir_yeast_s3_single_edge_type() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let untyped = enumerate_subgraphs(&graph, 3).unwrap();
+        let edges = graph
+            .edge_indices()
+            .map(|e| graph.edge_endpoints(e).unwrap())
+            .map(|(u, v)| (u.index() as u32, v.index() as u32, 3))
+            .collect::<Vec<_>>();
+        let typed = typed_result::<Directed>(&edges, 3, None);
+        assert_eq!(typed.total_subgraphs(), untyped.total_subgraphs());
+        assert_eq!(typed.unique_subgraphs(), untyped.unique_subgraphs());
+    }
+
+    #[test]
+    fn typed_too_large() {
+        let graph = BitGraph::from_typed_edges(2, &[(0, 1)], &[0], true);
+        assert!(Esu::<Directed>::from_bitgraph(10, graph).is_err());
+        let graph = BitGraph::from_typed_edges(2, &[(0, 1)], &[127], true);
+        assert!(Esu::<Directed>::from_bitgraph(3, graph).is_err());
+    }
+
+    #[test]
+    fn dir_yeast_s4_instances() {
+        use std::{cell::RefCell, rc::Rc};
+
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let bitgraph = BitGraph::from_graph(&graph);
+        let instances = Rc::new(RefCell::new(Vec::new()));
+        let mut esu = Esu::<Directed>::new(4, &graph).unwrap();
+        {
+            let instances = instances.clone();
+            esu.set_instances(move |instance| {
+                let canonical = instance.canonical_nodes().collect::<Vec<_>>();
+                instances
+                    .borrow_mut()
+                    .push((instance.label().clone(), canonical));
+            });
+        }
+        esu.enumerate();
+        let result = esu.result();
+        let instances = instances.take();
+        assert_eq!(instances.len(), result.total_subgraphs());
+
+        // The nodes in canonical order induce the canonical adjacency
+        for (label, canonical) in instances {
+            let label = label.words();
+            for (i, &u) in canonical.iter().enumerate() {
+                for (j, &v) in canonical.iter().enumerate() {
+                    let arc = label[i] >> (63 - j) & 1 == 1;
+                    assert_eq!(arc, bitgraph.is_connected_directed(u, v));
+                }
+            }
+        }
+    }
+
+    /// The adjacency matrix of a canonical label of the given size.
+    fn label_adjacency(label: &Label, k: usize) -> (usize, Vec<usize>) {
+        let label = label.words();
+        let adj = (0..k * k)
+            .map(|idx| (label[idx / k] >> (63 - idx % k) & 1) as usize)
+            .collect();
+        (k, adj)
+    }
+
+    #[test]
+    fn dir_yeast_query_matches_enumeration() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let full = enumerate_subgraph_sizes(&graph, 3, 4).unwrap();
+
+        // Every subgraph of size 3 and 4 as a target, in turns of a single target
+        for (k, result) in full.iter() {
+            for (label, &count) in result.counts().iter() {
+                let query = Query::new(&[label_adjacency(label, *k)], true).unwrap();
+                let mut esu = Esu::<Directed>::new(*k, &graph).unwrap();
+                esu.set_query(query).unwrap();
+                esu.enumerate();
+                let result = esu.result();
+                assert_eq!(result.unique_subgraphs(), 1);
+                assert_eq!(result.counts()[label], count);
+            }
+        }
+
+        // Targets of mixed sizes
+        let labels = full
+            .iter()
+            .flat_map(|(k, result)| result.counts().keys().take(2).map(|label| (*k, label)))
+            .collect::<Vec<_>>();
+        let targets = labels
+            .iter()
+            .map(|(k, label)| label_adjacency(label, *k))
+            .collect::<Vec<_>>();
+        let mut esu = Esu::<Directed>::new(4, &graph).unwrap();
+        esu.set_query(Query::new(&targets, true).unwrap()).unwrap();
+        esu.enumerate();
+        let results = esu.size_results();
+        for ((k, label), (full_k, full_result)) in
+            labels.iter().zip(full.iter().flat_map(|r| [r, r]))
+        {
+            assert_eq!(k, full_k);
+            let (_, result) = results.iter().find(|(size, _)| size == k).unwrap();
+            assert_eq!(result.counts()[*label], full_result.counts()[*label]);
+        }
+    }
+
+    #[test]
+    fn undir_ecoli_query_matches_enumeration() {
+        let filepath = "example/ecoli.txt";
+        let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
+        let full = enumerate_subgraphs(&graph, 4).unwrap();
+        let targets = full
+            .counts()
+            .keys()
+            .map(|label| label_adjacency(label, 4))
+            .collect::<Vec<_>>();
+        let mut esu = Esu::<Undirected>::new(4, &graph).unwrap();
+        esu.set_query(Query::new(&targets, false).unwrap()).unwrap();
+        esu.enumerate();
+        let result = esu.result();
+        assert_eq!(result.counts(), full.counts());
+        assert_eq!(result.total_subgraphs(), full.total_subgraphs());
+    }
+
+    #[test]
+    fn invalid_query() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let query = Query::from_graph6(&["&BP_"], true).unwrap();
+        let mut esu = Esu::<Directed>::new(4, &graph).unwrap();
+        assert!(esu.set_query(query.clone()).is_err());
+        let mut esu = Esu::<Directed>::new(3, &graph).unwrap();
+        esu.set_colors(vec![0; graph.node_count()]).unwrap();
+        assert!(esu.set_query(query).is_err());
+        let query = Query::from_graph6(&["Bw"], false).unwrap();
+        let mut esu = Esu::<Directed>::new(3, &graph).unwrap();
+        assert!(esu.set_query(query).is_err());
+    }
+
+    #[test]
+    fn dir_yeast_progress() {
+        use std::sync::{Arc, Mutex};
+
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut esu = Esu::new(3, &graph).unwrap();
+        {
+            let reports = reports.clone();
+            esu.set_progress(std::time::Duration::ZERO, move |progress| {
+                reports.lock().unwrap().push(*progress)
+            });
+        }
+        esu.enumerate();
+        let result = esu.result();
+
+        // The last report is the finished enumeration
+        let reports = reports.lock().unwrap();
+        let last = reports.last().unwrap();
+        assert!(reports.len() > 1);
+        assert_eq!(last.roots_done(), graph.node_count());
+        assert_eq!(last.roots_total(), graph.node_count());
+        assert_eq!(last.subgraphs(), result.total_subgraphs());
+        assert!(reports
+            .windows(2)
+            .all(|pair| pair[0].roots_done() <= pair[1].roots_done()));
+    }
+
+    #[test]
+    fn undir_ecoli_resume_parallel_checkpoint() {
+        use crate::enumerate::{Checkpoint, ParEsu};
+        use std::sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let filepath = "example/ecoli.txt";
+        let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
+        let full = enumerate_subgraphs(&graph, 4).unwrap();
+        let path = std::env::temp_dir().join("memoesu_undir_ecoli_resume_parallel_checkpoint.bin");
+
+        // Interrupt a parallel enumeration that writes a checkpoint after every batch
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut par_esu = ParEsu::new(4, &graph).unwrap();
+        par_esu
+            .set_checkpoint(&path, std::time::Duration::ZERO)
+            .unwrap();
+        par_esu.set_cancel(cancel.clone());
+        let seen = AtomicUsize::new(0);
+        let half = full.total_subgraphs() / 2;
+        par_esu.set_instances(move |_| {
+            if seen.fetch_add(1, Ordering::Relaxed) > half {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        });
+        par_esu.enumerate();
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(checkpoint.roots_done() < graph.node_count());
+
+        // The serial enumeration resumes from the same checkpoint format
+        let mut esu = Esu::new(4, &graph).unwrap();
+        esu.resume(checkpoint).unwrap();
+        esu.enumerate();
+        let resumed = esu.result();
+        assert_eq!(full.counts(), resumed.counts());
+        assert_eq!(full.total_subgraphs(), resumed.total_subgraphs());
+    }
+
+    #[test]
+    fn dir_yeast_shards_sum_to_full() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let full = enumerate_subgraphs(&graph, 4).unwrap();
+        let mut counts = Counts::default();
+        let mut total = 0;
+        for i in 1..=3 {
+            let mut esu = Esu::new(4, &graph).unwrap();
+            esu.set_shard(Shard::new(i, 3).unwrap());
+            esu.enumerate();
+            let result = esu.result();
+            assert!(result.total_subgraphs() < full.total_subgraphs());
+            for (label, count) in result.counts() {
+                *counts.entry(label.clone()).or_default() += count;
+            }
+            total += result.total_subgraphs();
+        }
+        assert_eq!(full.counts(), &counts);
+        assert_eq!(full.total_subgraphs(), total);
+    }
+
+    #[test]
+    fn dir_ecoli_memo_cache() {
+        // Subgraphs of up to four nodes are looked up without the memo
+        let filepath = "example/ecoli.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let mut small = Esu::new(4, &graph).unwrap();
+        small.enumerate();
+        assert!(small.memo_cache().is_empty());
+
+        let mut esu = Esu::new(5, &graph).unwrap();
+        esu.enumerate();
+        let cache = esu.memo_cache();
+        assert!(!cache.is_empty());
+
+        // Every subgraph of a second run is found in the cache
+        let mut cached = Esu::new(5, &graph).unwrap();
+        cached.set_memo_cache(cache.clone()).unwrap();
+        cached.enumerate();
+        assert_eq!(cached.memo_cache().len(), cache.len());
+        assert_eq!(cached.result().counts(), esu.result().counts());
+
+        // The cache only belongs to the same subgraph size and direction
+        let mut other = Esu::new(4, &graph).unwrap();
+        assert!(other.set_memo_cache(cache.clone()).is_err());
+        let undirected = load_numeric_graph::<Undirected>(filepath, false).unwrap();
+        let mut other = Esu::new(5, &undirected).unwrap();
+        assert!(other.set_memo_cache(cache).is_err());
+    }
+
+    #[test]
+    fn dir_yeast_lookup_matches_nauty() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let results = [true, false].map(|lookup| {
+            let mut esu = Esu::new(4, &graph).unwrap();
+            esu.set_min_size(2).unwrap();
+            if !lookup {
+                esu.lookup = vec![None; 4];
+            }
+            esu.identify_groups();
+            assert_eq!(esu.memo.is_empty(), lookup);
+            esu.group_results()
+        });
+        assert_eq!(results[0].total_subgraphs(), results[1].total_subgraphs());
+        assert_eq!(results[0].groups(), results[1].groups());
+    }
+
+    /// The summary of an enumeration that does not depend on the canonical
+    /// form: the sorted counts of the subgraph classes and the number of
+    /// subgraphs containing every node.
+    fn canonicalizer_summary<Ty: EdgeType, C: Canonicalizer>(
+        graph: &Graph<(), (), Ty>,
+        k: usize,
+        colors: Option<Vec<Color>>,
+    ) -> (Vec<usize>, Vec<usize>) {
+        let mut esu = Esu::<Ty, C>::with_canonicalizer(k, BitGraph::from_graph(graph)).unwrap();
+        if let Some(colors) = colors.clone() {
+            esu.set_colors(colors).unwrap();
+        }
+        esu.enumerate();
+        let mut counts = esu.result().counts().values().copied().collect::<Vec<_>>();
+        counts.sort_unstable();
+
+        let mut esu = Esu::<Ty, C>::with_canonicalizer(k, BitGraph::from_graph(graph)).unwrap();
+        if let Some(colors) = colors {
+            esu.set_colors(colors).unwrap();
+        }
+        esu.identify_groups();
+        let groups = esu.group_results();
+        let memberships = (0..graph.node_count())
+            .map(|v| groups.groups().get(&v).map_or(0, |g| g.values().sum()))
+            .collect();
+        (counts, memberships)
+    }
+
+    #[test]
+    fn canonicalizers_agree() {
+        use crate::enumerate::Exhaustive;
+        #[cfg(feature = "nauty")]
+        use crate::enumerate::{DenseNauty, SparseNauty, Traces};
+
+        let directed = load_numeric_graph::<Directed>("example/yeast.txt", false).unwrap();
+        let colors = (0..directed.node_count())
+            .map(|v| (v % 3) as Color)
+            .collect::<Vec<_>>();
+        let undirected = load_numeric_graph::<Undirected>("example/ecoli.txt", false).unwrap();
+        let expected = [
+            canonicalizer_summary::<Directed, DefaultCanonicalizer>(&directed, 3, None),
+            canonicalizer_summary::<Directed, DefaultCanonicalizer>(
+                &directed,
+                3,
+                Some(colors.clone()),
+            ),
+            canonicalizer_summary::<Undirected, DefaultCanonicalizer>(&undirected, 4, None),
+        ];
+        let check = |summaries: [(Vec<usize>, Vec<usize>); 3]| assert_eq!(summaries, expected);
+        macro_rules! check_backend {
+            ($backend:ty) => {
+                check([
+                    canonicalizer_summary::<Directed, $backend>(&directed, 3, None),
+                    canonicalizer_summary::<Directed, $backend>(&directed, 3, Some(colors.clone())),
+                    canonicalizer_summary::<Undirected, $backend>(&undirected, 4, None),
+                ])
+            };
+        }
+        check_backend!(Exhaustive);
+        #[cfg(feature = "nauty")]
+        {
+            check_backend!(DenseNauty);
+            check_backend!(SparseNauty);
+
+            // Traces only supports undirected graphs
+            let traces = canonicalizer_summary::<Undirected, Traces>(&undirected, 4, None);
+            assert_eq!(traces, expected[2]);
+            let colors = (0..undirected.node_count())
+                .map(|v| (v % 2) as Color)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                canonicalizer_summary::<Undirected, Traces>(&undirected, 3, Some(colors.clone())),
+                canonicalizer_summary::<Undirected, DefaultCanonicalizer>(
+                    &undirected,
+                    3,
+                    Some(colors)
+                ),
+            );
+            assert!(Esu::<Directed, Traces>::with_canonicalizer(
+                3,
+                BitGraph::from_graph(&directed)
+            )
+            .is_err());
+        }
+    }
 }
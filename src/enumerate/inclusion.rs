@@ -0,0 +1,255 @@
+use super::{has_label_arc, label_arcs, Counts, Label, NautyGraph, UnionFind};
+use ahash::HashMap;
+use anyhow::{bail, Result};
+use ndarray::Array2;
+
+/// The largest number of edges of a subgraph whose spanning subgraphs are enumerated.
+pub const MAX_INCLUSION_EDGES: usize = 20;
+
+/// The subgraph-inclusion matrix between isomorphism classes of size `k`.
+///
+/// Entry `(i, j)` is the number of distinct spanning subgraphs (i.e. edge
+/// subsets on the same nodes) of the supergraph class `j` that are isomorphic
+/// to the subgraph class `i`. The subgraph classes are all connected spanning
+/// subgraphs of the supergraph classes, which are themselves included.
+#[derive(Debug, Clone)]
+pub struct InclusionMatrix {
+    k: usize,
+    subgraphs: Vec<Label>,
+    supergraphs: Vec<Label>,
+    subgraph_index: HashMap<Label, usize>,
+    supergraph_index: HashMap<Label, usize>,
+    matrix: Array2<usize>,
+}
+impl InclusionMatrix {
+    /// Build the inclusion matrix of the given (uncolored and untyped)
+    /// canonical labels of size `k`.
+    pub fn new<'a, I: IntoIterator<Item = &'a Label>>(
+        labels: I,
+        k: usize,
+        is_directed: bool,
+    ) -> Result<Self> {
+        let m = k.div_ceil(64);
+        let mut supergraphs = labels.into_iter().cloned().collect::<Vec<_>>();
+        supergraphs.sort_unstable();
+        supergraphs.dedup();
+
+        let mut ngraph = NautyGraph::new(k, is_directed);
+        let mut memo = HashMap::<Vec<u64>, Label>::default();
+        let mut subgraph_index = HashMap::<Label, usize>::default();
+        let mut subgraphs = Vec::new();
+        let mut entries = Vec::new();
+        for (col, label) in supergraphs.iter().enumerate() {
+            if label.len() != k * m {
+                bail!("ERROR: Inclusion matrices are only supported for uncolored subgraphs without edge types.");
+            }
+            let words = label.words();
+            if (0..k).any(|u| has_label_arc(&words, k, u, u)) {
+                bail!("ERROR: Inclusion matrices are only supported for subgraphs without loops.");
+            }
+            let arcs = label_arcs(&words, k, is_directed);
+            if arcs.len() > MAX_INCLUSION_EDGES {
+                bail!(
+                    "ERROR: Inclusion matrices are limited to subgraphs with at most {MAX_INCLUSION_EDGES} edges."
+                );
+            }
+
+            // Every connected edge subset is a (non-induced) occurrence of its class
+            // (where the empty subset only connects a single node)
+            let mut counts = HashMap::<usize, usize>::default();
+            for mask in 0..(1u64 << arcs.len()) {
+                let subset = arcs
+                    .iter()
+                    .enumerate()
+                    .filter(|(idx, _)| mask >> idx & 1 == 1)
+                    .map(|(_, &arc)| arc)
+                    .collect::<Vec<_>>();
+                if !is_spanning_connected(&subset, k) {
+                    continue;
+                }
+                for &(u, v) in subset.iter() {
+                    ngraph.add_arc(u, v);
+                    if !is_directed {
+                        ngraph.add_arc(v, u);
+                    }
+                }
+                let sublabel = match memo.get(ngraph.graph()) {
+                    Some(sublabel) => sublabel.clone(),
+                    None => {
+                        ngraph.run();
+                        let sublabel = Label::from_words(ngraph.canon());
+                        memo.insert(ngraph.graph().to_vec(), sublabel.clone());
+                        ngraph.clear_canon();
+                        sublabel
+                    }
+                };
+                ngraph.clear_graph();
+                let next = subgraphs.len();
+                let row = *subgraph_index.entry(sublabel.clone()).or_insert(next);
+                if row == next {
+                    subgraphs.push(sublabel);
+                }
+                *counts.entry(row).or_insert(0) += 1;
+            }
+            entries.extend(counts.into_iter().map(|(row, count)| (row, col, count)));
+        }
+
+        let mut matrix = Array2::zeros((subgraphs.len(), supergraphs.len()));
+        for (row, col, count) in entries {
+            matrix[[row, col]] = count;
+        }
+        let supergraph_index = supergraphs
+            .iter()
+            .enumerate()
+            .map(|(idx, label)| (label.clone(), idx))
+            .collect();
+        Ok(Self {
+            k,
+            subgraphs,
+            supergraphs,
+            subgraph_index,
+            supergraph_index,
+            matrix,
+        })
+    }
+
+    /// The size of the subgraphs.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// The subgraph classes (rows of the matrix).
+    pub fn subgraphs(&self) -> &[Label] {
+        &self.subgraphs
+    }
+
+    /// The supergraph classes (columns of the matrix).
+    pub fn supergraphs(&self) -> &[Label] {
+        &self.supergraphs
+    }
+
+    /// The inclusion matrix with the subgraph classes as rows and the
+    /// supergraph classes as columns.
+    pub fn matrix(&self) -> &Array2<usize> {
+        &self.matrix
+    }
+
+    /// The number of spanning subgraphs of `supergraph` that are isomorphic to `subgraph`.
+    pub fn get(&self, subgraph: &Label, supergraph: &Label) -> usize {
+        match (
+            self.subgraph_index.get(subgraph),
+            self.supergraph_index.get(supergraph),
+        ) {
+            (Some(&row), Some(&col)) => self.matrix[[row, col]],
+            _ => 0,
+        }
+    }
+
+    /// The non-induced counts of every subgraph class given the induced
+    /// counts of the supergraph classes.
+    pub fn non_induced(&self, induced: &Counts) -> Counts {
+        let mut counts = Counts::default();
+        for (supergraph, &count) in induced.iter() {
+            let Some(&col) = self.supergraph_index.get(supergraph) else {
+                continue;
+            };
+            for (row, &inclusions) in self.matrix.column(col).iter().enumerate() {
+                if inclusions > 0 {
+                    *counts.entry(self.subgraphs[row].clone()).or_insert(0) += inclusions * count;
+                }
+            }
+        }
+        counts
+    }
+}
+
+/// Compute the non-induced counts of all subgraph classes of size `k` from the
+/// induced counts of an enumeration.
+pub fn non_induced_counts(induced: &Counts, k: usize, is_directed: bool) -> Result<Counts> {
+    let matrix = InclusionMatrix::new(induced.keys(), k, is_directed)?;
+    Ok(matrix.non_induced(induced))
+}
+
+/// Whether the arcs connect all `k` nodes.
+fn is_spanning_connected(arcs: &[(usize, usize)], k: usize) -> bool {
+    let mut nodes = UnionFind::new(k);
+    let merges = arcs.iter().filter(|&&(u, v)| nodes.union(u, v)).count();
+    merges + 1 == k
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use crate::{
+        enumerate::{canon, enumerate_subgraphs},
+        io::load_numeric_graph,
+    };
+    use petgraph::{Graph, Undirected};
+
+    #[test]
+    fn undir_k4_inclusions() {
+        let complete = canon(4, &[(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)], false);
+        let path = canon(4, &[(0, 1), (1, 2), (2, 3)], false);
+        let star = canon(4, &[(0, 1), (0, 2), (0, 3)], false);
+        let cycle = canon(4, &[(0, 1), (1, 2), (2, 3), (3, 0)], false);
+        let paw = canon(4, &[(0, 1), (1, 2), (2, 0), (0, 3)], false);
+        let diamond = canon(4, &[(0, 1), (1, 2), (2, 3), (3, 0), (0, 2)], false);
+
+        let matrix = InclusionMatrix::new([&complete, &path], 4, false).unwrap();
+        assert_eq!(matrix.supergraphs().len(), 2);
+        assert_eq!(matrix.subgraphs().len(), 6);
+        assert_eq!(matrix.get(&path, &complete), 12);
+        assert_eq!(matrix.get(&star, &complete), 4);
+        assert_eq!(matrix.get(&cycle, &complete), 3);
+        assert_eq!(matrix.get(&paw, &complete), 12);
+        assert_eq!(matrix.get(&diamond, &complete), 6);
+        assert_eq!(matrix.get(&complete, &complete), 1);
+        assert_eq!(matrix.get(&path, &path), 1);
+        assert_eq!(matrix.get(&complete, &path), 0);
+        assert_eq!(matrix.matrix().sum(), 12 + 4 + 3 + 12 + 6 + 1 + 1);
+    }
+
+    #[test]
+    fn dir_feed_forward_loop_inclusions() {
+        let ffl = canon(3, &[(0, 1), (1, 2), (0, 2)], true);
+        let chain = canon(3, &[(0, 1), (1, 2)], true);
+        let out_star = canon(3, &[(0, 1), (0, 2)], true);
+        let in_star = canon(3, &[(0, 2), (1, 2)], true);
+        let mut induced = Counts::default();
+        induced.insert(ffl.clone(), 2);
+        induced.insert(chain.clone(), 5);
+        let counts = non_induced_counts(&induced, 3, true).unwrap();
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts[&ffl], 2);
+        assert_eq!(counts[&chain], 7);
+        assert_eq!(counts[&out_star], 2);
+        assert_eq!(counts[&in_star], 2);
+    }
+
+    #[test]
+    fn undir_ecoli_s3_non_induced_paths() {
+        let filepath = "example/ecoli.txt";
+        let graph: Graph<(), (), Undirected> = load_numeric_graph(filepath, false).unwrap();
+        let result = enumerate_subgraphs(&graph, 3).unwrap();
+        let counts = non_induced_counts(result.counts(), 3, false).unwrap();
+
+        // Every pair of neighbors of a node is a (non-induced) path
+        let paths = graph
+            .node_indices()
+            .map(|v| graph.neighbors(v).collect::<ahash::HashSet<_>>().len())
+            .map(|d| d * d.saturating_sub(1) / 2)
+            .sum::<usize>();
+        let path = canon(3, &[(0, 1), (1, 2)], false);
+        assert_eq!(counts[&path], paths);
+    }
+
+    #[test]
+    fn invalid_labels() {
+        let colored: Label = vec![0, 0, 0, 1, 1, 1].into();
+        assert!(InclusionMatrix::new([&colored], 3, false).is_err());
+
+        // The chain 1 -> 2 -> 3 with a loop on 3
+        let looped = canon(3, &[(0, 1), (1, 2), (2, 2)], true);
+        assert!(InclusionMatrix::new([&looped], 3, true).is_err());
+    }
+}
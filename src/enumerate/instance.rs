@@ -0,0 +1,45 @@
+use super::{Label, Positions};
+
+/// A single occurrence of a subgraph, passed to the instance callback of
+/// an enumerator as soon as it is found.
+pub struct Instance<'a> {
+    nodes: &'a [usize],
+    label: &'a Label,
+    positions: &'a Positions,
+}
+
+impl<'a> Instance<'a> {
+    pub(crate) fn new(nodes: &'a [usize], label: &'a Label, positions: &'a Positions) -> Self {
+        Self {
+            nodes,
+            label,
+            positions,
+        }
+    }
+
+    /// The number of nodes in the subgraph.
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The graph nodes of the subgraph in the order they were enumerated.
+    pub fn nodes(&self) -> &[usize] {
+        self.nodes
+    }
+
+    /// The canonical label of the subgraph.
+    pub fn label(&self) -> &Label {
+        self.label
+    }
+
+    /// The graph nodes of the subgraph in canonical order.
+    ///
+    /// The `i`-th node is the one mapped onto position `i` of the canonical
+    /// label by nauty (i.e. `nodes[lab[i]]`).
+    pub fn canonical_nodes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.positions
+            .iter()
+            .take(self.nodes.len())
+            .map(|&(node_label, _)| self.nodes[node_label as usize])
+    }
+}
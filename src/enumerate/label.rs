@@ -0,0 +1,211 @@
+use std::{borrow::Cow, cmp::Ordering, sync::Arc};
+
+/// The bits kept of every row of a label packed into a `u64`.
+const NARROW_BITS: usize = 8;
+
+/// The bits kept of every row of a label packed into a `u128`.
+const WIDE_BITS: usize = 11;
+
+/// The words of a (canonical) nauty graph, which identify a subgraph up to
+/// isomorphism.
+///
+/// The adjacency matrix of an uncolored subgraph without edge types holds one
+/// word per node, of which only the leading bits are set. Matrices of up to
+/// 8 nodes are therefore packed into a `u64` and of up to 11 nodes into a
+/// `u128`, so that these labels are hashed and stored without touching the
+/// heap. All other labels (e.g. with node colors or edge type layers) keep
+/// their words.
+///
+/// Packing is lossless, so labels are equal (and ordered) exactly like their
+/// words.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Label(Repr);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Repr {
+    Narrow {
+        rows: u8,
+        bits: u64,
+    },
+    /// The two halves of a `u128`, which keep the label 8-byte aligned.
+    Wide {
+        rows: u8,
+        bits: [u64; 2],
+    },
+    Words(Arc<[u64]>),
+}
+
+impl Label {
+    /// The label of the words of a nauty graph.
+    pub fn from_words(words: &[u64]) -> Self {
+        if let Some(bits) = pack(words, NARROW_BITS) {
+            let rows = words.len() as u8;
+            Self(Repr::Narrow {
+                rows,
+                bits: bits as u64,
+            })
+        } else if let Some(bits) = pack(words, WIDE_BITS) {
+            let rows = words.len() as u8;
+            Self(Repr::Wide {
+                rows,
+                bits: [(bits >> 64) as u64, bits as u64],
+            })
+        } else {
+            Self(Repr::Words(words.into()))
+        }
+    }
+
+    /// The words of the nauty graph, which are only allocated for packed labels.
+    pub fn words(&self) -> Cow<'_, [u64]> {
+        match &self.0 {
+            Repr::Narrow { rows, bits } => unpack(*bits as u128, *rows, NARROW_BITS).into(),
+            Repr::Wide { rows, bits } => {
+                let bits = (bits[0] as u128) << 64 | bits[1] as u128;
+                unpack(bits, *rows, WIDE_BITS).into()
+            }
+            Repr::Words(words) => Cow::Borrowed(words),
+        }
+    }
+
+    /// The number of words of the nauty graph.
+    pub fn len(&self) -> usize {
+        match &self.0 {
+            Repr::Narrow { rows, .. } | Repr::Wide { rows, .. } => *rows as usize,
+            Repr::Words(words) => words.len(),
+        }
+    }
+
+    /// Whether the label has no words.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the label is packed into an integer.
+    pub fn is_packed(&self) -> bool {
+        !matches!(self.0, Repr::Words(_))
+    }
+}
+
+/// Packs the leading `row_bits` bits of every word, if no other bits are set.
+fn pack(words: &[u64], row_bits: usize) -> Option<u128> {
+    if words.len() > row_bits {
+        return None;
+    }
+    let shift = u64::BITS as usize - row_bits;
+    words.iter().try_fold(0u128, |bits, &word| {
+        (word << row_bits == 0).then_some(bits << row_bits | (word >> shift) as u128)
+    })
+}
+
+/// Unpacks the leading `row_bits` bits of every word.
+fn unpack(bits: u128, rows: u8, row_bits: usize) -> Vec<u64> {
+    let shift = u64::BITS as usize - row_bits;
+    let mask = (1u128 << row_bits) - 1;
+    (0..rows as usize)
+        .rev()
+        .map(|row| ((bits >> (row * row_bits) & mask) as u64) << shift)
+        .collect()
+}
+
+impl PartialOrd for Label {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Label {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // The rows are packed from the most significant bit, so packed labels
+        // of the same number of rows are ordered like their integers
+        match (&self.0, &other.0) {
+            (
+                Repr::Narrow { rows, bits },
+                Repr::Narrow {
+                    rows: o_rows,
+                    bits: o_bits,
+                },
+            ) if rows == o_rows => bits.cmp(o_bits),
+            (
+                Repr::Wide { rows, bits },
+                Repr::Wide {
+                    rows: o_rows,
+                    bits: o_bits,
+                },
+            ) if rows == o_rows => bits.cmp(o_bits),
+            _ => self.words().cmp(&other.words()),
+        }
+    }
+}
+
+impl From<&[u64]> for Label {
+    fn from(words: &[u64]) -> Self {
+        Self::from_words(words)
+    }
+}
+
+impl From<Vec<u64>> for Label {
+    fn from(words: Vec<u64>) -> Self {
+        Self::from_words(&words)
+    }
+}
+
+impl FromIterator<u64> for Label {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        Self::from_words(&iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use crate::enumerate::{arcs_graph, DefaultCanonicalizer};
+
+    #[test]
+    fn label_roundtrip() {
+        for k in [1, 3, 8, 9, 11, 12] {
+            let arcs = (1..k)
+                .map(|v| (v - 1, v))
+                .chain([(k - 1, 0)])
+                .collect::<Vec<_>>();
+            let ngraph = arcs_graph::<DefaultCanonicalizer>(k, &arcs, true);
+            let words = ngraph.canon();
+            let label = Label::from_words(words);
+            assert_eq!(label.is_packed(), k <= 11);
+            assert_eq!(label.len(), k);
+            assert_eq!(label.words().as_ref(), words);
+        }
+
+        // Colored labels keep their words
+        let words = vec![1 << 63, 0, 1];
+        let label = Label::from(words.clone());
+        assert!(!label.is_packed());
+        assert_eq!(label.words().as_ref(), words.as_slice());
+        assert!(Label::from_words(&[]).is_empty());
+    }
+
+    #[test]
+    fn label_ordering() {
+        // Labels are ordered like their words regardless of the packing
+        let words = [
+            vec![0, 1 << 63],
+            vec![1 << 63],
+            vec![1 << 63, 1 << 62],
+            vec![1 << 63, 1],
+            vec![0, 1 << 62],
+            vec![1 << 62, 1 << 63],
+            vec![1 << 50; 10],
+            vec![1 << 53; 10],
+            [vec![1 << 53; 9], vec![1 << 63]].concat(),
+            vec![1 << 62; 12],
+        ];
+        for a in words.iter() {
+            for b in words.iter() {
+                assert_eq!(
+                    Label::from(a.clone()).cmp(&Label::from(b.clone())),
+                    a.cmp(b)
+                );
+                assert_eq!(Label::from(a.clone()) == Label::from(b.clone()), a == b);
+            }
+        }
+    }
+}
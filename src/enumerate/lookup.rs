@@ -0,0 +1,178 @@
+use super::{is_default, positions, BitGraph, Canonicalizer, Label, NautyGraph, Positions};
+use std::sync::OnceLock;
+
+/// The largest subgraph size of directed graphs with a lookup table.
+pub const MAX_DIRECTED_LOOKUP: usize = 4;
+
+/// The largest subgraph size of undirected graphs with a lookup table.
+pub const MAX_UNDIRECTED_LOOKUP: usize = 5;
+
+static DIRECTED: [OnceLock<LookupTable>; MAX_DIRECTED_LOOKUP] =
+    [const { OnceLock::new() }; MAX_DIRECTED_LOOKUP];
+static UNDIRECTED: [OnceLock<LookupTable>; MAX_UNDIRECTED_LOOKUP] =
+    [const { OnceLock::new() }; MAX_UNDIRECTED_LOOKUP];
+
+/// The canonical label and positions of every subgraph of a small size,
+/// indexed by the packed bits of its adjacency matrix.
+///
+/// Directed subgraphs use all `size * size` bits of the matrix (including
+/// loops) and undirected subgraphs the `size * (size - 1) / 2` bits above
+/// the diagonal. An entry is canonicalized by nauty the first time its
+/// matrix is seen, and the tables are shared by all enumerations of the
+/// process, so nauty only runs once per matrix.
+///
+/// The tables only hold uncolored subgraphs without edge types.
+pub(crate) struct LookupTable {
+    size: usize,
+    is_directed: bool,
+    entries: Box<[OnceLock<(Label, Positions)>]>,
+}
+impl LookupTable {
+    /// The shared table of the subgraph size, if it is small enough.
+    pub(crate) fn get(size: usize, is_directed: bool) -> Option<&'static Self> {
+        let tables: &'static [OnceLock<Self>] = if is_directed { &DIRECTED } else { &UNDIRECTED };
+        let table = tables.get(size.checked_sub(1)?)?;
+        Some(table.get_or_init(|| Self::new(size, is_directed)))
+    }
+
+    /// The lookup tables of every subgraph size up to the motif size, if the
+    /// subgraphs of the graph can be looked up.
+    ///
+    /// The tables hold the labels of the default canonicalizer, so other
+    /// backends canonicalize every subgraph.
+    pub(crate) fn for_sizes<C: Canonicalizer>(
+        motif_size: usize,
+        graph: &BitGraph,
+        colored: bool,
+    ) -> Vec<Option<&'static Self>> {
+        let supported = is_default::<C>() && !colored && graph.max_edge_color().is_none();
+        (1..=motif_size)
+            .map(|size| {
+                supported
+                    .then(|| Self::get(size, graph.is_directed))
+                    .flatten()
+            })
+            .collect()
+    }
+
+    fn new(size: usize, is_directed: bool) -> Self {
+        let bits = if is_directed {
+            size * size
+        } else {
+            size * (size - 1) / 2
+        };
+        let entries = (0..1usize << bits).map(|_| OnceLock::new()).collect();
+        Self {
+            size,
+            is_directed,
+            entries,
+        }
+    }
+
+    /// The canonical label and positions of the subgraph induced by the nodes.
+    pub(crate) fn lookup(&self, graph: &BitGraph, nodes: &[usize]) -> &(Label, Positions) {
+        let index = self.index(graph, nodes);
+        self.entries[index].get_or_init(|| self.canonicalize(index))
+    }
+
+    /// Pack the adjacency matrix of the subgraph induced by the nodes.
+    fn index(&self, graph: &BitGraph, nodes: &[usize]) -> usize {
+        debug_assert_eq!(nodes.len(), self.size);
+        let mut index = 0;
+        let mut bit = 0;
+        for (i, &u) in nodes.iter().enumerate() {
+            if self.is_directed {
+                for &v in nodes {
+                    index |= (graph.is_connected_directed(u, v) as usize) << bit;
+                    bit += 1;
+                }
+            } else {
+                for &v in &nodes[i + 1..] {
+                    index |= (graph.is_connected(u, v) as usize) << bit;
+                    bit += 1;
+                }
+            }
+        }
+        index
+    }
+
+    /// Run nauty on the adjacency matrix packed in the index.
+    fn canonicalize(&self, index: usize) -> (Label, Positions) {
+        let mut ngraph = NautyGraph::new(self.size, self.is_directed);
+        let mut bit = 0;
+        for i in 0..self.size {
+            if self.is_directed {
+                for j in 0..self.size {
+                    if index >> bit & 1 == 1 {
+                        ngraph.add_arc(i, j);
+                    }
+                    bit += 1;
+                }
+            } else {
+                for j in i + 1..self.size {
+                    if index >> bit & 1 == 1 {
+                        ngraph.add_arc(i, j);
+                        ngraph.add_arc(j, i);
+                    }
+                    bit += 1;
+                }
+            }
+        }
+        ngraph.run();
+        (Label::from_words(ngraph.canon()), positions(&ngraph))
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use crate::enumerate::DefaultCanonicalizer;
+
+    #[test]
+    fn lookup_matches_nauty() {
+        // A directed path with a loop at its end
+        let graph = BitGraph::from_edges(4, &[(0, 1), (1, 2), (2, 3), (3, 3)], true);
+        let table = LookupTable::get(4, true).unwrap();
+        let (label, positions) = table.lookup(&graph, &[0, 1, 2, 3]);
+        let mut ngraph = NautyGraph::new(4, true);
+        for (u, v) in [(0, 1), (1, 2), (2, 3), (3, 3)] {
+            ngraph.add_arc(u, v);
+        }
+        ngraph.run();
+        assert_eq!(label.words().as_ref(), ngraph.canon());
+        assert_eq!(positions, &super::positions(&ngraph));
+        let (reversed, _) = table.lookup(&graph, &[3, 2, 1, 0]);
+        assert_eq!(label, reversed);
+        let looped = BitGraph::from_edges(4, &[(0, 0), (0, 1), (1, 2), (2, 3)], true);
+        let (start, _) = table.lookup(&looped, &[0, 1, 2, 3]);
+        assert_ne!(label, start);
+
+        // Undirected subgraphs only depend on the entries above the diagonal
+        let graph = BitGraph::from_edges(5, &[(0, 1), (1, 2), (2, 3), (3, 4)], false);
+        let table = LookupTable::get(5, false).unwrap();
+        let (path, _) = table.lookup(&graph, &[0, 1, 2, 3, 4]);
+        let (permuted, _) = table.lookup(&graph, &[4, 3, 2, 1, 0]);
+        assert_eq!(path, permuted);
+    }
+
+    #[test]
+    fn lookup_sizes() {
+        assert!(LookupTable::get(0, true).is_none());
+        assert!(LookupTable::get(MAX_DIRECTED_LOOKUP + 1, true).is_none());
+        assert!(LookupTable::get(MAX_UNDIRECTED_LOOKUP, false).is_some());
+        assert!(LookupTable::get(MAX_UNDIRECTED_LOOKUP + 1, false).is_none());
+
+        // Colored and typed subgraphs are canonicalized by nauty
+        let graph = BitGraph::from_edges(3, &[(0, 1), (1, 2)], true);
+        assert!(
+            LookupTable::for_sizes::<DefaultCanonicalizer>(3, &graph, false)
+                .iter()
+                .all(Option::is_some)
+        );
+        assert!(
+            LookupTable::for_sizes::<DefaultCanonicalizer>(3, &graph, true)
+                .iter()
+                .all(Option::is_none)
+        );
+    }
+}
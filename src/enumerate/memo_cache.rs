@@ -0,0 +1,267 @@
+use super::{
+    binary::{self, BinaryReader, BinaryWriter},
+    ngraph::edge_layers,
+    BitGraph, Label,
+};
+use ahash::HashMap;
+use anyhow::{bail, Context, Result};
+use std::{
+    fmt,
+    io::{Read, Write},
+    path::Path,
+};
+
+/// The first bytes of a memo cache file.
+const MAGIC: &[u8; 8] = b"MEMOESUM";
+
+/// The version of the memo cache format.
+const VERSION: u64 = 2;
+
+/// The parameters of an enumeration that determine the canonical labels, so
+/// that only memos of interchangeable enumerations are reused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MemoKey {
+    motif_size: usize,
+    is_directed: bool,
+    loops: bool,
+    colored: bool,
+    layers: usize,
+    canonicalizer: String,
+}
+impl MemoKey {
+    pub(crate) fn new(
+        motif_size: usize,
+        graph: &BitGraph,
+        colored: bool,
+        canonicalizer: &str,
+    ) -> Self {
+        Self {
+            motif_size,
+            is_directed: graph.is_directed,
+            loops: graph.has_loops(),
+            colored,
+            layers: graph.max_edge_color().map_or(1, edge_layers),
+            canonicalizer: canonicalizer.to_string(),
+        }
+    }
+
+    /// The size of the subgraphs.
+    pub(crate) fn motif_size(&self) -> usize {
+        self.motif_size
+    }
+
+    pub(crate) fn write<W: Write>(&self, writer: &mut BinaryWriter<W>) -> Result<()> {
+        for value in [
+            self.motif_size as u64,
+            self.is_directed as u64,
+            self.loops as u64,
+            self.colored as u64,
+            self.layers as u64,
+        ] {
+            writer.put(value)?;
+        }
+        writer.put_str(&self.canonicalizer)
+    }
+
+    pub(crate) fn read<R: Read>(reader: &mut BinaryReader<R>) -> Result<Self> {
+        Ok(Self {
+            motif_size: reader.get()? as usize,
+            is_directed: reader.get()? == 1,
+            loops: reader.get()? == 1,
+            colored: reader.get()? == 1,
+            layers: reader.get()? as usize,
+            canonicalizer: reader.get_string()?,
+        })
+    }
+}
+
+impl fmt::Display for MemoKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "subgraph size {} of {} graph {} loops",
+            self.motif_size,
+            if self.is_directed {
+                "a directed"
+            } else {
+                "an undirected"
+            },
+            if self.loops { "with" } else { "without" }
+        )?;
+        if self.colored {
+            write!(f, ", with node colors")?;
+        }
+        if self.layers > 1 {
+            write!(f, ", with {} edge type layers", self.layers)?;
+        }
+        write!(f, ", canonicalized by {}", self.canonicalizer)
+    }
+}
+
+/// The memoized canonical labels of the (non-canonical) subgraphs of an
+/// enumeration, so that later enumerations with the same subgraph size,
+/// direction, loops, and canonicalizer can skip the canonicalization of the
+/// subgraphs seen before.
+#[derive(Debug, Clone, Default)]
+pub struct MemoCache {
+    key: Option<MemoKey>,
+    labels: HashMap<Label, Label>,
+}
+impl MemoCache {
+    pub(crate) fn new(key: MemoKey, labels: HashMap<Label, Label>) -> Self {
+        Self {
+            key: Some(key),
+            labels,
+        }
+    }
+
+    /// The number of memoized subgraphs.
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Whether no subgraphs are memoized.
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Add the memoized labels of another cache of the same enumeration.
+    pub(crate) fn extend(&mut self, other: MemoCache) {
+        if self.key.is_none() {
+            self.key = other.key;
+        }
+        self.labels.extend(other.labels);
+    }
+
+    /// Take the memoized labels if they belong to an enumeration with the
+    /// given parameters.
+    pub(crate) fn into_labels(self, key: MemoKey) -> Result<HashMap<Label, Label>> {
+        match self.key {
+            Some(cached) if cached != key => bail!(
+                "ERROR: The memo cache was written for {cached} but the enumeration is for {key}."
+            ),
+            _ => Ok(self.labels),
+        }
+    }
+
+    /// Load a memo cache from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut reader = binary::open(path)
+            .with_context(|| format!("ERROR: Unable to open memo cache: {}", path.display()))?;
+        Self::read(&mut reader)
+            .with_context(|| format!("ERROR: Unable to read memo cache: {}", path.display()))
+    }
+
+    /// Write the memo cache to a file (through a temporary file).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        binary::save(path.as_ref(), |writer| self.write(writer))
+    }
+
+    fn write<W: Write>(&self, writer: &mut BinaryWriter<W>) -> Result<()> {
+        let Some(key) = &self.key else {
+            bail!("ERROR: The memo cache does not belong to an enumeration.");
+        };
+        writer.put_bytes(MAGIC)?;
+        writer.put(VERSION)?;
+        key.write(writer)?;
+        writer.put(self.labels.len() as u64)?;
+        for (original, label) in self.labels.iter() {
+            writer.put_label(original)?;
+            writer.put_label(label)?;
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(reader: &mut BinaryReader<R>) -> Result<Self> {
+        if &reader.get_magic()? != MAGIC {
+            bail!("ERROR: Not a memoesu memo cache.");
+        }
+        let version = reader.get()?;
+        if version != VERSION {
+            bail!("ERROR: Unsupported memo cache version {version}.");
+        }
+        let key = MemoKey::read(reader)?;
+        // Every memo takes at least the lengths of its two labels
+        let len = reader.get_len(16)?;
+        let mut labels = HashMap::default();
+        for _ in 0..len {
+            let original = reader.get_label()?;
+            labels.insert(original, reader.get_label()?);
+        }
+        Ok(Self::new(key, labels))
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use crate::enumerate::{Canonicalizer, DefaultCanonicalizer, Exhaustive};
+
+    const CANON: &str = DefaultCanonicalizer::NAME;
+
+    #[test]
+    fn memo_cache_roundtrip() {
+        let graph = BitGraph::from_edges(3, &[(0, 1), (1, 2)], true);
+        let key = MemoKey::new(3, &graph, false, CANON);
+        let mut labels = HashMap::default();
+        let original: Label = vec![1, 2, 3].into();
+        let label: Label = vec![3, 2, u64::MAX].into();
+        labels.insert(original.clone(), label.clone());
+        let cache = MemoCache::new(key.clone(), labels);
+
+        let mut writer = BinaryWriter::new(Vec::new());
+        cache.write(&mut writer).unwrap();
+        let buffer = writer.into_inner();
+        let loaded = MemoCache::read(&mut buffer.as_slice().into()).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.key, Some(key.clone()));
+        let labels = loaded.clone().into_labels(key.clone()).unwrap();
+        assert_eq!(labels[&original], label);
+
+        // The cache only belongs to enumerations with the same parameters
+        assert!(loaded
+            .clone()
+            .into_labels(MemoKey::new(4, &graph, false, CANON))
+            .is_err());
+        assert!(loaded
+            .clone()
+            .into_labels(MemoKey::new(3, &graph, true, CANON))
+            .is_err());
+        let undirected = BitGraph::from_edges(3, &[(0, 1), (1, 2)], false);
+        assert!(loaded
+            .clone()
+            .into_labels(MemoKey::new(3, &undirected, false, CANON))
+            .is_err());
+        let loops = BitGraph::from_edges(3, &[(0, 1), (1, 1)], true);
+        assert!(loaded
+            .clone()
+            .into_labels(MemoKey::new(3, &loops, false, CANON))
+            .is_err());
+        let other = MemoKey::new(3, &graph, false, Exhaustive::NAME);
+        assert_eq!(other == key, CANON == Exhaustive::NAME);
+        assert!(MemoCache::default().into_labels(key).is_ok());
+    }
+
+    #[test]
+    fn invalid_memo_cache() {
+        assert!(MemoCache::read(&mut b"MEMOESU".as_slice().into()).is_err());
+        assert!(MemoCache::read(&mut b"MEMOESU\0".as_slice().into()).is_err());
+        let mut file = MAGIC.to_vec();
+        file.extend(2u64.to_le_bytes());
+        assert!(MemoCache::read(&mut file.as_slice().into()).is_err());
+        assert!(MemoCache::default()
+            .write(&mut BinaryWriter::new(Vec::new()))
+            .is_err());
+
+        // A truncated cache that claims more memos than fit in the file
+        let graph = BitGraph::from_edges(3, &[(0, 1), (1, 2)], true);
+        let cache = MemoCache::new(MemoKey::new(3, &graph, false, CANON), HashMap::default());
+        let mut writer = BinaryWriter::new(Vec::new());
+        cache.write(&mut writer).unwrap();
+        let mut file = writer.into_inner();
+        let len = file.len();
+        file[len - 8..].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(MemoCache::read(&mut file.as_slice().into()).is_err());
+    }
+}
@@ -1,21 +1,240 @@
+mod binary;
 mod bitgraph;
+mod canonicalizer;
+mod checkpoint;
 mod esu;
+mod inclusion;
+mod instance;
+mod label;
+mod lookup;
+mod memo_cache;
+#[cfg(feature = "nauty")]
+mod nauty;
 mod ngraph;
 mod parallel_esu;
+mod progress;
+mod query;
 mod result;
+mod sampling;
+mod shard;
 
 use std::sync::Arc;
 
 use ahash::HashMap;
-pub use bitgraph::BitGraph;
-pub use esu::{enumerate_subgraphs, group_subgraphs};
-pub use ngraph::NautyGraph;
-pub use parallel_esu::parallel_enumerate_subgraphs;
+use anyhow::{bail, Result};
+pub use bitgraph::{BitGraph, Neighbors, Representation};
+pub(crate) use canonicalizer::is_default;
+pub use canonicalizer::{Canonicalizer, DefaultCanonicalizer, Exhaustive};
+pub use checkpoint::Checkpoint;
+pub(crate) use checkpoint::Checkpointer;
+pub use esu::{enumerate_subgraph_sizes, enumerate_subgraphs, group_subgraphs, Esu};
+pub use inclusion::{non_induced_counts, InclusionMatrix, MAX_INCLUSION_EDGES};
+pub use instance::Instance;
+pub use label::Label;
+pub(crate) use lookup::LookupTable;
+pub use lookup::{MAX_DIRECTED_LOOKUP, MAX_UNDIRECTED_LOOKUP};
+pub use memo_cache::MemoCache;
+pub(crate) use memo_cache::MemoKey;
+#[cfg(feature = "nauty")]
+pub use nauty::{DenseNauty, SparseNauty, Traces};
+pub use ngraph::{NautyGraph, Nodes};
+pub(crate) use ngraph::{EDGE_CODE_BITS, TYPE_ROW_TAG};
+pub use parallel_esu::{
+    parallel_enumerate_subgraph_sizes, parallel_enumerate_subgraphs, parallel_group_subgraphs,
+    ParEsu,
+};
+use petgraph::EdgeType;
+pub use progress::Progress;
+pub(crate) use progress::ProgressTracker;
+pub use query::Query;
 pub use result::{EnumResult, GroupResult};
+pub use sampling::{Estimate, Estimates, Sampling};
+pub use shard::Shard;
 
 pub type Counts = HashMap<Label, usize>;
-pub type Label = Arc<[u64]>;
 pub type Groups = HashMap<usize, HashMap<GroupInfo, usize>>;
 pub type GroupInfo = (Label, NodeLabel, Orbit);
 pub type Orbit = i32;
 pub type NodeLabel = i32;
+pub type Color = u32;
+pub type EdgeColor = u32;
+
+/// The enumeration results of each subgraph size, in increasing order of size.
+pub type SizeResults = Vec<(usize, EnumResult)>;
+
+/// The node label and orbit of every position of a (non-canonical) subgraph.
+type Positions = Arc<[(NodeLabel, Orbit)]>;
+
+/// Collects the node labels and orbits of the last nauty run.
+fn positions<C: Canonicalizer>(ngraph: &NautyGraph<C>) -> Positions {
+    ngraph
+        .nodes
+        .lab
+        .iter()
+        .zip(ngraph.nodes.orbits.iter())
+        .map(|(&node_label, &orbit)| (node_label, orbit))
+        .collect()
+}
+
+/// Adds the subgraph membership and orbit positions of all nodes in a subgraph
+/// to the node groups.
+fn update_groups(groups: &mut Groups, current: &[usize], label: &Label, positions: &Positions) {
+    for (&node_idx, &(node_label, orbit)) in current.iter().zip(positions.iter()) {
+        let group = groups.entry(node_idx).or_default();
+        let group_info = (label.clone(), node_label, orbit);
+        *group.entry(group_info).or_insert(0) += 1;
+    }
+}
+
+/// Merges the node groups of an independent enumeration into another.
+fn merge_groups(groups: &mut Groups, other: Groups) {
+    for (node_idx, group) in other {
+        let merged = groups.entry(node_idx).or_default();
+        for (group_info, count) in group {
+            *merged.entry(group_info).or_insert(0) += count;
+        }
+    }
+}
+
+/// Validates that a motif size can be enumerated.
+fn validate_motif_size(motif_size: usize) -> Result<()> {
+    if motif_size == 0 {
+        bail!("ERROR: Subgraph size must be at least 1.");
+    }
+    Ok(())
+}
+
+/// Validates that a range of subgraph sizes can be enumerated.
+fn validate_size_range(min_size: usize, max_size: usize) -> Result<()> {
+    validate_motif_size(min_size)?;
+    if min_size > max_size {
+        bail!("ERROR: Minimum subgraph size ({min_size}) must not exceed the maximum subgraph size ({max_size}).");
+    }
+    Ok(())
+}
+
+/// Validates that there is a color for every node of the graph.
+fn validate_colors(colors: &[Color], graph: &BitGraph) -> Result<()> {
+    if colors.len() != graph.n {
+        bail!(
+            "ERROR: Expected a color for each of the {} nodes but found {} colors.",
+            graph.n,
+            colors.len()
+        );
+    }
+    Ok(())
+}
+
+/// Validates that the edge types of a graph can be used for subgraphs of the given size.
+fn validate_edge_colors(motif_size: usize, graph: &BitGraph) -> Result<()> {
+    let Some(max_color) = graph.max_edge_color() else {
+        return Ok(());
+    };
+    if motif_size > ngraph::MAX_TYPED_NODES {
+        bail!(
+            "ERROR: Subgraphs with typed edges can have at most {} nodes.",
+            ngraph::MAX_TYPED_NODES
+        );
+    }
+    if max_color > ngraph::MAX_EDGE_COLOR {
+        bail!(
+            "ERROR: Edge types must be at most {} but found {max_color}.",
+            ngraph::MAX_EDGE_COLOR
+        );
+    }
+    Ok(())
+}
+
+/// Creates the nauty graph used to canonicalize subgraphs of the given size,
+/// respecting the edge types of the graph and (optionally) the node colors.
+fn nauty_graph<C: Canonicalizer>(size: usize, graph: &BitGraph, colored: bool) -> NautyGraph<C> {
+    NautyGraph::with_backend(size, graph.is_directed, colored, graph.max_edge_color())
+}
+
+/// Validates that a graph matches the edge type of the enumerator.
+fn validate_edge_type<Ty: EdgeType>(graph: &BitGraph) -> Result<()> {
+    if graph.is_directed != Ty::is_directed() {
+        bail!("ERROR: The edge type of the graph does not match the edge type of the enumerator.");
+    }
+    Ok(())
+}
+
+/// Validates that the canonicalizer supports the edge type of a graph.
+fn validate_canonicalizer<C: Canonicalizer>(graph: &BitGraph) -> Result<()> {
+    if graph.is_directed && !C::SUPPORTS_DIRECTED {
+        bail!(
+            "ERROR: The {} canonicalizer only supports undirected graphs.",
+            C::NAME
+        );
+    }
+    Ok(())
+}
+
+/// Whether the adjacency rows of a canonical label with `k` nodes contain the arc `(u, v)`.
+pub(crate) fn has_label_arc(label: &[u64], k: usize, u: usize, v: usize) -> bool {
+    let m = k.div_ceil(64);
+    label[u * m + v / 64] >> (63 - v % 64) & 1 == 1
+}
+
+/// The arcs between distinct nodes of a canonical label with `k` nodes,
+/// where undirected edges are only returned once with `u < v`.
+pub(crate) fn label_arcs(label: &[u64], k: usize, is_directed: bool) -> Vec<(usize, usize)> {
+    (0..k)
+        .flat_map(|u| (0..k).map(move |v| (u, v)))
+        .filter(|&(u, v)| u != v && (is_directed || u < v) && has_label_arc(label, k, u, v))
+        .collect()
+}
+
+/// Disjoint sets of the elements `0..n`, each represented by its smallest element.
+pub(crate) struct UnionFind {
+    parent: Vec<usize>,
+}
+impl UnionFind {
+    pub(crate) fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n).collect(),
+        }
+    }
+
+    /// The representative of the set of `u`.
+    pub(crate) fn find(&mut self, mut u: usize) -> usize {
+        while self.parent[u] != u {
+            self.parent[u] = self.parent[self.parent[u]];
+            u = self.parent[u];
+        }
+        u
+    }
+
+    /// Merge the sets of `u` and `v`, returning whether they were disjoint.
+    pub(crate) fn union(&mut self, u: usize, v: usize) -> bool {
+        let (ru, rv) = (self.find(u), self.find(v));
+        if ru != rv {
+            self.parent[ru.max(rv)] = ru.min(rv);
+        }
+        ru != rv
+    }
+}
+
+/// Canonicalizes the graph of `k` nodes with the given arcs (which are
+/// mirrored if the graph is undirected) with the backend `C`.
+pub(crate) fn arcs_graph<C: Canonicalizer>(
+    k: usize,
+    arcs: &[(usize, usize)],
+    is_directed: bool,
+) -> NautyGraph<C> {
+    let mut ngraph = NautyGraph::with_backend(k, is_directed, false, None);
+    for &(u, v) in arcs {
+        ngraph.add_arc(u, v);
+        if !is_directed {
+            ngraph.add_arc(v, u);
+        }
+    }
+    ngraph.run();
+    ngraph
+}
+
+/// The canonical label of the graph of `k` nodes with the given arcs.
+#[cfg(test)]
+pub(crate) fn canon(k: usize, arcs: &[(usize, usize)], is_directed: bool) -> Label {
+    Label::from_words(arcs_graph::<DefaultCanonicalizer>(k, arcs, is_directed).canon())
+}
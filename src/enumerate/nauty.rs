@@ -0,0 +1,229 @@
+use super::{Canonicalizer, Nodes};
+use nauty_Traces_sys::{
+    densenauty, optionblk, sparsegraph, sparsenauty, statsblk, SparseGraph, Traces as traces,
+    TracesOptions, TracesStats, TRUE,
+};
+use std::os::raw::c_int;
+
+/// The `densenauty` routine of nauty, which is fastest for small graphs.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DenseNauty;
+
+impl Canonicalizer for DenseNauty {
+    const NAME: &'static str = "dense-nauty";
+
+    fn canonicalize(
+        &mut self,
+        graph: &mut [u64],
+        m: usize,
+        is_directed: bool,
+        partition: bool,
+        nodes: &mut Nodes,
+        canon: &mut [u64],
+    ) -> f64 {
+        let mut opts = optionblk {
+            getcanon: TRUE as c_int,
+            digraph: is_directed as c_int,
+            defaultptn: !partition as c_int,
+            ..Default::default()
+        };
+        let mut stats = statsblk::default();
+        unsafe {
+            densenauty(
+                graph.as_mut_ptr(),
+                nodes.lab.as_mut_ptr(),
+                nodes.ptn.as_mut_ptr(),
+                nodes.orbits.as_mut_ptr(),
+                &mut opts,
+                &mut stats,
+                m as c_int,
+                nodes.lab.len() as c_int,
+                canon.as_mut_ptr(),
+            );
+        }
+        stats.grpsize1 * 10f64.powi(stats.grpsize2)
+    }
+}
+
+/// The `sparsenauty` routine of nauty, which scales better to larger sparse
+/// subgraphs.
+#[derive(Debug, Default, Clone)]
+pub struct SparseNauty {
+    sparse: Sparse,
+}
+
+impl Canonicalizer for SparseNauty {
+    const NAME: &'static str = "sparse-nauty";
+
+    fn canonicalize(
+        &mut self,
+        graph: &mut [u64],
+        m: usize,
+        is_directed: bool,
+        partition: bool,
+        nodes: &mut Nodes,
+        canon: &mut [u64],
+    ) -> f64 {
+        let mut opts = optionblk {
+            getcanon: TRUE as c_int,
+            digraph: is_directed as c_int,
+            defaultptn: !partition as c_int,
+            ..optionblk::default_sparse()
+        };
+        let mut stats = statsblk::default();
+        let (mut sg, mut canon_sg) = self.sparse.load(graph, m, nodes.lab.len(), true);
+        unsafe {
+            sparsenauty(
+                &mut sg,
+                nodes.lab.as_mut_ptr(),
+                nodes.ptn.as_mut_ptr(),
+                nodes.orbits.as_mut_ptr(),
+                &mut opts,
+                &mut stats,
+                &mut canon_sg,
+            );
+        }
+        self.sparse.store(canon, m);
+        stats.grpsize1 * 10f64.powi(stats.grpsize2)
+    }
+}
+
+/// The Traces canonicalizer of the nauty package, which is fastest for
+/// large and highly symmetric graphs.
+///
+/// Traces only supports undirected graphs without loops, so looped vertices
+/// are split from the other vertices of their cells in the initial partition
+/// and their loops are restored in the canonical graph.
+#[derive(Debug, Default, Clone)]
+pub struct Traces {
+    sparse: Sparse,
+    loops: Vec<bool>,
+}
+
+impl Canonicalizer for Traces {
+    const NAME: &'static str = "traces";
+    const SUPPORTS_DIRECTED: bool = false;
+
+    fn canonicalize(
+        &mut self,
+        graph: &mut [u64],
+        m: usize,
+        is_directed: bool,
+        partition: bool,
+        nodes: &mut Nodes,
+        canon: &mut [u64],
+    ) -> f64 {
+        debug_assert!(!is_directed);
+        let n = nodes.lab.len();
+        self.loops.clear();
+        self.loops
+            .extend((0..n).map(|v| graph[v * m + v / 64] >> (63 - v % 64) & 1 == 1));
+        let has_loops = self.loops.contains(&true);
+        if has_loops {
+            if !partition {
+                nodes
+                    .lab
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(i, v)| *v = i as c_int);
+                nodes.ptn.iter_mut().for_each(|ptn| *ptn = 1);
+                nodes.ptn[n - 1] = 0;
+            }
+            self.split_looped(nodes);
+        }
+
+        let mut opts = TracesOptions {
+            getcanon: TRUE as c_int,
+            defaultptn: !(partition || has_loops) as c_int,
+            ..Default::default()
+        };
+        let mut stats = TracesStats::default();
+        let (mut sg, mut canon_sg) = self.sparse.load(graph, m, n, false);
+        unsafe {
+            traces(
+                &mut sg,
+                nodes.lab.as_mut_ptr(),
+                nodes.ptn.as_mut_ptr(),
+                nodes.orbits.as_mut_ptr(),
+                &mut opts,
+                &mut stats,
+                &mut canon_sg,
+            );
+        }
+        self.sparse.store(canon, m);
+        for (i, &v) in nodes.lab.iter().enumerate() {
+            if self.loops[v as usize] {
+                canon[i * m + i / 64] |= 1 << (63 - i % 64);
+            }
+        }
+        stats.grpsize1 * 10f64.powi(stats.grpsize2)
+    }
+}
+
+impl Traces {
+    /// Split every cell of the partition into its vertices without and with
+    /// a loop.
+    fn split_looped(&self, nodes: &mut Nodes) {
+        let mut start = 0;
+        while start < nodes.lab.len() {
+            let end = start + nodes.ptn[start..].iter().position(|&ptn| ptn == 0).unwrap();
+            let cell = &mut nodes.lab[start..=end];
+            cell.sort_by_key(|&v| self.loops[v as usize]);
+            if let Some(split) = cell.iter().position(|&v| self.loops[v as usize]) {
+                if split > 0 {
+                    nodes.ptn[start + split - 1] = 0;
+                }
+            }
+            start = end + 1;
+        }
+    }
+}
+
+/// The buffers of a dense graph converted to the sparse format of nauty
+/// and of its canonical form.
+#[derive(Debug, Default, Clone)]
+struct Sparse {
+    graph: SparseGraph,
+    canon: SparseGraph,
+}
+impl Sparse {
+    /// Convert the dense graph (optionally without its loops), and size the
+    /// canonical graph such that nauty never reallocates its buffers.
+    fn load(
+        &mut self,
+        graph: &[u64],
+        m: usize,
+        n: usize,
+        keep_loops: bool,
+    ) -> (sparsegraph, sparsegraph) {
+        let sparse = &mut self.graph;
+        sparse.v.clear();
+        sparse.d.clear();
+        sparse.e.clear();
+        for u in 0..n {
+            sparse.v.push(sparse.e.len());
+            let row = &graph[u * m..(u + 1) * m];
+            let neighbors = (0..n)
+                .filter(|&v| keep_loops || v != u)
+                .filter(|&v| row[v / 64] >> (63 - v % 64) & 1 == 1);
+            sparse.e.extend(neighbors.map(|v| v as c_int));
+            sparse.d.push((sparse.e.len() - sparse.v[u]) as c_int);
+        }
+        self.canon.v.resize(n, 0);
+        self.canon.d.resize(n, 0);
+        self.canon.e.resize(self.graph.e.len(), 0);
+        ((&mut self.graph).into(), (&mut self.canon).into())
+    }
+
+    /// Write the canonical graph in the dense format.
+    fn store(&self, canon: &mut [u64], m: usize) {
+        canon.iter_mut().for_each(|word| *word = 0);
+        let sparse = &self.canon;
+        for (u, (&start, &degree)) in sparse.v.iter().zip(sparse.d.iter()).enumerate() {
+            for &v in &sparse.e[start..start + degree as usize] {
+                let v = v as usize;
+                canon[u * m + v / 64] |= 1 << (63 - v % 64);
+            }
+        }
+    }
+}
@@ -1,16 +1,52 @@
+use super::{Canonicalizer, Color, DefaultCanonicalizer, EdgeColor};
 use bitvec::prelude::*;
-use nauty_Traces_sys::{densenauty, empty_graph, optionblk, statsblk, ADDONEARC, SETWORDSNEEDED};
 use std::os::raw::c_int;
 
+/// The number of bits used for each edge code in the edge type rows of a label.
+pub(crate) const EDGE_CODE_BITS: usize = 7;
+
+/// The marker bit distinguishing the edge type rows of a label from node colors.
+pub(crate) const TYPE_ROW_TAG: u64 = 1 << 63;
+
+/// The maximum number of nodes of an edge-typed subgraph.
+pub(crate) const MAX_TYPED_NODES: usize = 63 / EDGE_CODE_BITS;
+
+/// The number of bits of a word of the graph.
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// The maximum edge type (the edge codes are offset by one so that zero means no edge).
+pub(crate) const MAX_EDGE_COLOR: EdgeColor = (1 << EDGE_CODE_BITS) - 2;
+
+/// The number of layers needed to encode the edge types up to `max_edge_color`.
+pub(crate) fn edge_layers(max_edge_color: EdgeColor) -> usize {
+    (EdgeColor::BITS - (max_edge_color + 1).leading_zeros()) as usize
+}
+
 /// A dense graph representation for use with nauty.
+///
+/// Edge-typed graphs use the layered graph construction of the nauty user guide:
+/// every node has one copy per layer, the copies of a node are connected by vertical
+/// edges, and an edge of type `t` is present in layer `l` if bit `l` of `t + 1` is set.
+///
+/// The canonical labeling is computed by the [`Canonicalizer`] backend `C`.
 #[derive(Debug)]
-pub struct NautyGraph {
+pub struct NautyGraph<C: Canonicalizer = DefaultCanonicalizer> {
     /// The binary representation of the graph.
+    ///
+    /// For colored graphs this is followed by the color of every node.
     pub graph: Vec<u64>,
 
     /// The binary representation of the canonical labeling.
+    ///
+    /// For colored graphs this is followed by the color of every canonical position.
     pub canon: Vec<u64>,
 
+    /// The canonical label of an edge-typed graph.
+    ///
+    /// This is the canonical adjacency of the nodes, followed by one row of edge codes
+    /// per node (tagged with [`TYPE_ROW_TAG`]), followed by the node colors if the graph is colored.
+    pub label: Vec<u64>,
+
     /// The number of nodes in the graph.
     pub n: usize,
 
@@ -20,36 +56,45 @@ pub struct NautyGraph {
     /// The number of words in the graph representation.
     pub m: usize,
 
+    /// The number of layers of the graph (one unless the edges are typed).
+    pub layers: usize,
+
     /// The nodes labeling / coloring of the graph.
     pub nodes: Nodes,
 
-    /// The options for the nauty run.
-    pub opts: optionblk,
+    /// Whether the graph is directed.
+    pub directed: bool,
 
-    /// The statistics of the nauty run.
-    pub stats: statsblk,
+    /// The size of the automorphism group found by the last run.
+    pub group_size: f64,
+
+    /// The backend computing the canonical labeling.
+    backend: C,
+
+    /// Whether the nodes of the graph are colored.
+    pub colored: bool,
+
+    /// Whether the edges of the graph are typed.
+    pub typed: bool,
 }
 impl NautyGraph {
     pub fn new(n: usize, dir: bool) -> Self {
-        let m = SETWORDSNEEDED(n);
-        let graph = empty_graph(m, n);
-        let canon = empty_graph(m, n);
-        let opts = if dir {
-            opts_default_dir()
-        } else {
-            opts_default_undir()
-        };
-        let stats = statsblk::default();
-        Self {
-            graph,
-            canon,
-            n,
-            m,
-            opts,
-            stats,
-            e: 0,
-            nodes: Nodes::new(n),
-        }
+        Self::with_backend(n, dir, false, None)
+    }
+
+    /// Create a graph whose node colors are respected by the canonical labeling.
+    ///
+    /// The colors of the nodes are set with [`NautyGraph::set_colors`] before every run.
+    pub fn new_colored(n: usize, dir: bool) -> Self {
+        Self::with_backend(n, dir, true, None)
+    }
+
+    /// Create a graph whose edge types (up to `max_edge_color`) are respected by
+    /// the canonical labeling.
+    ///
+    /// Edges are added with [`NautyGraph::add_typed_arc`].
+    pub fn new_typed(n: usize, dir: bool, colored: bool, max_edge_color: EdgeColor) -> Self {
+        Self::with_backend(n, dir, colored, Some(max_edge_color))
     }
 
     #[allow(dead_code)]
@@ -61,25 +106,179 @@ impl NautyGraph {
     pub fn new_undirected(n: usize) -> Self {
         Self::new(n, false)
     }
+}
+
+impl<C: Canonicalizer> NautyGraph<C> {
+    /// Create a graph that is canonicalized by the backend `C`, optionally
+    /// with node colors and edge types (up to `max_edge_color`).
+    pub fn with_backend(
+        n: usize,
+        dir: bool,
+        colored: bool,
+        max_edge_color: Option<EdgeColor>,
+    ) -> Self {
+        let layers = max_edge_color.map_or(1, edge_layers);
+        let typed = max_edge_color.is_some();
+        let num_vertices = n * layers;
+        let m = num_vertices.div_ceil(WORD_BITS);
+        let mut words = m * num_vertices;
+        if colored {
+            words += n;
+        }
+        let mut ngraph = Self {
+            graph: vec![0; words],
+            canon: vec![0; words],
+            label: Vec::new(),
+            n,
+            m,
+            layers,
+            directed: dir,
+            group_size: 1.0,
+            backend: C::default(),
+            e: 0,
+            nodes: Nodes::new(num_vertices),
+            colored,
+            typed,
+        };
+        ngraph.clear_graph();
+        ngraph
+    }
+
+    /// The number of vertices of the graph passed to nauty.
+    fn num_vertices(&self) -> usize {
+        self.n * self.layers
+    }
+
+    /// Set the colors of the nodes of a colored graph, given the color of each position.
+    ///
+    /// The colors seed the initial partition of nauty with one cell per color
+    /// (in increasing order of color), so that only color-preserving
+    /// automorphisms are considered and the canonical positions are sorted by color.
+    pub fn set_colors<F: Fn(usize) -> Color>(&mut self, color: F) {
+        debug_assert!(self.colored);
+        let offset = self.m * self.num_vertices();
+        (0..self.n).for_each(|i| self.graph[offset + i] = color(i) as u64);
+    }
+
+    /// Set the initial partition of nauty, with one cell per layer and node color.
+    fn set_partition(&mut self) {
+        let offset = self.m * self.num_vertices();
+        let n = self.n;
+        let graph = &self.graph;
+        let colored = self.colored;
+        let cell = |x: c_int| {
+            let x = x as usize;
+            let color = if colored { graph[offset + x % n] } else { 0 };
+            (x / n, color)
+        };
+        let lab = &mut self.nodes.lab;
+        lab.iter_mut()
+            .enumerate()
+            .for_each(|(i, x)| *x = i as c_int);
+        lab.sort_by_key(|&x| (cell(x), x));
+        for i in 0..lab.len() {
+            let end_of_cell = i + 1 == lab.len() || cell(lab[i]) != cell(lab[i + 1]);
+            self.nodes.ptn[i] = if end_of_cell { 0 } else { 1 };
+        }
+    }
+
+    /// Add the arc from `u` to `v` (the `v`-th bit of the row of `u`).
+    fn set_arc(&mut self, u: usize, v: usize) {
+        self.graph[u * self.m + v / WORD_BITS] |= 1 << (WORD_BITS - 1 - v % WORD_BITS);
+    }
 
     pub fn add_arc(&mut self, u: usize, v: usize) {
-        ADDONEARC(&mut self.graph, u, v, self.m);
+        self.set_arc(u, v);
+        self.e += 1;
+    }
+
+    /// Add an arc of the given type (color) to an edge-typed graph.
+    pub fn add_typed_arc(&mut self, u: usize, v: usize, color: EdgeColor) {
+        let code = color as usize + 1;
+        for layer in 0..self.layers {
+            if code >> layer & 1 == 1 {
+                let offset = layer * self.n;
+                self.set_arc(offset + u, offset + v);
+            }
+        }
         self.e += 1;
     }
 
+    /// Whether the arc between two vertices of the nauty graph is present.
+    fn has_arc(&self, u: usize, v: usize) -> bool {
+        self.graph[u * self.m + v / WORD_BITS] >> (WORD_BITS - 1 - v % WORD_BITS) & 1 == 1
+    }
+
+    /// The edge code (i.e. the type plus one, or zero if absent) of an arc.
+    fn edge_code(&self, u: usize, v: usize) -> u64 {
+        (0..self.layers)
+            .filter(|layer| self.has_arc(layer * self.n + u, layer * self.n + v))
+            .map(|layer| 1 << layer)
+            .sum()
+    }
+
+    /// Compute the canonical labeling, orbits, and automorphism group size
+    /// of the graph.
     pub fn run(&mut self) {
-        unsafe {
-            densenauty(
-                self.graph.as_mut_ptr(),
-                self.nodes.lab.as_mut_ptr(),
-                self.nodes.ptn.as_mut_ptr(),
-                self.nodes.orbits.as_mut_ptr(),
-                &mut self.opts,
-                &mut self.stats,
-                self.m as c_int,
-                self.n as c_int,
-                self.canon.as_mut_ptr(),
-            );
+        let partition = self.colored || self.typed;
+        if partition {
+            self.set_partition();
+        }
+        let words = self.m * self.num_vertices();
+        self.group_size = self.backend.canonicalize(
+            &mut self.graph[..words],
+            self.m,
+            self.directed,
+            partition,
+            &mut self.nodes,
+            &mut self.canon[..words],
+        );
+
+        if self.typed {
+            self.build_typed_label();
+
+        // Record the colors of the canonical positions
+        } else if self.colored {
+            let offset = self.m * self.n;
+            for i in 0..self.n {
+                self.canon[offset + i] = self.graph[offset + self.nodes.lab[i] as usize];
+            }
+        }
+    }
+
+    /// Build the canonical label of an edge-typed graph.
+    ///
+    /// The first layer comes first in the partition, so the canonical labeling
+    /// orders the nodes by their first layer copies, and the edge types of the
+    /// nodes in that order are a canonical form of the typed graph.
+    fn build_typed_label(&mut self) {
+        let order = self.nodes.lab[..self.n]
+            .iter()
+            .map(|&x| x as usize)
+            .collect::<Vec<_>>();
+        self.label.clear();
+        for &u in order.iter() {
+            let row = order
+                .iter()
+                .enumerate()
+                .filter(|&(_, &v)| self.edge_code(u, v) != 0)
+                .map(|(j, _)| 1 << (63 - j))
+                .sum();
+            self.label.push(row);
+        }
+        for &u in order.iter() {
+            let row = order
+                .iter()
+                .enumerate()
+                .map(|(j, &v)| self.edge_code(u, v) << (EDGE_CODE_BITS * j))
+                .sum::<u64>();
+            self.label.push(row | TYPE_ROW_TAG);
+        }
+        if self.colored {
+            let offset = self.m * self.num_vertices();
+            for &u in order.iter() {
+                self.label.push(self.graph[offset + u]);
+            }
         }
     }
 
@@ -87,12 +286,28 @@ impl NautyGraph {
         self.canon[..].iter_mut().for_each(|x| *x = 0);
     }
 
+    /// Clears all edges (and node colors) of the graph.
+    ///
+    /// The vertical edges between the layers of an edge-typed graph are kept.
     pub fn clear_graph(&mut self) {
         self.graph[..].iter_mut().for_each(|x| *x = 0);
+        for layer in 1..self.layers {
+            for i in 0..self.n {
+                let u = (layer - 1) * self.n + i;
+                let v = layer * self.n + i;
+                self.set_arc(u, v);
+                self.set_arc(v, u);
+            }
+        }
     }
 
+    /// The canonical label of the graph.
     pub fn canon(&self) -> &[u64] {
-        &self.canon
+        if self.typed {
+            &self.label
+        } else {
+            &self.canon
+        }
     }
 
     pub fn graph(&self) -> &[u64] {
@@ -136,18 +351,3 @@ impl Nodes {
         }
     }
 }
-
-fn opts_default_undir() -> optionblk {
-    optionblk {
-        getcanon: 1,
-        ..Default::default()
-    }
-}
-
-fn opts_default_dir() -> optionblk {
-    optionblk {
-        getcanon: 1,
-        digraph: 1,
-        ..Default::default()
-    }
-}
@@ -1,106 +1,607 @@
-use super::{Counts, Label};
-use crate::enumerate::{BitGraph, EnumResult, NautyGraph};
+use super::{
+    merge_groups, nauty_graph, positions, result::GroupResult, sampling::SampleState,
+    update_groups, validate_canonicalizer, validate_colors, validate_edge_colors,
+    validate_edge_type, validate_motif_size, validate_size_range, Canonicalizer, Checkpoint,
+    Checkpointer, Color, Counts, Groups, Instance, Label, LookupTable, MemoCache, MemoKey,
+    Positions, Progress, ProgressTracker, Query, Shard, SizeResults,
+};
+use crate::enumerate::{
+    is_default, BitGraph, DefaultCanonicalizer, EnumResult, NautyGraph, Sampling,
+};
+use anyhow::{bail, Result};
 use petgraph::{EdgeType, Graph};
-use rayon::prelude::*;
-use std::{marker::PhantomData, sync::Arc};
+use rayon::{prelude::*, ThreadPool};
+use std::{
+    marker::PhantomData,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 type Memo = flurry::HashMap<Label, Label>;
+type PositionMemo = flurry::HashMap<Label, Positions>;
+type InstanceCallback = Box<dyn Fn(&Instance) + Send + Sync>;
 
-pub struct ParEsu<Ty: EdgeType + Sync> {
+/// The smallest extension of a root that is split into separate tasks, so
+/// that the subtrees of hubs are shared between threads.
+const SPLIT_EXTENSION: usize = 32;
+
+/// The number of roots enumerated between checkpoints.
+const CHECKPOINT_ROOTS: usize = 256;
+
+pub struct ParEsu<Ty: EdgeType + Sync, C: Canonicalizer = DefaultCanonicalizer> {
     motif_size: usize,
+    min_size: usize,
     graph: BitGraph,
-    counts: Counts,
+    lookup: Vec<Option<&'static LookupTable>>,
+    counts: Vec<Counts>,
     memo: Memo,
-    total: usize,
+    position_memo: PositionMemo,
+    groups: Groups,
+    totals: Vec<usize>,
     is_directed: bool,
-    phantom: PhantomData<Ty>,
+    identify_groups: bool,
+    colors: Option<Vec<Color>>,
+    sampling: Option<Sampling>,
+    sampler: Option<SampleState>,
+    instances: Option<InstanceCallback>,
+    query: Option<Query>,
+    pool: Option<Arc<ThreadPool>>,
+    cancel: Option<Arc<AtomicBool>>,
+    progress: Option<ProgressTracker>,
+    checkpoint: Option<(PathBuf, Duration)>,
+    resume: Option<Checkpoint>,
+    shard: Option<Shard>,
+    busy: Vec<AtomicU64>,
+    elapsed: Duration,
+    /// A function pointer so that the enumerator can be sent to any thread pool.
+    phantom: PhantomData<fn() -> (Ty, C)>,
 }
 impl<Ty: EdgeType + Sync> ParEsu<Ty> {
-    pub fn new(motif_size: usize, petgraph: &Graph<(), (), Ty>) -> Self {
-        let is_directed = petgraph.is_directed();
-        let graph = BitGraph::from_graph(petgraph);
-        let counts = Counts::default();
+    pub fn new(motif_size: usize, petgraph: &Graph<(), (), Ty>) -> Result<Self> {
+        Self::from_bitgraph(motif_size, BitGraph::from_graph(petgraph))
+    }
+
+    /// Create the enumerator directly from a [`BitGraph`].
+    pub fn from_bitgraph(motif_size: usize, graph: BitGraph) -> Result<Self> {
+        Self::with_canonicalizer(motif_size, graph)
+    }
+}
+
+impl<Ty: EdgeType + Sync, C: Canonicalizer> ParEsu<Ty, C> {
+    /// Create the enumerator of a [`BitGraph`] whose subgraphs are
+    /// canonicalized by the backend `C`.
+    pub fn with_canonicalizer(motif_size: usize, graph: BitGraph) -> Result<Self> {
+        validate_motif_size(motif_size)?;
+        validate_edge_type::<Ty>(&graph)?;
+        validate_canonicalizer::<C>(&graph)?;
+        validate_edge_colors(motif_size, &graph)?;
+        let is_directed = graph.is_directed;
+        let min_size = motif_size;
+        let lookup = LookupTable::for_sizes::<C>(motif_size, &graph, false);
+        let counts = vec![Counts::default(); motif_size];
         let memo = Memo::default();
-        let total = 0;
+        let position_memo = PositionMemo::default();
+        let groups = Groups::default();
+        let totals = vec![0; motif_size];
+        let identify_groups = false;
+        let colors = None;
+        let sampling = None;
+        let sampler = None;
+        let instances = None;
+        let query = None;
+        let pool = None;
+        let cancel = None;
+        let progress = None;
+        let checkpoint = None;
+        let resume = None;
+        let shard = None;
+        let busy = Vec::new();
+        let elapsed = Duration::ZERO;
         let phantom = PhantomData;
-        Self {
+        Ok(Self {
             motif_size,
+            min_size,
             graph,
+            lookup,
             counts,
             memo,
-            total,
+            position_memo,
+            groups,
+            totals,
             is_directed,
+            identify_groups,
+            colors,
+            sampling,
+            sampler,
+            instances,
+            query,
+            pool,
+            cancel,
+            progress,
+            checkpoint,
+            resume,
+            shard,
+            busy,
+            elapsed,
             phantom,
+        })
+    }
+
+    /// Color the nodes of the graph so that subgraphs are only considered
+    /// isomorphic if the isomorphism also preserves the node colors.
+    pub fn set_colors(&mut self, colors: Vec<Color>) -> Result<()> {
+        validate_colors(&colors, &self.graph)?;
+        if self.query.is_some() {
+            bail!("ERROR: Queries are not supported for colored graphs.");
+        }
+        if !self.memo.is_empty() {
+            bail!("ERROR: Node colors must be set before loading a memo cache.");
+        }
+        if self.resume.is_some() {
+            bail!("ERROR: Node colors must be set before resuming from a checkpoint.");
+        }
+        self.lookup = LookupTable::for_sizes::<C>(self.motif_size, &self.graph, true);
+        self.colors = Some(colors);
+        Ok(())
+    }
+
+    /// Also count all subgraphs from `min_size` up to the motif size
+    /// within the same traversal of the ESU tree.
+    pub fn set_min_size(&mut self, min_size: usize) -> Result<()> {
+        validate_size_range(min_size, self.motif_size)?;
+        if self.sampling.is_some() && min_size != self.motif_size {
+            bail!("ERROR: Sampling is not supported when enumerating a range of subgraph sizes.");
+        }
+        self.min_size = min_size;
+        Ok(())
+    }
+
+    /// Use RAND-ESU to sample the ESU tree instead of exploring it fully.
+    pub fn set_sampling(&mut self, sampling: Sampling) -> Result<()> {
+        sampling.validate(self.motif_size)?;
+        if self.checkpoint.is_some() || self.resume.is_some() {
+            bail!("ERROR: Checkpoints are not supported when sampling.");
+        }
+        if self.min_size != self.motif_size {
+            bail!("ERROR: Sampling is not supported when enumerating a range of subgraph sizes.");
+        }
+        self.sampling = Some(sampling);
+        Ok(())
+    }
+
+    /// Only count the target motifs of the query, pruning the branches of
+    /// the ESU tree that cannot grow into one of them.
+    ///
+    /// The sizes from the smallest to the largest target are enumerated, so
+    /// the largest target must have the motif size.
+    pub fn set_query(&mut self, mut query: Query) -> Result<()> {
+        if query.max_size() != self.motif_size {
+            bail!(
+                "ERROR: The largest target motif has {} nodes but the subgraph size is {}.",
+                query.max_size(),
+                self.motif_size
+            );
+        }
+        if self.colors.is_some() {
+            bail!("ERROR: Queries are not supported for colored graphs.");
+        }
+        if !is_default::<C>() {
+            bail!("ERROR: Queries are only supported with the default canonicalizer.");
+        }
+        query.bind(&self.graph)?;
+        self.set_min_size(query.min_size())?;
+        self.query = Some(query);
+        Ok(())
+    }
+
+    /// Whether the node can be part of a target motif of the query.
+    fn admits(&self, v: usize) -> bool {
+        self.query.as_ref().is_none_or(|query| query.admits(v))
+    }
+
+    /// Only explore the ESU trees of the roots in the shard, so that the
+    /// enumeration can be split over independent processes whose counts
+    /// sum to the full counts.
+    pub fn set_shard(&mut self, shard: Shard) {
+        self.shard = Some(shard);
+    }
+
+    /// Whether the root belongs to the shard of the enumeration.
+    fn in_shard(&self, root: usize) -> bool {
+        self.shard.is_none_or(|shard| shard.contains(root))
+    }
+
+    /// Pass every subgraph occurrence to the callback as soon as it is
+    /// enumerated. The callback is shared by all threads, so the order of
+    /// the occurrences is not deterministic.
+    pub fn set_instances<F: Fn(&Instance) + Send + Sync + 'static>(&mut self, callback: F) {
+        self.instances = Some(Box::new(callback));
+    }
+
+    /// Run the enumeration on the given thread pool instead of the current
+    /// (by default the global) rayon thread pool.
+    pub fn set_thread_pool(&mut self, pool: Arc<ThreadPool>) {
+        self.pool = Some(pool);
+    }
+
+    /// Stop the enumeration as soon as the flag is set, e.g. from another
+    /// thread. The results of a cancelled enumeration are incomplete.
+    pub fn set_cancel(&mut self, cancel: Arc<AtomicBool>) {
+        self.cancel = Some(cancel);
+    }
+
+    /// Whether the enumeration has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// Pass the progress of the enumeration (in finished root vertices) to
+    /// the callback (from any of the threads) at most once per `interval`, and once when it is finished.
+    pub fn set_progress<F: Fn(&Progress) + Send + Sync + 'static>(
+        &mut self,
+        interval: Duration,
+        callback: F,
+    ) {
+        self.progress = Some(ProgressTracker::new(interval, callback));
+    }
+
+    /// Periodically write the counts of the finished roots to a checkpoint
+    /// file (and once the enumeration is finished), from which an
+    /// interrupted enumeration can be resumed.
+    pub fn set_checkpoint<P: Into<PathBuf>>(&mut self, path: P, interval: Duration) -> Result<()> {
+        if self.sampling.is_some() {
+            bail!("ERROR: Checkpoints are not supported when sampling.");
+        }
+        self.checkpoint = Some((path.into(), interval));
+        Ok(())
+    }
+
+    /// Resume an interrupted enumeration from a checkpoint, skipping its
+    /// finished roots and starting from its counts.
+    ///
+    /// The node colors, the minimum size, and the shard must be set before
+    /// resuming.
+    pub fn resume(&mut self, checkpoint: Checkpoint) -> Result<()> {
+        if self.sampling.is_some() {
+            bail!("ERROR: Checkpoints are not supported when sampling.");
+        }
+        checkpoint.validate(
+            &self.graph,
+            self.min_size,
+            &self.memo_key(),
+            self.shard,
+            self.colors.as_deref(),
+        )?;
+        self.resume = Some(checkpoint);
+        Ok(())
+    }
+
+    /// Start from the canonical labels memoized by an earlier enumeration
+    /// with the same subgraph size, direction, and loops, so that nauty only
+    /// runs for subgraphs that were not seen before.
+    ///
+    /// Node colors must be set before loading a memo cache.
+    pub fn set_memo_cache(&mut self, cache: MemoCache) -> Result<()> {
+        let labels = cache.into_labels(self.memo_key())?;
+        let memo = self.memo.pin();
+        for (original, label) in labels {
+            memo.insert(original, label);
         }
+        Ok(())
+    }
+
+    /// The canonical labels memoized so far, to be reused by later enumerations.
+    pub fn memo_cache(&self) -> MemoCache {
+        let labels = self
+            .memo
+            .pin()
+            .iter()
+            .map(|(original, label)| (original.clone(), label.clone()))
+            .collect();
+        MemoCache::new(self.memo_key(), labels)
+    }
+
+    fn memo_key(&self) -> MemoKey {
+        MemoKey::new(self.motif_size, &self.graph, self.colors.is_some(), C::NAME)
     }
 
+    /// Whether the canonical positions of the subgraphs are needed.
+    fn track_positions(&self) -> bool {
+        self.identify_groups || self.instances.is_some()
+    }
+
+    /// Enumerate the subgraphs on the thread pool of the enumerator (or the
+    /// current rayon thread pool if none was set).
+    ///
+    /// The large extensions of roots are split into a task per child, so
+    /// that the subtrees of hubs do not leave the other threads idle.
     pub fn enumerate(&mut self) {
-        let ext = vec![0; self.graph.n];
-        let (counts, total) = (0..self.graph.n)
-            .par_bridge()
-            .map(|i| {
-                let mut ngraph = NautyGraph::new(self.motif_size, self.is_directed);
-                let mut counts = Counts::default();
-                let mut current = vec![0; self.motif_size];
-                let mut total = 0;
-                self.go(
-                    i,
-                    0,
-                    0,
-                    &ext,
-                    &mut ngraph,
-                    &mut counts,
-                    &mut current,
-                    &mut total,
-                );
-                (counts, total)
-            })
-            .reduce(
-                || (Counts::default(), 0),
-                |(mut counts1, total1), (counts2, total2)| {
-                    counts2.into_iter().for_each(|(k, v)| {
-                        *counts1.entry(k).or_insert(0) += v;
-                    });
-                    (counts1, total1 + total2)
-                },
+        match self.pool.clone() {
+            Some(pool) => pool.install(|| self.enumerate_on_pool()),
+            None => self.enumerate_on_pool(),
+        }
+    }
+
+    /// Enumerate the subgraphs on the current rayon thread pool.
+    fn enumerate_on_pool(&mut self) {
+        let now = Instant::now();
+        self.busy = (0..rayon::current_num_threads())
+            .map(|_| AtomicU64::new(0))
+            .collect();
+
+        // Start from the counts of the finished roots of a checkpoint
+        let mut done = vec![false; self.graph.n];
+        let mut state = ThreadState::new(self);
+        if let Some(checkpoint) = self.resume.take() {
+            let (finished, counts, totals) = checkpoint.into_parts();
+            done = finished;
+            state.counts = counts;
+            state.totals = totals;
+        }
+        let mut checkpointer = self.checkpoint.clone().map(|(path, interval)| {
+            let checkpoint = Checkpoint::new(
+                &self.graph,
+                self.min_size,
+                self.memo_key(),
+                self.shard,
+                self.colors.as_deref(),
             );
-        self.counts = counts;
-        self.total = total;
+            Checkpointer::new(path, interval, checkpoint)
+        });
+        let roots = (0..self.graph.n)
+            .filter(|&i| !done[i] && self.in_shard(i))
+            .collect::<Vec<_>>();
+        if let Some(progress) = self.progress.as_mut() {
+            progress.start(roots.len());
+        }
+
+        // The roots are enumerated in batches between checkpoints
+        let batch_size = if checkpointer.is_some() {
+            CHECKPOINT_ROOTS
+        } else {
+            roots.len().max(1)
+        };
+        let ext = vec![0; self.graph.n];
+        for batch in roots.chunks(batch_size) {
+            let batch_state = batch
+                .par_iter()
+                .fold(
+                    || ThreadState::new(self),
+                    |mut state, &i| {
+                        let before = state.totals.iter().sum::<usize>();
+                        self.root(i, &ext, &mut state);
+                        if let Some(progress) = &self.progress {
+                            progress.root_done(state.totals.iter().sum::<usize>() - before);
+                        }
+                        state
+                    },
+                )
+                .reduce(|| ThreadState::new(self), ThreadState::merge);
+
+            // The roots of a cancelled batch may be incomplete, so the
+            // checkpoint only covers the batches before it
+            if self.is_cancelled() {
+                if let Some(checkpointer) = checkpointer.as_mut() {
+                    checkpointer.save(&done, &state.counts, &state.totals);
+                }
+                checkpointer = None;
+                state.absorb(batch_state);
+                break;
+            }
+            state.absorb(batch_state);
+            batch.iter().for_each(|&i| done[i] = true);
+            if let Some(checkpointer) = checkpointer.as_mut().filter(|c| c.is_due()) {
+                checkpointer.save(&done, &state.counts, &state.totals);
+            }
+        }
+        if let Some(progress) = &self.progress {
+            progress.finish();
+        }
+        if let Some(checkpointer) = checkpointer.as_mut() {
+            checkpointer.save(&done, &state.counts, &state.totals);
+        }
+        self.counts = state.counts;
+        self.groups = state.groups;
+        self.totals = state.totals;
+        self.sampler = state.sampler;
+        self.elapsed = now.elapsed();
+    }
+
+    /// Explore the ESU tree of a single root.
+    fn root(&self, i: usize, ext: &[usize], state: &mut ThreadState<C>) {
+        if !self.admits(i) || self.is_cancelled() {
+            return;
+        }
+        if let Some(sampler) = state.sampler.as_mut() {
+            sampler.start_root(i);
+            if !sampler.keep(0) {
+                return;
+            }
+        }
+        self.task(i, 0, 0, ext, state);
+    }
+
+    /// The fraction of the last enumeration that each thread of the pool
+    /// spent exploring the ESU tree.
+    pub fn thread_utilization(&self) -> Vec<f64> {
+        let elapsed = self.elapsed.as_nanos() as f64;
+        self.busy
+            .iter()
+            .map(|busy| busy.load(Ordering::Relaxed) as f64 / elapsed.max(1.0))
+            .collect()
+    }
+
+    /// Explore the subtree of a node of the ESU tree as a single task on the
+    /// current thread and record the time spent on it.
+    fn task(&self, n: usize, size: usize, next: usize, ext: &[usize], state: &mut ThreadState<C>) {
+        let now = Instant::now();
+        let split = state.split;
+
+        // The nauty graphs of a state are reused by all of its tasks
+        let mut ngraphs = std::mem::take(&mut state.ngraphs);
+        if ngraphs.is_empty() {
+            ngraphs = (1..=self.motif_size)
+                .map(|size| nauty_graph(size, &self.graph, self.colors.is_some()))
+                .collect();
+        }
+        self.go(n, size, next, ext, &mut ngraphs, state);
+        state.ngraphs = ngraphs;
+
+        // The time spent waiting on split subtrees is recorded by their own tasks
+        let busy = now.elapsed().saturating_sub(state.split - split);
+        let thread = rayon::current_thread_index().unwrap_or(0);
+        if let Some(total) = self.busy.get(thread) {
+            total.fetch_add(busy.as_nanos() as u64, Ordering::Relaxed);
+        }
     }
 
-    pub fn build_nauty(&self, current: &[usize], ngraph: &mut NautyGraph) {
+    /// Enumerate all subgraphs and record the subgraph membership
+    /// and orbit position of every node.
+    pub fn identify_groups(&mut self) {
+        self.identify_groups = true;
+        self.enumerate();
+    }
+
+    pub fn build_nauty(&self, current: &[usize], ngraph: &mut NautyGraph<C>) {
         if self.is_directed {
             self.build_nauty_dir(current, ngraph);
         } else {
             self.build_nauty_undir(current, ngraph);
         }
+        if let Some(colors) = &self.colors {
+            ngraph.set_colors(|i| colors[current[i]]);
+        }
     }
 
-    fn build_nauty_dir(&self, current: &[usize], ngraph: &mut NautyGraph) {
+    fn build_nauty_dir(&self, current: &[usize], ngraph: &mut NautyGraph<C>) {
         current.iter().enumerate().for_each(|(i, &u)| {
             current.iter().enumerate().for_each(|(j, &v)| {
                 if self.graph.is_connected_directed(u, v) {
-                    ngraph.add_arc(i, j);
+                    match self.graph.edge_color(u, v) {
+                        Some(color) => ngraph.add_typed_arc(i, j, color),
+                        None => ngraph.add_arc(i, j),
+                    }
                 }
             })
         });
     }
 
-    fn build_nauty_undir(&self, current: &[usize], ngraph: &mut NautyGraph) {
+    fn build_nauty_undir(&self, current: &[usize], ngraph: &mut NautyGraph<C>) {
         current.iter().enumerate().for_each(|(i, &u)| {
             current.iter().enumerate().skip(i + 1).for_each(|(j, &v)| {
                 if self.graph.is_connected(u, v) {
-                    ngraph.add_arc(i, j);
-                    ngraph.add_arc(j, i);
+                    match self.graph.edge_color(u, v) {
+                        Some(color) => {
+                            ngraph.add_typed_arc(i, j, color);
+                            ngraph.add_typed_arc(j, i, color);
+                        }
+                        None => {
+                            ngraph.add_arc(i, j);
+                            ngraph.add_arc(j, i);
+                        }
+                    }
                 }
             })
         });
     }
 
-    pub fn run_nauty(&self, ngraph: &mut NautyGraph) {
+    pub fn run_nauty(&self, ngraph: &mut NautyGraph<C>) {
         ngraph.run();
     }
 
+    /// Canonicalize the first `size` nodes of the current subgraph and
+    /// count it.
+    fn count_subgraph(&self, size: usize, ngraph: &mut NautyGraph<C>, state: &mut ThreadState<C>) {
+        // Small uncolored subgraphs are looked up by their adjacency matrix
+        if let Some(table) = self.lookup[size - 1] {
+            let (label, positions) = table.lookup(&self.graph, &state.current[..size]);
+            self.record_subgraph(size, label, Some(positions), state);
+            return;
+        }
+
+        self.build_nauty(&state.current[..size], ngraph);
+
+        let original = Label::from_words(ngraph.graph());
+
+        // Check if the subgraph is isomorphic to a subgraph that has already been enumerated.
+        let guard = self.memo.guard();
+        let position_guard = self.position_memo.guard();
+        let memoized = self.memo.get(&original, &guard).and_then(|label| {
+            if self.track_positions() {
+                let positions = self.position_memo.get(&original, &position_guard)?;
+                Some((label.clone(), Some(positions.clone())))
+            } else {
+                Some((label.clone(), None))
+            }
+        });
+        let (label, positions) = match memoized {
+            Some(memoized) => memoized,
+
+            // Otherwise run nauty to find the canonical label of the subgraph.
+            None => {
+                self.run_nauty(ngraph);
+                let label = Label::from_words(ngraph.canon());
+
+                // The orbits are only valid for the nauty run of this exact subgraph
+                let positions = self.track_positions().then(|| positions(ngraph));
+                if let Some(positions) = &positions {
+                    self.position_memo
+                        .insert(original.clone(), positions.clone(), &position_guard);
+                }
+                self.memo.insert(original, label.clone(), &guard);
+                (label, positions)
+            }
+        };
+        ngraph.clear_canon();
+        ngraph.clear_graph();
+        self.record_subgraph(size, &label, positions.as_ref(), state);
+    }
+
+    /// Count the first `size` nodes of the current subgraph with its
+    /// canonical label and positions.
+    fn record_subgraph(
+        &self,
+        size: usize,
+        label: &Label,
+        positions: Option<&Positions>,
+        state: &mut ThreadState<C>,
+    ) {
+        // Queries only count the target motifs
+        if self
+            .query
+            .as_ref()
+            .is_some_and(|query| !query.contains(label))
+        {
+            return;
+        }
+
+        state.totals[size - 1] += 1;
+        if let Some(sampler) = state.sampler.as_mut() {
+            sampler.record(label);
+        }
+        if let Some(positions) = positions {
+            let current = &state.current[..size];
+            if self.identify_groups {
+                update_groups(&mut state.groups, current, label, positions);
+            }
+            if let Some(callback) = &self.instances {
+                callback(&Instance::new(current, label, positions));
+            }
+        }
+        let counts = &mut state.counts[size - 1];
+        if let Some(count) = counts.get_mut(label) {
+            *count += 1;
+        } else {
+            counts.insert(label.clone(), 1);
+        }
+    }
+
     /// The main function for the enumeration.
     ///
     /// This function is called recursively to enumerate all subgraphs of the
@@ -111,69 +612,57 @@ impl<Ty: EdgeType + Sync> ParEsu<Ty> {
     /// * `size` - The current size of the subgraph.
     /// * `next` - The next node to be added to the subgraph.
     /// * `ext` - The extension of the subgraph.
-    pub fn go(
+    /// * `ngraphs` - The nauty graphs used for canonical labeling of each size.
+    /// * `state` - The enumeration state of the current worker.
+    fn go(
         &self,
         n: usize,
         size: usize,
         next: usize,
-        ext: &Vec<usize>,
-        ngraph: &mut NautyGraph,
-        counts: &mut Counts,
-        current: &mut Vec<usize>,
-        total: &mut usize,
+        ext: &[usize],
+        ngraphs: &mut [NautyGraph<C>],
+        state: &mut ThreadState<C>,
     ) {
-        current[size] = n;
+        if self.is_cancelled() {
+            return;
+        }
+        state.current[size] = n;
+        let depth = size;
         let size = size + 1;
 
-        if size == self.motif_size {
-            *total += 1;
-            self.build_nauty(current, ngraph);
+        // Every node of the ESU tree is a connected subgraph of its depth,
+        // so all sizes in the range are counted in the same traversal.
+        // Queries only count (and extend) subgraphs that can be a target motif
+        let (count, extend) = match &self.query {
+            Some(query) => query.check(&self.graph, &state.current[..size]),
+            None => (true, true),
+        };
 
-            // Check if the subgraph is isomorphic to a subgraph that has already been enumerated.
-            if let Some(label) = self.memo.get(ngraph.graph(), &self.memo.guard()) {
-                if let Some(count) = counts.get_mut(label) {
-                    *count += 1;
-                } else {
-                    counts.insert(label.clone(), 1);
-                }
-
-            // Otherwise run nauty to find the canonical label of the subgraph.
-            } else {
-                self.run_nauty(ngraph);
-                let original = ngraph.graph().to_vec();
-                let label: Arc<[u64]> = ngraph.canon().to_vec().into();
-                // let label = ngraph.canon().to_vec();
-                self.memo
-                    .insert(original.into(), label.clone(), &self.memo.guard());
-
-                if let Some(count) = counts.get_mut(ngraph.canon()) {
-                    *count += 1;
-                } else {
-                    counts.insert(label, 1);
-                }
-            };
+        if count && size >= self.min_size {
+            self.count_subgraph(size, &mut ngraphs[size - 1], state);
+        }
 
-            ngraph.clear_canon();
-            ngraph.clear_graph();
-        } else {
+        if extend && size < self.motif_size {
             let mut next2 = next;
 
             // Copy the list of nodes in the extension.
-            let mut ext2 = ext.clone();
+            let mut ext2 = ext.to_vec();
 
             // Get the neighbors of the last node in the current subgraph
-            let neighbors = self.graph.neighbors(current[size - 1]).ones();
+            let neighbors = self.graph.neighbors(state.current[size - 1]);
 
             // Iterate over the neighbors of the last node in the current subgraph
             for v in neighbors {
                 // If the neighbor is smaller than the first node in the current subgraph, skip it
-                if v <= current[0] {
+                // (or if it cannot be part of a target motif of a query)
+                if v <= state.current[0] || !self.admits(v) {
                     continue;
                 }
 
                 // Iterate over the nodes in the current subgraph
                 // and if there are any neighbors, break
-                let exclusive = current
+                let exclusive = state
+                    .current
                     .iter()
                     .take(size - 1)
                     .all(|&u| !self.graph.is_connected(v, u));
@@ -185,35 +674,181 @@ impl<Ty: EdgeType + Sync> ParEsu<Ty> {
                 }
             }
 
+            // Split large extensions of the root into separate tasks
+            // (sampling keeps the whole tree of a root on a single thread)
+            if depth == 0 && next2 >= SPLIT_EXTENSION && state.sampler.is_none() {
+                self.split(size, next2, &ext2, state);
+                return;
+            }
+
             // Recursively call the function for each node in the extension
+            // (which are only explored with the depth probability when sampling)
             while next2 > 0 {
                 next2 -= 1;
-                self.go(
-                    ext2[next2],
-                    size,
-                    next2,
-                    &ext2,
-                    ngraph,
-                    counts,
-                    current,
-                    total,
-                );
+                if state.sampler.as_mut().is_none_or(|s| s.keep(size)) {
+                    self.go(ext2[next2], size, next2, &ext2, ngraphs, state);
+                }
+            }
+
+            if let Some(sampler) = state.sampler.as_mut() {
+                sampler.exit(depth);
             }
         }
     }
 
-    pub fn result(self) -> EnumResult {
-        EnumResult::new(self.counts, self.total)
+    /// Explore the first `next` nodes of the extension of the current
+    /// subgraph as separate tasks and merge their states into `state`.
+    fn split(&self, size: usize, next: usize, ext: &[usize], state: &mut ThreadState<C>) {
+        let now = Instant::now();
+        let current = &state.current[..size];
+        let other = (0..next)
+            .into_par_iter()
+            .rev()
+            .fold(
+                || ThreadState::new(self),
+                |mut other, idx| {
+                    other.current[..size].copy_from_slice(current);
+                    self.task(ext[idx], size, idx, ext, &mut other);
+                    other
+                },
+            )
+            .reduce(|| ThreadState::new(self), ThreadState::merge);
+        state.absorb(other);
+        state.split += now.elapsed();
+    }
+
+    /// The enumeration result for the motif size.
+    pub fn result(mut self) -> EnumResult {
+        let counts = std::mem::take(&mut self.counts[self.motif_size - 1]);
+        let total = self.totals[self.motif_size - 1];
+        let estimates = self.sampler.map(|s| s.estimates(&counts, total));
+        let result = EnumResult::new(counts, total);
+        match estimates {
+            Some(estimates) => result.with_estimates(estimates),
+            None => result,
+        }
+    }
+
+    /// The enumeration results for every subgraph size from the minimum
+    /// size up to the motif size.
+    pub fn size_results(self) -> SizeResults {
+        let counts = self.counts;
+        // Sampling is only supported for a single size
+        let mut estimates = self.sampler.map(|s| {
+            let idx = self.motif_size - 1;
+            s.estimates(&counts[idx], self.totals[idx])
+        });
+        counts
+            .into_iter()
+            .zip(self.totals)
+            .enumerate()
+            .skip(self.min_size - 1)
+            .map(|(idx, (counts, total))| {
+                let result = EnumResult::new(counts, total);
+                match estimates.take() {
+                    Some(estimates) => (idx + 1, result.with_estimates(estimates)),
+                    None => (idx + 1, result),
+                }
+            })
+            .collect()
+    }
+
+    pub fn group_results(mut self) -> GroupResult {
+        let counts = std::mem::take(&mut self.counts[self.motif_size - 1]);
+        let total = self.totals[self.motif_size - 1];
+        let estimates = self.sampler.map(|s| s.estimates(&counts, total));
+        let result = GroupResult::new(self.groups, total, counts.len());
+        match estimates {
+            Some(estimates) => result.with_estimates(estimates),
+            None => result,
+        }
     }
 }
 
+/// The enumeration state of a single rayon worker.
+///
+/// Every worker accumulates its own counts and groups over the roots
+/// it processes, which are merged once all roots are finished.
+struct ThreadState<C: Canonicalizer> {
+    current: Vec<usize>,
+    /// The nauty graphs of each size, which are created by the first task.
+    ngraphs: Vec<NautyGraph<C>>,
+    counts: Vec<Counts>,
+    groups: Groups,
+    totals: Vec<usize>,
+    sampler: Option<SampleState>,
+    /// The time spent waiting on split subtrees.
+    split: Duration,
+}
+impl<C: Canonicalizer> ThreadState<C> {
+    fn new<Ty: EdgeType + Sync>(esu: &ParEsu<Ty, C>) -> Self {
+        Self {
+            current: vec![0; esu.motif_size],
+            ngraphs: Vec::new(),
+            counts: vec![Counts::default(); esu.motif_size],
+            groups: Groups::default(),
+            totals: vec![0; esu.motif_size],
+            sampler: esu.sampling.as_ref().map(Sampling::state),
+            split: Duration::ZERO,
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        self.absorb(other);
+        self
+    }
+
+    /// Add the counts, groups, and totals of another state.
+    fn absorb(&mut self, other: Self) {
+        for (counts, other) in self.counts.iter_mut().zip(other.counts) {
+            for (label, count) in other {
+                *counts.entry(label).or_insert(0) += count;
+            }
+        }
+        merge_groups(&mut self.groups, other.groups);
+        for (total, other) in self.totals.iter_mut().zip(other.totals) {
+            *total += other;
+        }
+        if let (Some(sampler), Some(other)) = (self.sampler.as_mut(), other.sampler) {
+            sampler.merge(other);
+        }
+    }
+}
+
+/// Enumerate all subgraphs of a given size in a graph using the
+/// current rayon thread pool.
 pub fn parallel_enumerate_subgraphs<Ty: EdgeType + Sync>(
     graph: &Graph<(), (), Ty>,
     motif_size: usize,
-) -> EnumResult {
-    let mut esu = ParEsu::new(motif_size, graph);
+) -> Result<EnumResult> {
+    let mut esu = ParEsu::new(motif_size, graph)?;
     esu.enumerate();
-    esu.result()
+    Ok(esu.result())
+}
+
+/// Enumerate all subgraphs of every size from `min_size` to `max_size`
+/// in a graph within a single traversal using the current rayon thread pool.
+pub fn parallel_enumerate_subgraph_sizes<Ty: EdgeType + Sync>(
+    graph: &Graph<(), (), Ty>,
+    min_size: usize,
+    max_size: usize,
+) -> Result<SizeResults> {
+    let mut esu = ParEsu::new(max_size, graph)?;
+    esu.set_min_size(min_size)?;
+    esu.enumerate();
+    Ok(esu.size_results())
+}
+
+/// Enumerate all subgraphs of a given size in a graph using the
+/// current rayon thread pool and record the subgraph membership and
+/// orbit position of every node.
+pub fn parallel_group_subgraphs<Ty: EdgeType + Sync>(
+    graph: &Graph<(), (), Ty>,
+    motif_size: usize,
+) -> Result<GroupResult> {
+    let mut esu = ParEsu::new(motif_size, graph)?;
+    esu.identify_groups();
+    Ok(esu.group_results())
 }
 
 #[cfg(test)]
@@ -227,7 +862,7 @@ mod testing {
     fn dir_example_s3() {
         let filepath = "example/example.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = parallel_enumerate_subgraphs(&graph, 3);
+        let result = parallel_enumerate_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 16);
         assert_eq!(result.unique_subgraphs(), 4);
 
@@ -245,7 +880,7 @@ mod testing {
     fn undir_example_s3() {
         let filepath = "example/example.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = parallel_enumerate_subgraphs(&graph, 3);
+        let result = parallel_enumerate_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 16);
         assert_eq!(result.unique_subgraphs(), 2);
 
@@ -261,7 +896,7 @@ mod testing {
     fn dir_example_s4() {
         let filepath = "example/example.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = parallel_enumerate_subgraphs(&graph, 4);
+        let result = parallel_enumerate_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 24);
         assert_eq!(result.unique_subgraphs(), 8);
 
@@ -283,7 +918,7 @@ mod testing {
     fn undir_example_s4() {
         let filepath = "example/example.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = parallel_enumerate_subgraphs(&graph, 4);
+        let result = parallel_enumerate_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 24);
         assert_eq!(result.unique_subgraphs(), 3);
 
@@ -300,7 +935,7 @@ mod testing {
     fn dir_ecoli_s3() {
         let filepath = "example/ecoli.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = parallel_enumerate_subgraphs(&graph, 3);
+        let result = parallel_enumerate_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 674);
         assert_eq!(result.unique_subgraphs(), 4);
 
@@ -318,7 +953,7 @@ mod testing {
     fn undir_ecoli_s3() {
         let filepath = "example/ecoli.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = parallel_enumerate_subgraphs(&graph, 3);
+        let result = parallel_enumerate_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 674);
         assert_eq!(result.unique_subgraphs(), 2);
 
@@ -334,7 +969,7 @@ mod testing {
     fn dir_ecoli_s4() {
         let filepath = "example/ecoli.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = parallel_enumerate_subgraphs(&graph, 4);
+        let result = parallel_enumerate_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 2531);
         assert_eq!(result.unique_subgraphs(), 24);
 
@@ -393,7 +1028,7 @@ mod testing {
     fn undir_ecoli_s4() {
         let filepath = "example/ecoli.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = parallel_enumerate_subgraphs(&graph, 4);
+        let result = parallel_enumerate_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 2531);
         assert_eq!(result.unique_subgraphs(), 6);
 
@@ -418,7 +1053,7 @@ mod testing {
     fn dir_yeast_s3() {
         let filepath = "example/yeast.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = parallel_enumerate_subgraphs(&graph, 3);
+        let result = parallel_enumerate_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 13150);
         assert_eq!(result.unique_subgraphs(), 7);
 
@@ -444,7 +1079,7 @@ mod testing {
     fn undir_yeast_s3() {
         let filepath = "example/yeast.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = parallel_enumerate_subgraphs(&graph, 3);
+        let result = parallel_enumerate_subgraphs(&graph, 3).unwrap();
         assert_eq!(result.total_subgraphs(), 13150);
         assert_eq!(result.unique_subgraphs(), 2);
 
@@ -460,7 +1095,7 @@ mod testing {
     fn dir_yeast_s4() {
         let filepath = "example/yeast.txt";
         let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
-        let result = parallel_enumerate_subgraphs(&graph, 4);
+        let result = parallel_enumerate_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 183174);
         assert_eq!(result.unique_subgraphs(), 34);
 
@@ -528,11 +1163,35 @@ mod testing {
         })
     }
 
+    #[test]
+    fn dir_yeast_s4_sampled_matches_serial() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let sampling = Sampling::new(vec![1.0, 0.5, 0.5, 0.5], 7).unwrap();
+
+        let mut esu = crate::enumerate::Esu::new(4, &graph).unwrap();
+        esu.set_sampling(sampling.clone()).unwrap();
+        esu.enumerate();
+        let serial = esu.result();
+
+        let mut par_esu = ParEsu::new(4, &graph).unwrap();
+        par_esu.set_sampling(sampling).unwrap();
+        par_esu.enumerate();
+        let parallel = par_esu.result();
+
+        assert_eq!(serial.counts(), parallel.counts());
+        assert_eq!(serial.total_subgraphs(), parallel.total_subgraphs());
+        let serial_total = serial.estimates().unwrap().total();
+        let parallel_total = parallel.estimates().unwrap().total();
+        assert_eq!(serial_total.value, parallel_total.value);
+        assert!((serial_total.variance - parallel_total.variance).abs() < 1e-6);
+    }
+
     #[test]
     fn undir_yeast_s4() {
         let filepath = "example/yeast.txt";
         let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
-        let result = parallel_enumerate_subgraphs(&graph, 4);
+        let result = parallel_enumerate_subgraphs(&graph, 4).unwrap();
         assert_eq!(result.total_subgraphs(), 183174);
         assert_eq!(result.unique_subgraphs(), 6);
 
@@ -547,4 +1206,411 @@ mod testing {
             assert!(cond);
         })
     }
+
+    #[test]
+    fn dir_example_s3_groups() {
+        let filepath = "example/example.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let result = parallel_group_subgraphs(&graph, 3).unwrap();
+        assert_eq!(result.total_subgraphs(), 16);
+        assert_eq!(result.unique_subgraphs(), 4);
+        (0..graph.node_count()).for_each(|i| {
+            assert!(result.groups().contains_key(&i));
+        })
+    }
+
+    #[test]
+    fn dir_yeast_s4_groups_matches_serial() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let serial = crate::enumerate::group_subgraphs(&graph, 4).unwrap();
+        let parallel = parallel_group_subgraphs(&graph, 4).unwrap();
+        assert_eq!(serial.total_subgraphs(), parallel.total_subgraphs());
+        assert_eq!(serial.unique_subgraphs(), parallel.unique_subgraphs());
+        assert_eq!(serial.groups(), parallel.groups());
+    }
+
+    #[test]
+    fn undir_yeast_s4_groups_matches_serial() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
+        let serial = crate::enumerate::group_subgraphs(&graph, 4).unwrap();
+        let parallel = parallel_group_subgraphs(&graph, 4).unwrap();
+        assert_eq!(serial.total_subgraphs(), parallel.total_subgraphs());
+        assert_eq!(serial.groups(), parallel.groups());
+    }
+
+    #[test]
+    fn dir_yeast_size_range_matches_serial() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let serial = crate::enumerate::enumerate_subgraph_sizes(&graph, 2, 4).unwrap();
+        let parallel = parallel_enumerate_subgraph_sizes(&graph, 2, 4).unwrap();
+        assert_eq!(serial.len(), 3);
+        assert_eq!(serial.len(), parallel.len());
+        for ((k1, r1), (k2, r2)) in serial.iter().zip(parallel.iter()) {
+            assert_eq!(k1, k2);
+            assert_eq!(r1.total_subgraphs(), r2.total_subgraphs());
+            assert_eq!(r1.counts(), r2.counts());
+        }
+    }
+
+    #[test]
+    fn dir_yeast_s4_colored_matches_serial() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let colors = (0..graph.node_count())
+            .map(|i| (i % 3) as u32)
+            .collect::<Vec<_>>();
+
+        let mut esu = crate::enumerate::Esu::new(4, &graph).unwrap();
+        esu.set_colors(colors.clone()).unwrap();
+        esu.identify_groups();
+        let serial = esu.group_results();
+
+        let mut par_esu = ParEsu::new(4, &graph).unwrap();
+        par_esu.set_colors(colors).unwrap();
+        par_esu.identify_groups();
+        let parallel = par_esu.group_results();
+
+        assert_eq!(serial.total_subgraphs(), 183174);
+        assert_eq!(serial.unique_subgraphs(), parallel.unique_subgraphs());
+        assert!(serial.unique_subgraphs() > 34);
+        assert_eq!(serial.groups(), parallel.groups());
+    }
+
+    #[test]
+    fn dir_yeast_split_matches_serial() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let serial = crate::enumerate::enumerate_subgraph_sizes(&graph, 2, 4).unwrap();
+
+        // The hubs of yeast have extensions large enough to be split
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let mut esu = ParEsu::new(4, &graph).unwrap();
+        esu.set_min_size(2).unwrap();
+        pool.install(|| esu.enumerate());
+        let utilization = esu.thread_utilization();
+        assert_eq!(utilization.len(), 4);
+        assert!(utilization.iter().all(|&u| (0.0..=1.0).contains(&u)));
+        assert!(utilization.iter().sum::<f64>() > 0.0);
+
+        let parallel = esu.size_results();
+        for ((k, serial), (pk, parallel)) in serial.iter().zip(parallel.iter()) {
+            assert_eq!(k, pk);
+            assert_eq!(serial.total_subgraphs(), parallel.total_subgraphs());
+            assert_eq!(serial.counts(), parallel.counts());
+        }
+    }
+
+    #[test]
+    fn scoped_thread_pools() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let serial = crate::enumerate::enumerate_subgraphs(&graph, 3).unwrap();
+
+        // Every enumeration can run on its own pool
+        for num_threads in [2, 3] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap();
+            let mut esu = ParEsu::new(3, &graph).unwrap();
+            esu.set_thread_pool(Arc::new(pool));
+            esu.enumerate();
+            assert_eq!(esu.thread_utilization().len(), num_threads);
+            assert_eq!(esu.result().counts(), serial.counts());
+        }
+    }
+
+    #[test]
+    fn cancelled_enumeration() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let full = parallel_enumerate_subgraphs(&graph, 4).unwrap();
+
+        // A flag set before the enumeration stops it immediately
+        let cancel = Arc::new(AtomicBool::new(true));
+        let mut esu = ParEsu::new(4, &graph).unwrap();
+        esu.set_cancel(cancel.clone());
+        esu.enumerate();
+        assert!(esu.is_cancelled());
+        assert_eq!(esu.result().total_subgraphs(), 0);
+
+        // A flag set during the enumeration stops it early
+        cancel.store(false, Ordering::Relaxed);
+        let mut esu = ParEsu::new(4, &graph).unwrap();
+        esu.set_cancel(cancel.clone());
+        esu.set_instances(move |_| cancel.store(true, Ordering::Relaxed));
+        esu.enumerate();
+        assert!(esu.is_cancelled());
+        assert!(esu.result().total_subgraphs() < full.total_subgraphs());
+    }
+
+    #[test]
+    fn dir_yeast_s4_typed_matches_serial() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let edges = graph
+            .edge_indices()
+            .map(|e| graph.edge_endpoints(e).unwrap())
+            .map(|(u, v)| (u.index() as u32, v.index() as u32))
+            .collect::<Vec<_>>();
+        let types = (0..edges.len()).map(|i| (i % 3) as u32).collect::<Vec<_>>();
+        let typed = || BitGraph::from_typed_edges(graph.node_count(), &edges, &types, true);
+
+        let mut esu = crate::enumerate::Esu::<Directed>::from_bitgraph(4, typed()).unwrap();
+        esu.identify_groups();
+        let serial = esu.group_results();
+
+        let mut par_esu = ParEsu::<Directed>::from_bitgraph(4, typed()).unwrap();
+        par_esu.identify_groups();
+        let parallel = par_esu.group_results();
+
+        assert_eq!(serial.total_subgraphs(), 183174);
+        assert!(serial.unique_subgraphs() > 34);
+        assert_eq!(serial.unique_subgraphs(), parallel.unique_subgraphs());
+        assert_eq!(serial.groups(), parallel.groups());
+    }
+
+    #[test]
+    fn undir_yeast_s4_instances_matches_serial() {
+        use std::sync::{Arc, Mutex};
+
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
+        let collect = |instances: &Arc<Mutex<Vec<_>>>| {
+            let instances = instances.clone();
+            move |instance: &crate::enumerate::Instance| {
+                let mut nodes = instance.nodes().to_vec();
+                nodes.sort_unstable();
+                let canonical = instance.canonical_nodes().collect::<Vec<_>>();
+                instances
+                    .lock()
+                    .unwrap()
+                    .push((nodes, instance.label().clone(), canonical));
+            }
+        };
+
+        let serial = Arc::new(Mutex::new(Vec::new()));
+        let mut esu = crate::enumerate::Esu::<Undirected>::new(4, &graph).unwrap();
+        esu.set_instances(collect(&serial));
+        esu.enumerate();
+
+        let parallel = Arc::new(Mutex::new(Vec::new()));
+        let mut par_esu = ParEsu::<Undirected>::new(4, &graph).unwrap();
+        par_esu.set_instances(collect(&parallel));
+        par_esu.enumerate();
+        assert_eq!(par_esu.result().total_subgraphs(), 183174);
+
+        let mut serial = serial.lock().unwrap().clone();
+        let mut parallel = parallel.lock().unwrap().clone();
+        serial.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        parallel.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(serial.len(), 183174);
+        for (s, p) in serial.iter().zip(parallel.iter()) {
+            assert_eq!(s.0, p.0);
+            assert_eq!(s.1, p.1);
+        }
+    }
+
+    #[test]
+    fn dir_yeast_query_matches_serial() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let motifs = ["&BP_", "&BCo", "&C?@w", "&C??w"];
+        let query = crate::enumerate::Query::from_graph6(&motifs, true).unwrap();
+
+        let mut esu = crate::enumerate::Esu::<Directed>::new(4, &graph).unwrap();
+        esu.set_query(query.clone()).unwrap();
+        esu.enumerate();
+        let serial = esu.size_results();
+
+        let mut par_esu = ParEsu::<Directed>::new(4, &graph).unwrap();
+        par_esu.set_query(query).unwrap();
+        par_esu.enumerate();
+        let parallel = par_esu.size_results();
+
+        assert_eq!(serial.len(), 2);
+        for ((k, s), (pk, p)) in serial.iter().zip(parallel.iter()) {
+            assert_eq!(k, pk);
+            assert_eq!(s.counts(), p.counts());
+            assert_eq!(s.total_subgraphs(), p.total_subgraphs());
+        }
+        // &BCo (70), &C?@w (121) and &C??w (148761)
+        assert_eq!(serial[0].1.total_subgraphs(), 70);
+        assert_eq!(serial[1].1.total_subgraphs(), 121 + 148761);
+    }
+
+    #[test]
+    fn dir_yeast_progress() {
+        use std::sync::{Arc, Mutex};
+
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut esu = ParEsu::new(3, &graph).unwrap();
+        {
+            let reports = reports.clone();
+            esu.set_progress(std::time::Duration::ZERO, move |progress| {
+                reports.lock().unwrap().push(*progress)
+            });
+        }
+        esu.enumerate();
+        let result = esu.result();
+
+        // The last report is the finished enumeration
+        let reports = reports.lock().unwrap();
+        let last = reports.last().unwrap();
+        assert!(reports.len() > 1);
+        assert_eq!(last.roots_done(), graph.node_count());
+        assert_eq!(last.roots_total(), graph.node_count());
+        assert_eq!(last.subgraphs(), result.total_subgraphs());
+    }
+
+    #[test]
+    fn dir_yeast_resume_after_cancel() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let full = parallel_enumerate_subgraph_sizes(&graph, 2, 4).unwrap();
+        let path = std::env::temp_dir().join("memoesu_dir_yeast_resume_after_cancel.bin");
+
+        // Interrupt an enumeration that writes a checkpoint after every batch
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut esu = ParEsu::new(4, &graph).unwrap();
+        esu.set_min_size(2).unwrap();
+        esu.set_checkpoint(&path, Duration::ZERO).unwrap();
+        esu.set_cancel(cancel.clone());
+        {
+            let cancel = cancel.clone();
+            let seen = AtomicU64::new(0);
+            let last = full.iter().map(|(_, r)| r.total_subgraphs()).sum::<usize>() as u64 * 9 / 10;
+            esu.set_instances(move |_| {
+                if seen.fetch_add(1, Ordering::Relaxed) > last {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+        esu.enumerate();
+        assert!(esu.is_cancelled());
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        assert!(checkpoint.roots_done() > 0);
+        assert!(checkpoint.roots_done() < graph.node_count());
+
+        // The resumed enumeration skips the finished roots
+        let mut esu = ParEsu::new(4, &graph).unwrap();
+        esu.set_min_size(2).unwrap();
+        esu.resume(checkpoint).unwrap();
+        esu.enumerate();
+        let resumed = esu.size_results();
+        std::fs::remove_file(&path).unwrap();
+        for ((_, full), (_, resumed)) in full.iter().zip(resumed.iter()) {
+            assert_eq!(full.total_subgraphs(), resumed.total_subgraphs());
+            assert_eq!(full.counts(), resumed.counts());
+        }
+    }
+
+    #[test]
+    fn invalid_resume() {
+        let filepath = "example/yeast.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let path = std::env::temp_dir().join("memoesu_invalid_resume.bin");
+        let mut esu = ParEsu::new(3, &graph).unwrap();
+        esu.set_checkpoint(&path, Duration::from_secs(3600))
+            .unwrap();
+        esu.enumerate();
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(checkpoint.roots_done(), graph.node_count());
+
+        // Different sizes
+        let mut esu = ParEsu::new(4, &graph).unwrap();
+        assert!(esu.resume(checkpoint.clone()).is_err());
+        let mut esu = ParEsu::new(3, &graph).unwrap();
+        esu.set_min_size(2).unwrap();
+        assert!(esu.resume(checkpoint.clone()).is_err());
+
+        // Different graph
+        let undirected = load_numeric_graph::<Undirected>(filepath, false).unwrap();
+        let mut esu = ParEsu::new(3, &undirected).unwrap();
+        assert!(esu.resume(checkpoint.clone()).is_err());
+
+        // Node colors, which must be set before resuming
+        let colors = vec![0; graph.node_count()];
+        let mut esu = ParEsu::new(3, &graph).unwrap();
+        esu.set_colors(colors.clone()).unwrap();
+        assert!(esu.resume(checkpoint.clone()).is_err());
+        let mut esu = ParEsu::new(3, &graph).unwrap();
+        esu.resume(checkpoint.clone()).unwrap();
+        assert!(esu.set_colors(colors).is_err());
+
+        // Sampling
+        let mut esu = ParEsu::new(3, &graph).unwrap();
+        esu.resume(checkpoint).unwrap();
+        assert!(esu
+            .set_sampling(Sampling::new(vec![1.0, 1.0, 0.5], 0).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn undir_ecoli_group_shards_sum_to_full() {
+        let filepath = "example/ecoli.txt";
+        let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
+        let full = parallel_group_subgraphs(&graph, 3).unwrap();
+        let mut groups = Groups::default();
+        for i in 1..=2 {
+            let mut esu = ParEsu::new(3, &graph).unwrap();
+            esu.set_shard(Shard::new(i, 2).unwrap());
+            esu.identify_groups();
+            merge_groups(&mut groups, esu.group_results().groups().clone());
+        }
+        assert_eq!(full.groups(), &groups);
+    }
+
+    #[test]
+    fn undir_ecoli_memo_cache() {
+        let filepath = "example/ecoli.txt";
+        let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
+        let mut esu = ParEsu::new(6, &graph).unwrap();
+        esu.set_min_size(2).unwrap();
+        esu.enumerate();
+        let cache = esu.memo_cache();
+        assert!(!cache.is_empty());
+
+        // A cache of the serial enumeration can be used in parallel and vice versa
+        let mut cached = crate::enumerate::Esu::new(6, &graph).unwrap();
+        cached.set_memo_cache(cache.clone()).unwrap();
+        cached.enumerate();
+        let mut par_cached = ParEsu::new(6, &graph).unwrap();
+        par_cached.set_memo_cache(cached.memo_cache()).unwrap();
+        par_cached.enumerate();
+        assert_eq!(par_cached.memo_cache().len(), cache.len());
+        assert_eq!(par_cached.result().counts(), cached.result().counts());
+
+        // Node colors must be set before the cache is loaded
+        let mut colored = ParEsu::new(6, &graph).unwrap();
+        colored.set_memo_cache(cache).unwrap();
+        assert!(colored.set_colors(vec![0; graph.node_count()]).is_err());
+    }
+
+    #[test]
+    fn undir_ecoli_lookup_matches_nauty() {
+        let filepath = "example/ecoli.txt";
+        let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
+        let results = [true, false].map(|lookup| {
+            let mut esu = ParEsu::new(5, &graph).unwrap();
+            esu.set_min_size(3).unwrap();
+            if !lookup {
+                esu.lookup = vec![None; 5];
+            }
+            esu.identify_groups();
+            assert_eq!(esu.memo.is_empty(), lookup);
+            esu.group_results()
+        });
+        assert_eq!(results[0].total_subgraphs(), results[1].total_subgraphs());
+        assert_eq!(results[0].groups(), results[1].groups());
+    }
 }
@@ -0,0 +1,241 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+type ProgressCallback = Box<dyn Fn(&Progress) + Send + Sync>;
+
+/// A snapshot of the progress of an enumeration, measured in the number of
+/// root vertices of the ESU tree whose subtrees are finished.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    roots_done: usize,
+    roots_total: usize,
+    subgraphs: usize,
+    elapsed: Duration,
+}
+impl Progress {
+    /// The number of roots whose subtrees are finished.
+    pub fn roots_done(&self) -> usize {
+        self.roots_done
+    }
+
+    /// The number of roots of the enumeration.
+    pub fn roots_total(&self) -> usize {
+        self.roots_total
+    }
+
+    /// The number of subgraphs counted so far.
+    pub fn subgraphs(&self) -> usize {
+        self.subgraphs
+    }
+
+    /// The time since the enumeration started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The fraction of the roots that are finished.
+    pub fn fraction(&self) -> f64 {
+        if self.roots_total == 0 {
+            1.0
+        } else {
+            self.roots_done as f64 / self.roots_total as f64
+        }
+    }
+
+    /// The number of subgraphs counted per second.
+    pub fn throughput(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.subgraphs as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// The estimated time until all roots are finished, assuming the
+    /// remaining roots take as long as the finished ones on average.
+    ///
+    /// Roots with smaller indices tend to have larger subtrees, so the
+    /// estimate is usually pessimistic early on.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.roots_done == 0 {
+            return None;
+        }
+        let remaining = (self.roots_total - self.roots_done) as f64 / self.roots_done as f64;
+        Some(self.elapsed.mul_f64(remaining))
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} roots ({:.1}%), {} subgraphs ({:.0}/s), ETA {}",
+            self.roots_done,
+            self.roots_total,
+            self.fraction() * 100.0,
+            self.subgraphs,
+            self.throughput(),
+            self.eta()
+                .map_or_else(|| "unknown".to_string(), format_duration)
+        )
+    }
+}
+
+/// Formats a duration as hours, minutes, and seconds.
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}h{minutes:02}m{seconds:02}s")
+    } else if minutes > 0 {
+        format!("{minutes}m{seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+/// Tracks the finished roots of an enumeration and passes the progress to
+/// a callback at most once per interval (and once at the end).
+///
+/// The tracker is shared by all threads of a parallel enumeration.
+pub(crate) struct ProgressTracker {
+    interval: Duration,
+    callback: ProgressCallback,
+    start: Instant,
+    roots_total: usize,
+    roots_done: AtomicUsize,
+    subgraphs: AtomicUsize,
+    /// The time of the last report in nanoseconds since the start.
+    last: AtomicU64,
+}
+impl ProgressTracker {
+    pub(crate) fn new<F: Fn(&Progress) + Send + Sync + 'static>(
+        interval: Duration,
+        callback: F,
+    ) -> Self {
+        Self {
+            interval,
+            callback: Box::new(callback),
+            start: Instant::now(),
+            roots_total: 0,
+            roots_done: AtomicUsize::new(0),
+            subgraphs: AtomicUsize::new(0),
+            last: AtomicU64::new(0),
+        }
+    }
+
+    /// Reset the tracker at the start of an enumeration over `roots_total` roots.
+    pub(crate) fn start(&mut self, roots_total: usize) {
+        self.start = Instant::now();
+        self.roots_total = roots_total;
+        self.roots_done = AtomicUsize::new(0);
+        self.subgraphs = AtomicUsize::new(0);
+        self.last = AtomicU64::new(0);
+    }
+
+    /// Record a finished root and the number of subgraphs counted in its
+    /// subtree, and report the progress if the interval has passed.
+    pub(crate) fn root_done(&self, subgraphs: usize) {
+        self.roots_done.fetch_add(1, Ordering::Relaxed);
+        self.subgraphs.fetch_add(subgraphs, Ordering::Relaxed);
+
+        // Only a single thread reports each interval
+        let elapsed = self.start.elapsed().as_nanos() as u64;
+        let last = self.last.load(Ordering::Relaxed);
+        if elapsed.saturating_sub(last) >= self.interval.as_nanos() as u64
+            && self
+                .last
+                .compare_exchange(last, elapsed, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.report();
+        }
+    }
+
+    /// Report the final progress.
+    pub(crate) fn finish(&self) {
+        self.report();
+    }
+
+    fn report(&self) {
+        let progress = Progress {
+            roots_done: self.roots_done.load(Ordering::Relaxed),
+            roots_total: self.roots_total,
+            subgraphs: self.subgraphs.load(Ordering::Relaxed),
+            elapsed: self.start.elapsed(),
+        };
+        (self.callback)(&progress);
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn progress_estimates() {
+        let progress = Progress {
+            roots_done: 25,
+            roots_total: 100,
+            subgraphs: 1000,
+            elapsed: Duration::from_secs(10),
+        };
+        assert_eq!(progress.fraction(), 0.25);
+        assert_eq!(progress.throughput(), 100.0);
+        assert_eq!(progress.eta(), Some(Duration::from_secs(30)));
+        assert_eq!(
+            progress.to_string(),
+            "25/100 roots (25.0%), 1000 subgraphs (100/s), ETA 30s"
+        );
+
+        let start = Progress {
+            roots_done: 0,
+            elapsed: Duration::ZERO,
+            ..progress
+        };
+        assert_eq!(start.eta(), None);
+        assert_eq!(start.throughput(), 0.0);
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h02m03s");
+    }
+
+    #[test]
+    fn tracker_reports_every_interval() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut tracker = {
+            let reports = reports.clone();
+            ProgressTracker::new(Duration::ZERO, move |p: &Progress| {
+                reports.lock().unwrap().push(*p)
+            })
+        };
+        tracker.start(3);
+        tracker.root_done(2);
+        tracker.root_done(0);
+        tracker.root_done(5);
+        tracker.finish();
+        let reports = reports.lock().unwrap();
+        let last = reports.last().unwrap();
+        assert!(!reports.is_empty());
+        assert_eq!(last.roots_done(), 3);
+        assert_eq!(last.subgraphs(), 7);
+        assert_eq!(last.fraction(), 1.0);
+
+        // A long interval only reports at the end
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut tracker = {
+            let count = count.clone();
+            ProgressTracker::new(Duration::from_secs(3600), move |_: &Progress| {
+                count.fetch_add(1, Ordering::Relaxed);
+            })
+        };
+        tracker.start(2);
+        tracker.root_done(1);
+        tracker.root_done(1);
+        tracker.finish();
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+    }
+}
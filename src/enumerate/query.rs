@@ -0,0 +1,335 @@
+use super::{BitGraph, Label, NautyGraph};
+use ahash::HashSet;
+use anyhow::{bail, Result};
+use graph6_rs::{DiGraph, Graph};
+
+/// The edge count and largest internal degrees of a subgraph.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Bounds {
+    edges: usize,
+    out_degree: usize,
+    in_degree: usize,
+}
+impl Bounds {
+    /// The bounds of the subgraph induced by `nodes`.
+    fn of<F: Fn(usize, usize) -> bool>(nodes: &[usize], has_arc: F, is_directed: bool) -> Self {
+        let mut bounds = Self::default();
+        for &u in nodes.iter() {
+            let out_degree = nodes.iter().filter(|&&v| u != v && has_arc(u, v)).count();
+            let in_degree = if is_directed {
+                nodes.iter().filter(|&&v| u != v && has_arc(v, u)).count()
+            } else {
+                out_degree
+            };
+            bounds.edges += out_degree;
+            bounds.out_degree = bounds.out_degree.max(out_degree);
+            bounds.in_degree = bounds.in_degree.max(in_degree);
+        }
+        bounds
+    }
+
+    /// The largest bounds of either subgraph.
+    fn max(self, other: Self) -> Self {
+        Self {
+            edges: self.edges.max(other.edges),
+            out_degree: self.out_degree.max(other.out_degree),
+            in_degree: self.in_degree.max(other.in_degree),
+        }
+    }
+
+    /// Whether a subgraph with these bounds can be an induced subgraph of
+    /// a subgraph with the `other` bounds.
+    fn within(&self, other: &Self) -> bool {
+        self.edges <= other.edges
+            && self.out_degree <= other.out_degree
+            && self.in_degree <= other.in_degree
+    }
+}
+
+/// A single motif of a query.
+#[derive(Debug, Clone)]
+struct Target {
+    size: usize,
+    label: Label,
+    bounds: Bounds,
+    min_degree: usize,
+}
+
+/// A set of target motifs to count instead of all subgraphs.
+///
+/// Branches of the ESU tree that cannot grow into any of the targets are
+/// pruned: a subgraph is only extended while its edge count and internal
+/// degrees are within those of a larger target, and nodes with fewer
+/// neighbors than any target node are skipped entirely.
+#[derive(Debug, Clone)]
+pub struct Query {
+    targets: Vec<Target>,
+    labels: HashSet<Label>,
+    is_directed: bool,
+    /// The largest bounds of the targets larger than each size (index size-1).
+    extend_bounds: Vec<Option<Bounds>>,
+    /// The edge counts of the targets of each size (index size-1).
+    edge_counts: Vec<HashSet<usize>>,
+    /// Whether each node of the graph can be part of a target.
+    admitted: Vec<bool>,
+}
+impl Query {
+    /// Create a query from the graph6 (undirected) or digraph6 (directed)
+    /// strings of the target motifs.
+    pub fn from_graph6<S: AsRef<str>>(motifs: &[S], is_directed: bool) -> Result<Self> {
+        let motifs = motifs
+            .iter()
+            .map(|motif| parse_motif(motif.as_ref(), is_directed))
+            .collect::<Result<Vec<_>>>()?;
+        Self::new(&motifs, is_directed)
+    }
+
+    /// Create a query from the adjacency matrices (row-major) of the target motifs.
+    pub fn new(motifs: &[(usize, Vec<usize>)], is_directed: bool) -> Result<Self> {
+        if motifs.is_empty() {
+            bail!("ERROR: A query requires at least one target motif.");
+        }
+        let targets = motifs
+            .iter()
+            .map(|(n, adj)| Target::new(*n, adj, is_directed))
+            .collect::<Result<Vec<_>>>()?;
+        let max_size = targets.iter().map(|t| t.size).max().unwrap_or(0);
+        let extend_bounds = (1..=max_size)
+            .map(|size| {
+                targets
+                    .iter()
+                    .filter(|t| t.size > size)
+                    .map(|t| t.bounds)
+                    .reduce(Bounds::max)
+            })
+            .collect();
+        let mut edge_counts = vec![HashSet::default(); max_size];
+        for target in targets.iter() {
+            edge_counts[target.size - 1].insert(target.bounds.edges);
+        }
+        let labels = targets.iter().map(|t| t.label.clone()).collect();
+        Ok(Self {
+            targets,
+            labels,
+            is_directed,
+            extend_bounds,
+            edge_counts,
+            admitted: Vec::new(),
+        })
+    }
+
+    /// The number of nodes of the smallest target.
+    pub fn min_size(&self) -> usize {
+        self.targets.iter().map(|t| t.size).min().unwrap_or(0)
+    }
+
+    /// The number of nodes of the largest target.
+    pub fn max_size(&self) -> usize {
+        self.extend_bounds.len()
+    }
+
+    /// The canonical labels of the targets in the order they were given.
+    pub fn labels(&self) -> impl Iterator<Item = &Label> {
+        self.targets.iter().map(|t| &t.label)
+    }
+
+    /// The number of nodes of each target in the order they were given.
+    pub fn sizes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.targets.iter().map(|t| t.size)
+    }
+
+    /// Whether the label is one of the targets.
+    pub fn contains(&self, label: &Label) -> bool {
+        self.labels.contains(label)
+    }
+
+    /// Prepare the query for a graph by marking the nodes that have enough
+    /// neighbors to be part of a target.
+    pub(crate) fn bind(&mut self, graph: &BitGraph) -> Result<()> {
+        if graph.is_directed != self.is_directed {
+            bail!("ERROR: The target motifs and the graph must both be directed or undirected.");
+        }
+        if graph.is_typed() {
+            bail!("ERROR: Queries are not supported for graphs with edge types.");
+        }
+        let min_degree = self.targets.iter().map(|t| t.min_degree).min().unwrap_or(0);
+        self.admitted = (0..graph.n)
+            .map(|v| graph.neighbors(v).take(min_degree).count() >= min_degree)
+            .collect();
+        Ok(())
+    }
+
+    /// Whether the node can be part of a target.
+    pub(crate) fn admits(&self, v: usize) -> bool {
+        self.admitted[v]
+    }
+
+    /// Whether the subgraph induced by `nodes` may be a target (`count`) and
+    /// whether it may be extended into a larger target (`extend`).
+    pub(crate) fn check(&self, graph: &BitGraph, nodes: &[usize]) -> (bool, bool) {
+        let size = nodes.len();
+
+        // The largest subgraphs are cheaper to match by their (memoized) labels
+        if size == self.max_size() {
+            return (true, false);
+        }
+        let bounds = if self.is_directed {
+            Bounds::of(nodes, |u, v| graph.is_connected_directed(u, v), true)
+        } else {
+            Bounds::of(nodes, |u, v| graph.is_connected(u, v), false)
+        };
+        let count = self.edge_counts[size - 1].contains(&bounds.edges);
+        let extend = self.extend_bounds[size - 1]
+            .as_ref()
+            .is_some_and(|target| bounds.within(target));
+        (count, extend)
+    }
+}
+
+impl Target {
+    fn new(n: usize, adj: &[usize], is_directed: bool) -> Result<Self> {
+        if n == 0 {
+            bail!("ERROR: Target motifs must have at least one node.");
+        }
+        if adj.len() != n * n {
+            bail!("ERROR: The adjacency matrix of a target motif must have {n}x{n} entries.");
+        }
+        let has_arc = |u: usize, v: usize| adj[u * n + v] == 1;
+        let nodes = (0..n).collect::<Vec<_>>();
+        let bounds = Bounds::of(&nodes, has_arc, is_directed);
+        if !is_connected(n, has_arc) {
+            bail!("ERROR: Target motifs must be connected.");
+        }
+
+        let mut ngraph = NautyGraph::new(n, is_directed);
+        for u in 0..n {
+            for v in 0..n {
+                if u != v && has_arc(u, v) {
+                    ngraph.add_arc(u, v);
+                }
+            }
+        }
+        ngraph.run();
+        let label = Label::from_words(ngraph.canon());
+
+        let min_degree = (0..n)
+            .map(|u| (0..n).filter(|&v| has_arc(u, v) || has_arc(v, u)).count())
+            .min()
+            .unwrap_or(0);
+        Ok(Self {
+            size: n,
+            label,
+            bounds,
+            min_degree,
+        })
+    }
+}
+
+/// Whether the graph is (weakly) connected.
+fn is_connected<F: Fn(usize, usize) -> bool>(n: usize, has_arc: F) -> bool {
+    let mut visited = vec![false; n];
+    let mut stack = vec![0];
+    visited[0] = true;
+    while let Some(u) = stack.pop() {
+        for (v, seen) in visited.iter_mut().enumerate() {
+            if !*seen && (has_arc(u, v) || has_arc(v, u)) {
+                *seen = true;
+                stack.push(v);
+            }
+        }
+    }
+    visited.into_iter().all(|v| v)
+}
+
+/// Parse a graph6 or digraph6 string into its size and adjacency matrix.
+///
+/// The string is validated up front since the parser panics on malformed input.
+fn parse_motif(motif: &str, is_directed: bool) -> Result<(usize, Vec<usize>)> {
+    let body = match (is_directed, motif.strip_prefix('&')) {
+        (true, Some(body)) => body,
+        (false, None) => motif,
+        (true, None) => {
+            bail!("ERROR: The motif `{motif}` is undirected but the graph is directed.")
+        }
+        (false, Some(_)) => {
+            bail!("ERROR: The motif `{motif}` is directed but the graph is undirected.")
+        }
+    };
+    let bytes = body.as_bytes();
+    let valid = !bytes.is_empty()
+        && bytes[0] < 126
+        && bytes.iter().all(|b| (63..=126).contains(b))
+        && bytes.len() == graph6_len(usize::from(bytes[0] - 63), is_directed);
+    if !valid {
+        bail!("ERROR: Unable to parse the motif `{motif}` as graph6.");
+    }
+    let parsed = if is_directed {
+        DiGraph::from_d6(motif).map(|g| (g.n, g.bit_vec))
+    } else {
+        Graph::from_g6(motif).map(|g| (g.n, g.bit_vec))
+    };
+    parsed.map_err(|_| anyhow::anyhow!("ERROR: Unable to parse the motif `{motif}` as graph6."))
+}
+
+/// The length of the graph6 string (without the digraph header) of a graph
+/// with `n` nodes, for graphs of at most 62 nodes.
+fn graph6_len(n: usize, is_directed: bool) -> usize {
+    let bits = if is_directed {
+        n * n
+    } else {
+        n * n.saturating_sub(1) / 2
+    };
+    1 + bits.div_ceil(6)
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+
+    #[test]
+    fn parse_motifs() {
+        // The feed-forward loop and the bi-fan
+        let query = Query::from_graph6(&["&BP_", "&C?Bw"], true).unwrap();
+        assert_eq!(query.min_size(), 3);
+        assert_eq!(query.max_size(), 4);
+        assert_eq!(query.sizes().collect::<Vec<_>>(), vec![3, 4]);
+
+        // Isomorphic targets share a label
+        let query = Query::from_graph6(&["&BP_", "&BCo", "&BW?"], true).unwrap();
+        let labels = query.labels().collect::<Vec<_>>();
+        assert_eq!(labels.len(), 3);
+        assert_ne!(labels[0], labels[1]);
+
+        let query = Query::from_graph6(&["Bw", "CF"], false).unwrap();
+        assert_eq!(query.max_size(), 4);
+    }
+
+    #[test]
+    fn invalid_motifs() {
+        // Malformed strings
+        for motif in ["", "&", "&B", "&BP_?", "&B P", "~"] {
+            assert!(Query::from_graph6(&[motif], true).is_err(), "{motif}");
+        }
+        // Direction mismatch
+        assert!(Query::from_graph6(&["Bw"], true).is_err());
+        assert!(Query::from_graph6(&["&BP_"], false).is_err());
+        // Disconnected
+        assert!(Query::from_graph6(&["BG"], false).is_err());
+        assert!(Query::from_graph6(&["&B?_"], true).is_err());
+        // Empty
+        assert!(Query::from_graph6::<&str>(&[], true).is_err());
+    }
+
+    #[test]
+    fn bounds_of_feed_forward_loop() {
+        let adj = [0, 1, 1, 0, 0, 1, 0, 0, 0];
+        let nodes = [0, 1, 2];
+        let bounds = Bounds::of(&nodes, |u, v| adj[u * 3 + v] == 1, true);
+        assert_eq!(bounds.edges, 3);
+        assert_eq!(bounds.out_degree, 2);
+        assert_eq!(bounds.in_degree, 2);
+        let path = Bounds::of(&nodes[..2], |u, v| adj[u * 3 + v] == 1, true);
+        assert!(path.within(&bounds));
+        assert!(!bounds.within(&path));
+    }
+}
@@ -1,18 +1,26 @@
-use crate::enumerate::{Counts, Groups};
+use crate::enumerate::{Counts, Estimates, Groups};
 
 #[derive(Debug)]
 pub struct EnumResult {
     canon_counts: Counts,
     num_subgraphs: usize,
+    estimates: Option<Estimates>,
 }
 impl EnumResult {
     pub fn new(canon_counts: Counts, num_subgraphs: usize) -> Self {
         Self {
             canon_counts,
             num_subgraphs,
+            estimates: None,
         }
     }
 
+    /// Attach the RAND-ESU estimates of a sampled enumeration.
+    pub fn with_estimates(mut self, estimates: Estimates) -> Self {
+        self.estimates = Some(estimates);
+        self
+    }
+
     pub fn counts(&self) -> &Counts {
         &self.canon_counts
     }
@@ -24,12 +32,18 @@ impl EnumResult {
     pub fn unique_subgraphs(&self) -> usize {
         self.canon_counts.len()
     }
+
+    /// The estimated counts if the enumeration was sampled.
+    pub fn estimates(&self) -> Option<&Estimates> {
+        self.estimates.as_ref()
+    }
 }
 
 pub struct GroupResult {
     groups: Groups,
     num_total_subgraphs: usize,
     num_unique_subgraphs: usize,
+    estimates: Option<Estimates>,
 }
 impl GroupResult {
     pub fn new(groups: Groups, num_total_subgraphs: usize, num_unique_subgraphs: usize) -> Self {
@@ -37,9 +51,16 @@ impl GroupResult {
             groups,
             num_total_subgraphs,
             num_unique_subgraphs,
+            estimates: None,
         }
     }
 
+    /// Attach the RAND-ESU estimates of a sampled enumeration.
+    pub fn with_estimates(mut self, estimates: Estimates) -> Self {
+        self.estimates = Some(estimates);
+        self
+    }
+
     pub fn groups(&self) -> &Groups {
         &self.groups
     }
@@ -51,4 +72,9 @@ impl GroupResult {
     pub fn unique_subgraphs(&self) -> usize {
         self.num_unique_subgraphs
     }
+
+    /// The estimated counts if the enumeration was sampled.
+    pub fn estimates(&self) -> Option<&Estimates> {
+        self.estimates.as_ref()
+    }
 }
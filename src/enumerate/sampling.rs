@@ -0,0 +1,289 @@
+use super::{Counts, Label};
+use ahash::HashMap;
+use anyhow::{bail, Result};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+
+/// Two-sided 95% quantile of the standard normal distribution.
+const Z_95: f64 = 1.959_963_984_540_054;
+
+/// Parameters for RAND-ESU sampling.
+///
+/// Each node at depth `d` of the ESU tree (where the root vertices are at depth 0)
+/// is explored with probability `probs[d]`. Every subgraph is therefore reached
+/// with the same probability, the product of all `probs`, which is used to scale
+/// the sampled counts to unbiased estimates.
+///
+/// For more information see:
+/// S. Wernicke, “Efficient Detection of Network Motifs,” IEEE/ACM Trans. Comput. Biol. and Bioinf., 2006.
+#[derive(Debug, Clone)]
+pub struct Sampling {
+    /// The child retention probability at each depth of the ESU tree.
+    probs: Vec<f64>,
+
+    /// The seed for the random number generator.
+    seed: u64,
+}
+impl Sampling {
+    pub fn new(probs: Vec<f64>, seed: u64) -> Result<Self> {
+        if probs.is_empty() {
+            bail!("ERROR: At least one sampling probability is required.");
+        }
+        if let Some(p) = probs.iter().find(|&&p| !(p > 0.0 && p <= 1.0)) {
+            bail!("ERROR: Sampling probabilities must be in the range (0, 1]; found {p}.");
+        }
+        Ok(Self { probs, seed })
+    }
+
+    pub fn probs(&self) -> &[f64] {
+        &self.probs
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The probability that any single subgraph is sampled.
+    pub fn inclusion_probability(&self) -> f64 {
+        self.probs.iter().product()
+    }
+
+    /// Validates that there is one probability for every depth of the ESU tree.
+    pub(crate) fn validate(&self, motif_size: usize) -> Result<()> {
+        if self.probs.len() != motif_size {
+            bail!(
+                "ERROR: Expected {motif_size} sampling probabilities (one per depth) but found {}.",
+                self.probs.len()
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn state(&self) -> SampleState {
+        SampleState::new(self)
+    }
+}
+
+/// The running state of a RAND-ESU traversal.
+///
+/// Besides deciding which children to explore, this tracks the sampled
+/// subgraph counts below every partially sampled depth so that the variance
+/// of the estimates can be calculated with the unbiased multistage
+/// (Horvitz-Thompson) variance estimator:
+///
+/// `V = 1 / P^2 * sum_d (1 - p_d) * pi_{d-1} * sum_{u in S_d} c_u^2`
+///
+/// where `P` is the inclusion probability of a subgraph, `pi_{d-1}` is the
+/// inclusion probability of a node at depth `d - 1`, `S_d` are the sampled
+/// nodes at depth `d`, and `c_u` is the number of sampled subgraphs below `u`.
+pub(crate) struct SampleState {
+    rng: ChaChaRng,
+    seed: u64,
+    probs: Vec<f64>,
+    coefficients: Vec<f64>,
+    sampled_levels: Vec<usize>,
+    level_counts: Vec<Counts>,
+    level_totals: Vec<usize>,
+    variance: HashMap<Label, f64>,
+    total_variance: f64,
+}
+impl SampleState {
+    fn new(sampling: &Sampling) -> Self {
+        let depth = sampling.probs.len();
+        let mut coefficients = Vec::with_capacity(depth);
+        let mut parent_prob = 1.0;
+        for p in sampling.probs.iter() {
+            coefficients.push((1.0 - p) * parent_prob);
+            parent_prob *= p;
+        }
+        let sampled_levels = (0..depth.saturating_sub(1))
+            .filter(|&d| sampling.probs[d] < 1.0)
+            .collect();
+        Self {
+            rng: ChaChaRng::seed_from_u64(sampling.seed),
+            seed: sampling.seed,
+            probs: sampling.probs.clone(),
+            coefficients,
+            sampled_levels,
+            level_counts: vec![Counts::default(); depth],
+            level_totals: vec![0; depth],
+            variance: HashMap::default(),
+            total_variance: 0.0,
+        }
+    }
+
+    /// Starts the ESU tree rooted at `root`.
+    ///
+    /// Every root uses an independent stream of the random number generator
+    /// so that the sampled subgraphs do not depend on the order (or thread)
+    /// in which the roots are processed.
+    pub(crate) fn start_root(&mut self, root: usize) {
+        self.rng = ChaChaRng::seed_from_u64(self.seed);
+        self.rng.set_stream(root as u64);
+    }
+
+    /// Decides whether a node at the given depth is explored.
+    pub(crate) fn keep(&mut self, depth: usize) -> bool {
+        let p = self.probs[depth];
+        p >= 1.0 || self.rng.gen::<f64>() < p
+    }
+
+    /// Records a sampled subgraph with the given label.
+    pub(crate) fn record(&mut self, label: &Label) {
+        for &d in self.sampled_levels.iter() {
+            *self.level_counts[d].entry(label.clone()).or_insert(0) += 1;
+            self.level_totals[d] += 1;
+        }
+        let leaf = self.probs.len() - 1;
+        if self.probs[leaf] < 1.0 {
+            let coef = self.coefficients[leaf];
+            *self.variance.entry(label.clone()).or_insert(0.0) += coef;
+            self.total_variance += coef;
+        }
+    }
+
+    /// Finalizes the subtree of a node at the given depth.
+    pub(crate) fn exit(&mut self, depth: usize) {
+        if self.probs[depth] >= 1.0 {
+            return;
+        }
+        let coef = self.coefficients[depth];
+        for (label, count) in self.level_counts[depth].drain() {
+            *self.variance.entry(label).or_insert(0.0) += coef * (count * count) as f64;
+        }
+        let total = self.level_totals[depth];
+        self.total_variance += coef * (total * total) as f64;
+        self.level_totals[depth] = 0;
+    }
+
+    /// Merges the variance terms of another (independent) traversal.
+    pub(crate) fn merge(&mut self, other: SampleState) {
+        for (label, variance) in other.variance {
+            *self.variance.entry(label).or_insert(0.0) += variance;
+        }
+        self.total_variance += other.total_variance;
+    }
+
+    /// Scales the sampled counts to estimates of the true counts.
+    pub(crate) fn estimates(&self, counts: &Counts, total: usize) -> Estimates {
+        let prob = self.probs.iter().product::<f64>();
+        let scale = |count: usize, variance: f64| Estimate {
+            value: count as f64 / prob,
+            variance: variance / (prob * prob),
+        };
+        let counts = counts
+            .iter()
+            .map(|(label, &count)| {
+                let variance = self.variance.get(label).copied().unwrap_or(0.0);
+                (label.clone(), scale(count, variance))
+            })
+            .collect();
+        Estimates {
+            inclusion_probability: prob,
+            total: scale(total, self.total_variance),
+            counts,
+        }
+    }
+}
+
+/// An estimated subgraph count and its variance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub value: f64,
+    pub variance: f64,
+}
+impl Estimate {
+    pub fn std_error(&self) -> f64 {
+        self.variance.sqrt()
+    }
+
+    /// The normal approximation 95% confidence interval of the estimate.
+    pub fn confidence_interval(&self) -> (f64, f64) {
+        let margin = Z_95 * self.std_error();
+        ((self.value - margin).max(0.0), self.value + margin)
+    }
+}
+
+/// The estimated subgraph counts of a RAND-ESU run.
+#[derive(Debug, Clone)]
+pub struct Estimates {
+    inclusion_probability: f64,
+    total: Estimate,
+    counts: HashMap<Label, Estimate>,
+}
+impl Estimates {
+    /// The probability that any single subgraph was sampled.
+    pub fn inclusion_probability(&self) -> f64 {
+        self.inclusion_probability
+    }
+
+    /// The estimated total number of subgraphs.
+    pub fn total(&self) -> &Estimate {
+        &self.total
+    }
+
+    /// The estimated number of subgraphs for each canonical label.
+    pub fn counts(&self) -> &HashMap<Label, Estimate> {
+        &self.counts
+    }
+}
+
+#[cfg(test)]
+mod testing {
+
+    use super::*;
+
+    #[test]
+    fn invalid_probabilities() {
+        assert!(Sampling::new(vec![], 0).is_err());
+        assert!(Sampling::new(vec![1.0, 1.0], 0)
+            .unwrap()
+            .validate(3)
+            .is_err());
+        assert!(Sampling::new(vec![1.0, 0.0, 0.5], 0).is_err());
+        assert!(Sampling::new(vec![1.0, 1.5, 0.5], 0).is_err());
+        assert!(Sampling::new(vec![1.0, 1.0, 0.5], 0)
+            .unwrap()
+            .validate(3)
+            .is_ok());
+    }
+
+    #[test]
+    fn inclusion_probability() {
+        let sampling = Sampling::new(vec![1.0, 0.5, 0.2], 0).unwrap();
+        assert!((sampling.inclusion_probability() - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn exhaustive_has_no_variance() {
+        let sampling = Sampling::new(vec![1.0, 1.0, 1.0], 0).unwrap();
+        let mut state = sampling.state();
+        state.start_root(0);
+        let label: Label = vec![1, 2, 3].into();
+        for _ in 0..10 {
+            assert!(state.keep(2));
+            state.record(&label);
+        }
+        (0..3).rev().for_each(|d| state.exit(d));
+        let mut counts = Counts::default();
+        counts.insert(label.clone(), 10);
+        let estimates = state.estimates(&counts, 10);
+        assert_eq!(estimates.counts()[&label].value, 10.0);
+        assert_eq!(estimates.counts()[&label].variance, 0.0);
+        assert_eq!(estimates.total().variance, 0.0);
+    }
+
+    #[test]
+    fn root_streams_are_independent_of_order() {
+        let sampling = Sampling::new(vec![0.5, 0.5, 0.5], 42).unwrap();
+        let mut state = sampling.state();
+        let mut draws = |root| {
+            state.start_root(root);
+            (0..32).map(|_| state.keep(1)).collect::<Vec<_>>()
+        };
+        let a = draws(3);
+        let _ = draws(4);
+        assert_eq!(a, draws(3));
+        assert_ne!(a, draws(4));
+    }
+}
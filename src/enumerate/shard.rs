@@ -0,0 +1,100 @@
+use anyhow::{bail, Context, Result};
+use std::{fmt, str::FromStr};
+
+/// A deterministic subset of the roots of the ESU tree, so that a single
+/// enumeration can be split over independent processes.
+///
+/// The `i`-th of `N` shards (counting from 1) holds every root `v` with
+/// `v % N == i - 1`. Roots with smaller indices tend to have larger
+/// subtrees, so interleaving the roots balances the shards better than
+/// contiguous ranges. As every subgraph is found below exactly one root,
+/// the counts of all shards sum to the counts of the full enumeration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    index: usize,
+    count: usize,
+}
+impl Shard {
+    /// The `index`-th (counting from 1) of `count` shards.
+    pub fn new(index: usize, count: usize) -> Result<Self> {
+        if count == 0 {
+            bail!("ERROR: The number of shards must be at least 1.");
+        }
+        if !(1..=count).contains(&index) {
+            bail!("ERROR: The shard index must be between 1 and {count} but found {index}.");
+        }
+        Ok(Self { index, count })
+    }
+
+    /// The index of the shard (counting from 1).
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The total number of shards.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Whether the root belongs to the shard.
+    pub fn contains(&self, root: usize) -> bool {
+        root % self.count == self.index - 1
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl FromStr for Shard {
+    type Err = anyhow::Error;
+
+    /// Parse a shard given as `i/N`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (index, count) = s
+            .split_once('/')
+            .with_context(|| format!("ERROR: Expected a shard as i/N but found {s:?}."))?;
+        let index = index
+            .trim()
+            .parse()
+            .with_context(|| format!("ERROR: Could not parse the shard index in {s:?}."))?;
+        let count = count
+            .trim()
+            .parse()
+            .with_context(|| format!("ERROR: Could not parse the number of shards in {s:?}."))?;
+        Self::new(index, count)
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+
+    #[test]
+    fn shards_partition_roots() {
+        let shards = (1..=3)
+            .map(|i| Shard::new(i, 3).unwrap())
+            .collect::<Vec<_>>();
+        for root in 0..100 {
+            assert_eq!(shards.iter().filter(|s| s.contains(root)).count(), 1);
+        }
+        assert!(shards[0].contains(0));
+        assert!(shards[1].contains(4));
+        assert!(shards[2].contains(8));
+        assert!(Shard::new(1, 1).unwrap().contains(7));
+    }
+
+    #[test]
+    fn parse_shard() {
+        let shard = "2/8".parse::<Shard>().unwrap();
+        assert_eq!((shard.index(), shard.count()), (2, 8));
+        assert_eq!(shard.to_string(), "2/8");
+        assert!("0/8".parse::<Shard>().is_err());
+        assert!("9/8".parse::<Shard>().is_err());
+        assert!("1/0".parse::<Shard>().is_err());
+        assert!("1".parse::<Shard>().is_err());
+        assert!("a/2".parse::<Shard>().is_err());
+    }
+}
@@ -0,0 +1,441 @@
+use crate::enumerate::{
+    arcs_graph, BitGraph, DefaultCanonicalizer, Esu, Instance, Label, ParEsu, Shard,
+};
+use anyhow::{bail, Result};
+use hashbrown::HashMap;
+use ndarray::Array2;
+use petgraph::Undirected;
+use rayon::ThreadPool;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// The largest graphlet size with an orbit numbering.
+pub const MAX_GRAPHLET_SIZE: usize = 5;
+
+/// A graphlet given by its number of nodes, its edges, and the orbit of each node.
+type Graphlet = (usize, &'static [(usize, usize)], &'static [usize]);
+
+/// The graphlets G0-G29 with 2 to 5 nodes and the orbits 0-72 of their nodes.
+///
+/// The graphlets are ordered by their number of nodes and then of edges, and
+/// the orbits of every graphlet are numbered consecutively, following the
+/// layout of Pržulj (2007). The numbering has not been checked against a
+/// reference implementation such as ORCA, so the order of the orbits within a
+/// 5-node graphlet (and of the graphlets with equal numbers of edges) may
+/// differ from it.
+const GRAPHLETS: &[Graphlet] = &[
+    // G0: edge
+    (2, &[(0, 1)], &[0, 0]),
+    // G1: path
+    (3, &[(0, 1), (1, 2)], &[1, 2, 1]),
+    // G2: triangle
+    (3, &[(0, 1), (1, 2), (0, 2)], &[3, 3, 3]),
+    // G3: path
+    (4, &[(0, 1), (1, 2), (2, 3)], &[4, 5, 5, 4]),
+    // G4: star
+    (4, &[(0, 1), (0, 2), (0, 3)], &[7, 6, 6, 6]),
+    // G5: cycle
+    (4, &[(0, 1), (1, 2), (2, 3), (3, 0)], &[8, 8, 8, 8]),
+    // G6: paw (triangle 0-1-2 with a pendant 3 at 0)
+    (4, &[(0, 1), (1, 2), (0, 2), (0, 3)], &[11, 10, 10, 9]),
+    // G7: diamond (without the edge 2-3)
+    (
+        4,
+        &[(0, 1), (0, 2), (0, 3), (1, 2), (1, 3)],
+        &[13, 13, 12, 12],
+    ),
+    // G8: clique
+    (
+        4,
+        &[(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)],
+        &[14, 14, 14, 14],
+    ),
+    // G9: path
+    (5, &[(0, 1), (1, 2), (2, 3), (3, 4)], &[15, 16, 17, 16, 15]),
+    // G10: fork (center 0 with leaves 1, 2 and the path 0-3-4)
+    (5, &[(0, 1), (0, 2), (0, 3), (3, 4)], &[20, 21, 21, 19, 18]),
+    // G11: star
+    (5, &[(0, 1), (0, 2), (0, 3), (0, 4)], &[23, 22, 22, 22, 22]),
+    // G12: bull (triangle 0-1-2 with pendants 3 at 1 and 4 at 2)
+    (
+        5,
+        &[(0, 1), (1, 2), (0, 2), (1, 3), (2, 4)],
+        &[25, 26, 26, 24, 24],
+    ),
+    // G13: tadpole (triangle 0-1-2 with the path 0-3-4)
+    (
+        5,
+        &[(0, 1), (1, 2), (0, 2), (0, 3), (3, 4)],
+        &[30, 29, 29, 28, 27],
+    ),
+    // G14: cricket (triangle 0-1-2 with pendants 3 and 4 at 0)
+    (
+        5,
+        &[(0, 1), (1, 2), (0, 2), (0, 3), (0, 4)],
+        &[33, 32, 32, 31, 31],
+    ),
+    // G15: cycle
+    (
+        5,
+        &[(0, 1), (1, 2), (2, 3), (3, 4), (4, 0)],
+        &[34, 34, 34, 34, 34],
+    ),
+    // G16: banner (cycle 0-1-2-3 with a pendant 4 at 0)
+    (
+        5,
+        &[(0, 1), (1, 2), (2, 3), (3, 0), (0, 4)],
+        &[38, 36, 37, 36, 35],
+    ),
+    // G17: diamond 0-1-2-3 (without 0-3) with a pendant 4 at 0
+    (
+        5,
+        &[(0, 1), (0, 2), (1, 2), (1, 3), (2, 3), (0, 4)],
+        &[42, 41, 41, 40, 39],
+    ),
+    // G18: bowtie (triangles 0-1-2 and 0-3-4)
+    (
+        5,
+        &[(0, 1), (0, 2), (1, 2), (0, 3), (0, 4), (3, 4)],
+        &[44, 43, 43, 43, 43],
+    ),
+    // G19: house (cycle 0-1-2-3 with the roof 4 on 0 and 1)
+    (
+        5,
+        &[(0, 1), (1, 2), (2, 3), (3, 0), (0, 4), (1, 4)],
+        &[47, 47, 46, 46, 45],
+    ),
+    // G20: diamond 0-1-2-3 (without 2-3) with a pendant 4 at 0
+    (
+        5,
+        &[(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (0, 4)],
+        &[51, 50, 49, 49, 48],
+    ),
+    // G21: complete bipartite K2,3
+    (
+        5,
+        &[(0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4)],
+        &[53, 53, 52, 52, 52],
+    ),
+    // G22: book (0 and 1 joined to each other and to 2, 3, 4)
+    (
+        5,
+        &[(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4)],
+        &[55, 55, 54, 54, 54],
+    ),
+    // G23: clique 0-1-2-3 with a pendant 4 at 0
+    (
+        5,
+        &[(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3), (0, 4)],
+        &[58, 57, 57, 57, 56],
+    ),
+    // G24: cycle 0-1-2-3 with the chord 0-2 and 4 joined to 1 and 3
+    (
+        5,
+        &[(0, 1), (1, 2), (2, 3), (3, 0), (0, 2), (1, 4), (3, 4)],
+        &[60, 61, 60, 61, 59],
+    ),
+    // G25: gem (4 joined to all of the path 0-1-2-3)
+    (
+        5,
+        &[(0, 1), (1, 2), (2, 3), (0, 4), (1, 4), (2, 4), (3, 4)],
+        &[62, 63, 63, 62, 64],
+    ),
+    // G26: clique without the edges 0-1 and 1-2
+    (
+        5,
+        &[
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (1, 3),
+            (1, 4),
+            (2, 3),
+            (2, 4),
+            (3, 4),
+        ],
+        &[66, 65, 66, 67, 67],
+    ),
+    // G27: clique without the edges 0-1 and 2-3
+    (
+        5,
+        &[
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 4),
+            (3, 4),
+        ],
+        &[68, 68, 68, 68, 69],
+    ),
+    // G28: clique without the edge 0-1
+    (
+        5,
+        &[
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 3),
+            (2, 4),
+            (3, 4),
+        ],
+        &[70, 70, 71, 71, 71],
+    ),
+    // G29: clique
+    (
+        5,
+        &[
+            (0, 1),
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 3),
+            (2, 4),
+            (3, 4),
+        ],
+        &[72, 72, 72, 72, 72],
+    ),
+];
+
+/// The number of orbits of the graphlets with 2 up to `k` nodes.
+pub fn num_orbits(k: usize) -> usize {
+    GRAPHLETS
+        .iter()
+        .filter(|(n, _, _)| *n <= k)
+        .flat_map(|(_, _, orbits)| orbits.iter())
+        .max()
+        .map_or(0, |max| max + 1)
+}
+
+/// The orbit of every canonical position of the graphlets with up to `k` nodes.
+fn orbit_table(k: usize) -> HashMap<Label, Vec<usize>> {
+    GRAPHLETS
+        .iter()
+        .filter(|(n, _, _)| *n <= k)
+        .map(|&(n, edges, orbits)| {
+            let ngraph = arcs_graph::<DefaultCanonicalizer>(n, edges, false);
+            let label = Label::from_words(ngraph.canon());
+            let positions = ngraph.nodes.lab[..n]
+                .iter()
+                .map(|&node| orbits[node as usize])
+                .collect();
+            (label, positions)
+        })
+        .collect()
+}
+
+/// Count the graphlet degree vectors of all nodes of an undirected graph,
+/// i.e. how often every node touches each orbit of the graphlets with 2 up
+/// to `k` nodes.
+///
+/// The result has a row per node and a column per orbit (numbered as in the
+/// graphlet table of this module), and is computed in parallel on the thread pool if
+/// one is given. If a shard is given only the graphlets found below its roots
+/// are counted, so the vectors of all shards sum to the full vectors.
+pub fn graphlet_degree_vectors(
+    graph: BitGraph,
+    k: usize,
+    shard: Option<Shard>,
+    pool: Option<Arc<ThreadPool>>,
+) -> Result<Array2<u64>> {
+    if !(2..=MAX_GRAPHLET_SIZE).contains(&k) {
+        bail!("ERROR: Graphlet degree vectors require a graphlet size between 2 and {MAX_GRAPHLET_SIZE}.");
+    }
+    if graph.is_directed || graph.is_typed() || graph.has_loops() {
+        bail!("ERROR: Graphlet degree vectors require an undirected graph without edge types or loops.");
+    }
+    let n = graph.node_count();
+    let width = num_orbits(k);
+    let table = Arc::new(orbit_table(k));
+    let gdv = Arc::new(
+        (0..n * width)
+            .map(|_| AtomicU64::new(0))
+            .collect::<Vec<_>>(),
+    );
+
+    // Every instance adds its nodes to the orbits of their canonical positions
+    let callback = {
+        let gdv = gdv.clone();
+        move |instance: &Instance| {
+            let orbits = &table[instance.label()];
+            for (node, &orbit) in instance.canonical_nodes().zip(orbits) {
+                gdv[node * width + orbit].fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    };
+    if let Some(pool) = pool {
+        let mut esu = ParEsu::<Undirected>::from_bitgraph(k, graph)?;
+        esu.set_min_size(2)?;
+        esu.set_instances(callback);
+        esu.set_thread_pool(pool);
+        if let Some(shard) = shard {
+            esu.set_shard(shard);
+        }
+        esu.enumerate();
+    } else {
+        let mut esu = Esu::<Undirected>::from_bitgraph(k, graph)?;
+        esu.set_min_size(2)?;
+        esu.set_instances(callback);
+        if let Some(shard) = shard {
+            esu.set_shard(shard);
+        }
+        esu.enumerate();
+    }
+
+    let gdv = Arc::into_inner(gdv)
+        .expect("the enumerator has been dropped")
+        .into_iter()
+        .map(AtomicU64::into_inner)
+        .collect();
+    Ok(Array2::from_shape_vec((n, width), gdv)?)
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use crate::{enumerate::enumerate_subgraph_sizes, io::load_numeric_graph};
+    use hashbrown::HashSet;
+    use petgraph::Graph;
+
+    fn load_ecoli() -> Graph<(), (), Undirected> {
+        load_numeric_graph("example/ecoli.txt", false).unwrap()
+    }
+
+    #[test]
+    fn graphlet_orbits() {
+        assert_eq!(num_orbits(2), 1);
+        assert_eq!(num_orbits(3), 4);
+        assert_eq!(num_orbits(4), 15);
+        assert_eq!(num_orbits(5), 73);
+
+        // Every orbit belongs to a single graphlet and every graphlet is distinct
+        let table = orbit_table(5);
+        assert_eq!(table.len(), GRAPHLETS.len());
+        let orbits = table.values().flatten().collect::<HashSet<_>>();
+        assert_eq!(orbits.len(), 73);
+        for (_, _, orbits) in GRAPHLETS {
+            for (_, _, other) in GRAPHLETS.iter().filter(|(_, _, other)| other != orbits) {
+                assert!(orbits.iter().all(|o| !other.contains(o)));
+            }
+        }
+
+        // The orbits of a graphlet match its automorphism orbits
+        for &(n, edges, orbits) in GRAPHLETS {
+            let ngraph = arcs_graph::<DefaultCanonicalizer>(n, edges, false);
+            for u in 0..n {
+                for v in 0..n {
+                    let same = ngraph.nodes.orbits[u] == ngraph.nodes.orbits[v];
+                    assert_eq!(same, orbits[u] == orbits[v], "{orbits:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn undir_ecoli_gdv() {
+        let graph = load_ecoli();
+        let gdv = graphlet_degree_vectors(BitGraph::from_graph(&graph), 5, None, None).unwrap();
+        assert_eq!(gdv.dim(), (graph.node_count(), 73));
+
+        // Orbit 0 is the degree and orbits 2 and 3 cover every pair of neighbors
+        for v in graph.node_indices() {
+            let degree = graph.neighbors(v).collect::<HashSet<_>>().len() as u64;
+            assert_eq!(gdv[[v.index(), 0]], degree);
+            let pairs = degree * degree.saturating_sub(1) / 2;
+            assert_eq!(gdv[[v.index(), 2]] + gdv[[v.index(), 3]], pairs);
+        }
+
+        // Every graphlet is counted once for each of its nodes
+        let table = orbit_table(5);
+        let results = enumerate_subgraph_sizes(&graph, 2, 5).unwrap();
+        for (_, result) in results.iter() {
+            for (label, &count) in result.counts().iter() {
+                let orbits = &table[label];
+                let total = orbits
+                    .iter()
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .map(|&o| gdv.column(o).sum())
+                    .sum::<u64>();
+                assert_eq!(total, count as u64 * orbits.len() as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn undir_fixture_gdv() {
+        // A random graph with a planted clique, in which every orbit occurs. The
+        // fixture was counted by brute force with the orbits of the graphlet
+        // table, so it pins the enumeration but not the orbit numbering
+        let graph: Graph<(), (), Undirected> =
+            load_numeric_graph("example/graphlets.txt", false).unwrap();
+        let gdv = graphlet_degree_vectors(BitGraph::from_graph(&graph), 5, None, None).unwrap();
+        let reference = std::fs::read_to_string("example/graphlets_gdv.tsv").unwrap();
+        let mut rows = reference.lines();
+        assert_eq!(rows.next().unwrap().split('\t').count(), 74);
+        let reference = rows
+            .map(|row| {
+                row.split('\t')
+                    .skip(1)
+                    .map(|value| value.parse::<u64>().unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(reference.len(), graph.node_count());
+        for (v, expected) in reference.iter().enumerate() {
+            assert_eq!(gdv.row(v).to_vec(), *expected, "node {}", v + 1);
+        }
+        assert!((0..73).all(|o| gdv.column(o).sum() > 0));
+    }
+
+    #[test]
+    fn undir_ecoli_gdv_parallel() {
+        let graph = load_ecoli();
+        let serial = graphlet_degree_vectors(BitGraph::from_graph(&graph), 4, None, None).unwrap();
+        let pool = Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(4)
+                .build()
+                .unwrap(),
+        );
+        let parallel =
+            graphlet_degree_vectors(BitGraph::from_graph(&graph), 4, None, Some(pool)).unwrap();
+        assert_eq!(serial.dim(), (graph.node_count(), 15));
+        assert_eq!(serial, parallel);
+    }
+
+    #[test]
+    fn undir_ecoli_gdv_shards() {
+        let graph = load_ecoli();
+        let full = graphlet_degree_vectors(BitGraph::from_graph(&graph), 4, None, None).unwrap();
+        let sharded = (1..=3)
+            .map(|i| {
+                let shard = Shard::new(i, 3).unwrap();
+                graphlet_degree_vectors(BitGraph::from_graph(&graph), 4, Some(shard), None).unwrap()
+            })
+            .reduce(|a, b| a + b)
+            .unwrap();
+        assert_eq!(full, sharded);
+    }
+
+    #[test]
+    fn invalid_gdv() {
+        let graph = load_ecoli();
+        assert!(graphlet_degree_vectors(BitGraph::from_graph(&graph), 1, None, None).is_err());
+        assert!(graphlet_degree_vectors(BitGraph::from_graph(&graph), 6, None, None).is_err());
+        let directed = BitGraph::from_edges(2, &[(0, 1)], true);
+        assert!(graphlet_degree_vectors(directed, 3, None, None).is_err());
+        let looped = BitGraph::from_edges(2, &[(0, 1), (1, 1)], false);
+        assert!(graphlet_degree_vectors(looped, 3, None, None).is_err());
+    }
+}
@@ -1,7 +1,8 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use bitvec::{prelude::Msb0, view::BitView};
 use graph6_rs::write_graph6;
 use hashbrown::{HashMap, HashSet};
+use ndarray::Array2;
 use petgraph::{Directed, EdgeType, Graph};
 use std::{
     fs::File,
@@ -9,8 +10,13 @@ use std::{
 };
 
 use crate::{
+    edge_orbit::EdgeOrbitCounts,
     enrichment::EnrichResult,
-    enumerate::{Counts, Groups, Label},
+    enumerate::{
+        BitGraph, Color, Counts, EdgeColor, Estimates, Groups, Instance, Label, SizeResults,
+    },
+    enumerate::{EDGE_CODE_BITS, TYPE_ROW_TAG},
+    merge::{write_shard_header, ShardHeader},
 };
 
 pub struct FormatGraph {
@@ -33,22 +39,21 @@ impl FormatGraph {
 
     /// Reads a graph from a file path.
     pub fn from_filepath(filepath: &str, filter_loops: bool) -> Result<Self> {
-        let mut reader = File::open(filepath).map(BufReader::new)?;
-        Ok(Self::from_buffer(&mut reader, filter_loops))
+        let mut reader = File::open(filepath)
+            .map(BufReader::new)
+            .with_context(|| format!("ERROR: Could not open graph file: {filepath}"))?;
+        Self::from_buffer(&mut reader, filter_loops)
     }
 
     /// Reads a graph from a buffer.
-    pub fn from_buffer<B: BufRead>(buffer: &mut B, filter_loops: bool) -> Self {
+    pub fn from_buffer<B: BufRead>(buffer: &mut B, filter_loops: bool) -> Result<Self> {
         let mut map = HashMap::new();
         let mut edges = HashSet::new();
         let mut num_filtered = 0;
 
-        for line in buffer.lines() {
-            let line = line.unwrap();
-            let mut split = line.split_whitespace();
-
-            let u = split.next().unwrap();
-            let v = split.next().unwrap();
+        for (line_idx, line) in buffer.lines().enumerate() {
+            let line = line?;
+            let (u, v) = split_edge(&line, line_idx)?;
 
             if filter_loops && u == v {
                 num_filtered += 1;
@@ -67,7 +72,7 @@ impl FormatGraph {
         }
 
         let graph = Graph::from_edges(&edges);
-        Self::new(graph, map, num_filtered)
+        Ok(Self::new(graph, map, num_filtered))
     }
 
     pub fn write_graph(&self, output: &str) -> Result<()> {
@@ -107,7 +112,9 @@ pub fn load_numeric_graph<Ty: EdgeType>(
     filepath: &str,
     include_loops: bool,
 ) -> Result<Graph<(), (), Ty>> {
-    let mut reader = File::open(filepath).map(BufReader::new)?;
+    let mut reader = File::open(filepath)
+        .map(BufReader::new)
+        .with_context(|| format!("ERROR: Could not open graph file: {filepath}"))?;
     load_numeric_graph_from_buffer(&mut reader, include_loops)
 }
 
@@ -118,12 +125,172 @@ pub fn load_numeric_graph_from_buffer<B: BufRead, Ty: EdgeType>(
     buffer: &mut B,
     include_loops: bool,
 ) -> Result<Graph<(), (), Ty>> {
+    let edges = load_numeric_edges(buffer, include_loops)?;
+    Ok(Graph::from_edges(&edges))
+}
+
+/// Load a graph from a file directly into a [`BitGraph`]
+///
+/// Expects a 1-Indexed numeric white-space delimited edgelist.
+/// This avoids building an intermediate petgraph `Graph` and chooses
+/// a dense or sparse adjacency representation based on the density of the graph.
+pub fn load_numeric_bitgraph<Ty: EdgeType>(
+    filepath: &str,
+    include_loops: bool,
+) -> Result<BitGraph> {
+    let mut reader = File::open(filepath)
+        .map(BufReader::new)
+        .with_context(|| format!("ERROR: Could not open graph file: {filepath}"))?;
+    load_numeric_bitgraph_from_buffer::<_, Ty>(&mut reader, include_loops)
+}
+
+/// Load a graph from a buffer directly into a [`BitGraph`]
+///
+/// Expects a 1-Indexed numeric white-space delimited edgelist.
+pub fn load_numeric_bitgraph_from_buffer<B: BufRead, Ty: EdgeType>(
+    buffer: &mut B,
+    include_loops: bool,
+) -> Result<BitGraph> {
+    let edges = load_numeric_edges(buffer, include_loops)?;
+    let n = edges
+        .iter()
+        .map(|&(u, v)| u.max(v) as usize + 1)
+        .max()
+        .unwrap_or(0);
+    Ok(BitGraph::from_edges(n, &edges, Ty::is_directed()))
+}
+
+/// Load a graph with typed edges from a file directly into a [`BitGraph`]
+///
+/// Expects a 1-Indexed numeric white-space delimited edgelist with a third
+/// column containing the numeric type of every edge (e.g. 0 for activation and
+/// 1 for repression).
+pub fn load_numeric_typed_bitgraph<Ty: EdgeType>(
+    filepath: &str,
+    include_loops: bool,
+) -> Result<BitGraph> {
+    let mut reader = File::open(filepath)
+        .map(BufReader::new)
+        .with_context(|| format!("ERROR: Could not open graph file: {filepath}"))?;
+    load_numeric_typed_bitgraph_from_buffer::<_, Ty>(&mut reader, include_loops)
+}
+
+/// Load a graph with typed edges from a buffer directly into a [`BitGraph`]
+///
+/// Expects a 1-Indexed numeric white-space delimited edgelist with a third
+/// column containing the numeric type of every edge.
+pub fn load_numeric_typed_bitgraph_from_buffer<B: BufRead, Ty: EdgeType>(
+    buffer: &mut B,
+    include_loops: bool,
+) -> Result<BitGraph> {
+    let mut edges = Vec::new();
+    let mut colors = Vec::new();
+    let mut known = HashMap::new();
+    for (line_idx, line) in buffer.lines().enumerate() {
+        let line = line?;
+        let (u, v) = split_edge(&line, line_idx)?;
+        let u = parse_node(u, line_idx)?;
+        let v = parse_node(v, line_idx)?;
+        let color = match line.split_whitespace().nth(2) {
+            Some(color) => color.parse::<EdgeColor>().with_context(|| {
+                format!(
+                    "ERROR: Could not parse edge type {color:?} on line {} as an integer",
+                    line_idx + 1
+                )
+            })?,
+            None => bail!(
+                "ERROR: Line {} does not contain an edge type: {line:?}",
+                line_idx + 1
+            ),
+        };
+        if u == 0 || v == 0 {
+            bail!("ERROR: Found a node index: 0; Please use 1-indexed node indices.");
+        }
+        if !include_loops && u == v {
+            continue;
+        }
+        let key = if Ty::is_directed() {
+            (u, v)
+        } else {
+            (u.min(v), u.max(v))
+        };
+        if let Some(known_color) = known.insert(key, color) {
+            if known_color != color {
+                bail!(
+                    "ERROR: Edge {u} {v} on line {} has type {color} but was previously given type {known_color}",
+                    line_idx + 1
+                );
+            }
+        }
+        edges.push((u - 1, v - 1));
+        colors.push(color);
+    }
+    let n = edges
+        .iter()
+        .map(|&(u, v)| u.max(v) as usize + 1)
+        .max()
+        .unwrap_or(0);
+    Ok(BitGraph::from_typed_edges(
+        n,
+        &edges,
+        &colors,
+        Ty::is_directed(),
+    ))
+}
+
+/// Load the colors of the nodes of a graph from a file
+///
+/// Expects a 1-Indexed white-space delimited list of numeric nodes and their
+/// numeric colors.
+/// Nodes which are not listed are assigned the color 0.
+pub fn load_node_colors(filepath: &str, num_nodes: usize) -> Result<Vec<Color>> {
+    let mut reader = File::open(filepath)
+        .map(BufReader::new)
+        .with_context(|| format!("ERROR: Could not open color file: {filepath}"))?;
+    load_node_colors_from_buffer(&mut reader, num_nodes)
+}
+
+/// Load the colors of the nodes of a graph from a buffer
+///
+/// Expects a 1-Indexed white-space delimited list of numeric nodes and their
+/// numeric colors.
+/// Nodes which are not listed are assigned the color 0.
+pub fn load_node_colors_from_buffer<B: BufRead>(
+    buffer: &mut B,
+    num_nodes: usize,
+) -> Result<Vec<Color>> {
+    let mut colors = vec![0; num_nodes];
+    for (line_idx, line) in buffer.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (node, color) = split_edge(&line, line_idx)?;
+        let node = parse_node(node, line_idx)? as usize;
+        let color = color
+            .parse::<Color>()
+            .with_context(|| format!("ERROR: Invalid color on line {}: '{color}'", line_idx + 1))?;
+        if node == 0 || node > num_nodes {
+            bail!(
+                "ERROR: Node {node} on line {} is not in the graph ({num_nodes} nodes)",
+                line_idx + 1
+            );
+        }
+        colors[node - 1] = color;
+    }
+    Ok(colors)
+}
+
+/// Load a 0-indexed edgelist from a buffer
+///
+/// Expects a 1-Indexed numeric white-space delimited edgelist.
+fn load_numeric_edges<B: BufRead>(buffer: &mut B, include_loops: bool) -> Result<Vec<(u32, u32)>> {
     let mut edges = Vec::new();
-    for line in buffer.lines() {
-        let line = line.unwrap();
-        let mut split = line.split_whitespace();
-        let u = split.next().unwrap().parse::<u32>()?;
-        let v = split.next().unwrap().parse::<u32>()?;
+    for (line_idx, line) in buffer.lines().enumerate() {
+        let line = line?;
+        let (u, v) = split_edge(&line, line_idx)?;
+        let u = parse_node(u, line_idx)?;
+        let v = parse_node(v, line_idx)?;
         if u == 0 || v == 0 {
             bail!("ERROR: Found a node index: 0; Please use 1-indexed node indices.");
         }
@@ -133,23 +300,63 @@ pub fn load_numeric_graph_from_buffer<B: BufRead, Ty: EdgeType>(
             edges.push((u - 1, v - 1));
         }
     }
-    Ok(Graph::from_edges(&edges))
+    Ok(edges)
+}
+
+/// Splits an edgelist line into its source and target node.
+fn split_edge(line: &str, line_idx: usize) -> Result<(&str, &str)> {
+    let mut split = line.split_whitespace();
+    match (split.next(), split.next()) {
+        (Some(u), Some(v)) => Ok((u, v)),
+        _ => bail!(
+            "ERROR: Line {} does not contain two white-space separated nodes: {line:?}",
+            line_idx + 1
+        ),
+    }
+}
+
+/// Parses a numeric node identifier.
+fn parse_node(node: &str, line_idx: usize) -> Result<u32> {
+    node.parse::<u32>().with_context(|| {
+        format!(
+            "ERROR: Could not parse node {node:?} on line {} as an integer",
+            line_idx + 1
+        )
+    })
 }
 
 /// Write the counts of each subgraph to a file or stdout
+///
+/// The output of a sharded run starts with the parameters of the run.
 pub fn write_counts(
     canon_counts: &Counts,
     k: usize,
     output: Option<String>,
     is_directed: bool,
+    with_header: bool,
+    shard: Option<&ShardHeader>,
 ) -> Result<()> {
     if let Some(output) = output {
         let mut buffer = File::create(&output).map(BufWriter::new)?;
         eprintln!(">> Writing results to      : {}", &output);
-        write_counts_to_buffer(&mut buffer, canon_counts, k, is_directed)
+        write_counts_to_buffer(
+            &mut buffer,
+            canon_counts,
+            k,
+            is_directed,
+            with_header,
+            shard,
+        )
     } else {
         let mut buffer = BufWriter::new(stdout().lock());
-        write_counts_to_buffer(&mut buffer, canon_counts, k, is_directed)
+        write_counts_to_buffer(
+            &mut buffer,
+            canon_counts,
+            k,
+            is_directed,
+            with_header,
+            shard,
+        )
     }
 }
 
@@ -159,35 +366,189 @@ fn write_counts_to_buffer<W: Write>(
     canon_counts: &Counts,
     k: usize,
     is_directed: bool,
+    with_header: bool,
+    shard: Option<&ShardHeader>,
 ) -> Result<()> {
+    write_shard_header(buffer, shard)?;
+
     // Sort by count
     let mut sorted_counts: Vec<(&Label, &usize)> = canon_counts.iter().collect();
     sorted_counts.sort_by(|a, b| a.1.cmp(b.1));
 
     // Write to buffer
+    if with_header {
+        let header = label_header(canon_counts.keys().next(), k);
+        writeln!(buffer, "{header}\tcount")?;
+    }
     for (label, count) in sorted_counts {
-        let adj = graph_to_flat_adj(label, k);
-        let canon = write_graph6(adj, k, is_directed);
+        let canon = format_label(&label.words(), k, is_directed);
         writeln!(buffer, "{canon}\t{count}")?;
     }
     Ok(())
 }
 
+/// Write the counts of each subgraph of every size to a file or stdout
+///
+/// The subgraph size is written as an additional leading `k` column.
+pub fn write_size_counts(
+    results: &SizeResults,
+    output: Option<String>,
+    is_directed: bool,
+    with_header: bool,
+    shard: Option<&ShardHeader>,
+) -> Result<()> {
+    if let Some(output) = output {
+        let mut buffer = File::create(&output).map(BufWriter::new)?;
+        eprintln!(">> Writing results to      : {}", &output);
+        write_size_counts_to_buffer(&mut buffer, results, is_directed, with_header, shard)
+    } else {
+        let mut buffer = BufWriter::new(stdout().lock());
+        write_size_counts_to_buffer(&mut buffer, results, is_directed, with_header, shard)
+    }
+}
+
+/// Write the counts of each subgraph of every size to a buffer
+fn write_size_counts_to_buffer<W: Write>(
+    buffer: &mut BufWriter<W>,
+    results: &SizeResults,
+    is_directed: bool,
+    with_header: bool,
+    shard: Option<&ShardHeader>,
+) -> Result<()> {
+    write_shard_header(buffer, shard)?;
+    if with_header {
+        // The label columns are the same for every size.
+        let label = results
+            .iter()
+            .find_map(|(k, result)| result.counts().keys().next().map(|label| (*k, label)));
+        let header = match label {
+            Some((k, label)) => label_header(Some(label), k),
+            None => label_header(None, 0),
+        };
+        writeln!(buffer, "k\t{header}\tcount")?;
+    }
+    for (k, result) in results {
+        // Sort by count
+        let mut sorted_counts: Vec<(&Label, &usize)> = result.counts().iter().collect();
+        sorted_counts.sort_by(|a, b| a.1.cmp(b.1));
+
+        // Write to buffer
+        for (label, count) in sorted_counts {
+            let canon = format_label(&label.words(), *k, is_directed);
+            writeln!(buffer, "{k}\t{canon}\t{count}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the sampled and estimated counts of each subgraph to a file or stdout
+pub fn write_sampled_counts(
+    canon_counts: &Counts,
+    estimates: &Estimates,
+    k: usize,
+    output: Option<String>,
+    is_directed: bool,
+) -> Result<()> {
+    if let Some(output) = output {
+        let mut buffer = File::create(&output).map(BufWriter::new)?;
+        eprintln!(">> Writing results to      : {}", &output);
+        write_sampled_counts_to_buffer(&mut buffer, canon_counts, estimates, k, is_directed)
+    } else {
+        let mut buffer = BufWriter::new(stdout().lock());
+        write_sampled_counts_to_buffer(&mut buffer, canon_counts, estimates, k, is_directed)
+    }
+}
+
+/// Write the sampled and estimated counts of each subgraph to a buffer
+fn write_sampled_counts_to_buffer<W: Write>(
+    buffer: &mut BufWriter<W>,
+    canon_counts: &Counts,
+    estimates: &Estimates,
+    k: usize,
+    is_directed: bool,
+) -> Result<()> {
+    // Sort by count
+    let mut sorted_counts: Vec<(&Label, &usize)> = canon_counts.iter().collect();
+    sorted_counts.sort_by(|a, b| a.1.cmp(b.1));
+
+    // Write to buffer
+    let header = label_header(canon_counts.keys().next(), k);
+    writeln!(buffer, "{header}\tsampled\testimate\tci_lower\tci_upper")?;
+    for (label, count) in sorted_counts {
+        let canon = format_label(&label.words(), k, is_directed);
+        let estimate = &estimates.counts()[label];
+        let (lower, upper) = estimate.confidence_interval();
+        writeln!(
+            buffer,
+            "{canon}\t{count}\t{:.3}\t{lower:.3}\t{upper:.3}",
+            estimate.value
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the counts of the target motifs of a query in the order they were given.
+pub fn write_query_counts(
+    motifs: &[String],
+    counts: &[usize],
+    output: Option<String>,
+) -> Result<()> {
+    if let Some(output) = output {
+        let mut buffer = File::create(&output).map(BufWriter::new)?;
+        eprintln!(">> Writing results to      : {}", &output);
+        write_query_counts_to_buffer(&mut buffer, motifs, counts)
+    } else {
+        let mut buffer = BufWriter::new(stdout().lock());
+        write_query_counts_to_buffer(&mut buffer, motifs, counts)
+    }
+}
+fn write_query_counts_to_buffer<W: Write>(
+    buffer: &mut BufWriter<W>,
+    motifs: &[String],
+    counts: &[usize],
+) -> Result<()> {
+    for (motif, count) in motifs.iter().zip(counts) {
+        writeln!(buffer, "{motif}\t{count}")?;
+    }
+    Ok(())
+}
+
 /// Write the groups of each node to a file or stdout
+///
+/// If the groups were sampled the `inclusion_probability` is used to
+/// report the estimated abundance of each group as an additional column.
 pub fn write_groups(
     groups: &Groups,
     k: usize,
     output: Option<String>,
     is_directed: bool,
     no_header: bool,
+    inclusion_probability: Option<f64>,
+    shard: Option<&ShardHeader>,
 ) -> Result<()> {
     if let Some(output) = output {
         let mut buffer = File::create(&output).map(BufWriter::new)?;
         eprintln!(">> Writing results to      : {}", &output);
-        write_groups_to_buffer(&mut buffer, groups, k, is_directed, no_header)
+        write_groups_to_buffer(
+            &mut buffer,
+            groups,
+            k,
+            is_directed,
+            no_header,
+            inclusion_probability,
+            shard,
+        )
     } else {
         let mut buffer = BufWriter::new(stdout().lock());
-        write_groups_to_buffer(&mut buffer, groups, k, is_directed, no_header)
+        write_groups_to_buffer(
+            &mut buffer,
+            groups,
+            k,
+            is_directed,
+            no_header,
+            inclusion_probability,
+            shard,
+        )
     }
 }
 
@@ -198,30 +559,219 @@ fn write_groups_to_buffer<W: Write>(
     k: usize,
     is_directed: bool,
     no_header: bool,
+    inclusion_probability: Option<f64>,
+    shard: Option<&ShardHeader>,
 ) -> Result<()> {
+    write_shard_header(buffer, shard)?;
     if !no_header {
-        writeln!(buffer, "node_idx\tcanon\tlabel\torbit\tabundance")?;
-    }
-    for (node_idx, group_info) in groups.iter() {
-        for ((label, node_label, orbit), abundance) in group_info.iter() {
-            let adj = graph_to_flat_adj(label, k);
-            let canon = write_graph6(adj, k, is_directed);
-            let adj_node_idx = node_idx + 1;
-            writeln!(
-                buffer,
-                "{adj_node_idx}\t{canon}\t{node_label}\t{orbit}\t{abundance}"
-            )?;
+        let label = groups
+            .values()
+            .flat_map(|group_info| group_info.keys())
+            .map(|(label, _, _)| label)
+            .next();
+        let header = label_header(label, k);
+        write!(buffer, "node_idx\t{header}\tlabel\torbit\tabundance")?;
+        if inclusion_probability.is_some() {
+            write!(buffer, "\testimate")?;
         }
+        writeln!(buffer)?;
+    }
+    // Sort the rows so that the output does not depend on hashing or thread order.
+    let mut rows = groups
+        .iter()
+        .flat_map(|(node_idx, group_info)| {
+            group_info
+                .iter()
+                .map(move |((label, node_label, orbit), abundance)| {
+                    let canon = format_label(&label.words(), k, is_directed);
+                    (*node_idx, canon, *node_label, *orbit, *abundance)
+                })
+        })
+        .collect::<Vec<_>>();
+    rows.sort_unstable();
+    for (node_idx, canon, node_label, orbit, abundance) in rows {
+        let adj_node_idx = node_idx + 1;
+        write!(
+            buffer,
+            "{adj_node_idx}\t{canon}\t{node_label}\t{orbit}\t{abundance}"
+        )?;
+        if let Some(prob) = inclusion_probability {
+            write!(buffer, "\t{:.3}", abundance as f64 / prob)?;
+        }
+        writeln!(buffer)?;
     }
     Ok(())
 }
 
+/// Streams subgraph instances as they are enumerated.
+///
+/// Every line holds the (1-indexed) nodes of an instance in the order they
+/// were enumerated, its canonical label, and the nodes in canonical order.
+/// Instances can be restricted to a set of motifs given as graph6 strings.
+pub struct InstanceWriter<W: Write> {
+    buffer: BufWriter<W>,
+    is_directed: bool,
+    header: bool,
+    motifs: Option<HashSet<String>>,
+    formatted: HashMap<Label, Option<String>>,
+    written: usize,
+    error: Option<std::io::Error>,
+}
+
+impl<W: Write> InstanceWriter<W> {
+    pub fn new(buffer: W, is_directed: bool, no_header: bool, motifs: Option<Vec<String>>) -> Self {
+        Self {
+            buffer: BufWriter::new(buffer),
+            is_directed,
+            header: !no_header,
+            motifs: motifs.map(|motifs| motifs.into_iter().collect()),
+            formatted: HashMap::new(),
+            written: 0,
+            error: None,
+        }
+    }
+
+    /// Write an instance if its motif is selected.
+    ///
+    /// Writing stops at the first error, which is returned by [`InstanceWriter::finish`].
+    pub fn write(&mut self, instance: &Instance) {
+        if self.error.is_none() {
+            if let Err(error) = self.write_instance(instance) {
+                self.error = Some(error);
+            }
+        }
+    }
+
+    fn write_instance(&mut self, instance: &Instance) -> std::io::Result<()> {
+        let k = instance.size();
+        if self.header {
+            let header = label_header(Some(instance.label()), k);
+            writeln!(self.buffer, "nodes\t{header}\tcanonical_nodes")?;
+            self.header = false;
+        }
+
+        // Every label is only formatted (and matched against the motifs) once
+        let canon = self
+            .formatted
+            .entry(instance.label().clone())
+            .or_insert_with(|| {
+                let canon = format_label(&instance.label().words(), k, self.is_directed);
+                let graph6 = canon.split('\t').next().unwrap_or_default();
+                self.motifs
+                    .as_ref()
+                    .is_none_or(|motifs| motifs.contains(graph6))
+                    .then_some(canon)
+            });
+        let Some(canon) = canon else {
+            return Ok(());
+        };
+        let nodes = join(instance.nodes().iter().map(|u| u + 1));
+        let canonical_nodes = join(instance.canonical_nodes().map(|u| u + 1));
+        writeln!(self.buffer, "{nodes}\t{canon}\t{canonical_nodes}")?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Flush the output and return the number of written instances.
+    pub fn finish(mut self) -> Result<usize> {
+        if let Some(error) = self.error.take() {
+            return Err(error.into());
+        }
+        if self.header {
+            writeln!(self.buffer, "nodes\tcanon\tcanonical_nodes")?;
+        }
+        self.buffer.flush()?;
+        Ok(self.written)
+    }
+}
+
+/// Create an [`InstanceWriter`] for the output file (or stdout).
+pub fn instance_writer(
+    output: Option<String>,
+    is_directed: bool,
+    no_header: bool,
+    motifs: Option<Vec<String>>,
+) -> Result<InstanceWriter<Box<dyn Write + Send>>> {
+    let buffer: Box<dyn Write + Send> = if let Some(output) = output {
+        eprintln!(">> Writing results to      : {}", &output);
+        Box::new(File::create(&output)?)
+    } else {
+        Box::new(stdout())
+    };
+    Ok(InstanceWriter::new(buffer, is_directed, no_header, motifs))
+}
+
+/// Format a canonical label as its graph6 string
+///
+/// The color vector of colored subgraphs (i.e. the colors of the nodes in
+/// canonical order) and the edge types of edge-typed subgraphs (in row-major
+/// order of the canonical adjacency) are appended as additional comma separated columns.
+fn format_label(label: &[u64], k: usize, is_directed: bool) -> String {
+    let (adj, types, colors) = label_sections(label, k);
+    let adj = graph_to_flat_adj(adj, k);
+    let mut canon = write_graph6(adj, k, is_directed);
+    if let Some(colors) = colors {
+        canon.push('\t');
+        canon.push_str(&join(colors.iter()));
+    }
+    if let Some(types) = types {
+        let mask = (1 << EDGE_CODE_BITS) - 1;
+        let edge_types = types.iter().enumerate().flat_map(|(i, row)| {
+            (0..k)
+                .filter(move |&j| is_directed || j >= i)
+                .map(move |j| row >> (EDGE_CODE_BITS * j) & mask)
+                .filter(|&code| code != 0)
+                .map(|code| code - 1)
+        });
+        canon.push('\t');
+        canon.push_str(&join(edge_types));
+    }
+    canon
+}
+
+/// The header of the columns written by [`format_label`]
+fn label_header(label: Option<&Label>, k: usize) -> String {
+    let mut header = String::from("canon");
+    if let Some(label) = label {
+        let words = label.words();
+        let (_, types, colors) = label_sections(&words, k);
+        if colors.is_some() {
+            header.push_str("\tcolors");
+        }
+        if types.is_some() {
+            header.push_str("\tedge_types");
+        }
+    }
+    header
+}
+
+/// Splits a canonical label into its adjacency, edge type rows, and node colors
+fn label_sections(label: &[u64], k: usize) -> (&[u64], Option<&[u64]>, Option<&[u64]>) {
+    let (adj, rest) = label.split_at(k.min(label.len()));
+    let (types, colors) = match rest.first() {
+        Some(row) if row & TYPE_ROW_TAG != 0 => {
+            let (types, colors) = rest.split_at(k);
+            (Some(types), colors)
+        }
+        _ => (None, rest),
+    };
+    (adj, types, (!colors.is_empty()).then_some(colors))
+}
+
+/// Joins values with commas
+fn join<T: ToString>(values: impl Iterator<Item = T>) -> String {
+    values
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
 /// Convert a nauty graph to a flat adjacency matrix
 fn graph_to_flat_adj(graph: &[u64], n: usize) -> Vec<usize> {
     let mut adj = Vec::with_capacity(n * n);
-    for num in graph.iter() {
+    for num in graph.iter().take(n) {
         let bv = num.view_bits::<Msb0>();
-        for b in bv.iter().take(graph.len()) {
+        for b in bv.iter().take(n) {
             if *b {
                 adj.push(1);
             } else {
@@ -232,14 +782,19 @@ fn graph_to_flat_adj(graph: &[u64], n: usize) -> Vec<usize> {
     adj
 }
 
-pub fn write_stats(results: &EnrichResult, k: usize, output: Option<String>) -> Result<()> {
+pub fn write_stats(
+    results: &EnrichResult,
+    k: usize,
+    output: Option<String>,
+    is_directed: bool,
+) -> Result<()> {
     if let Some(output) = output {
         let mut buffer = File::create(&output).map(BufWriter::new)?;
         eprintln!(">> Writing results to      : {}", &output);
-        write_stats_to_buffer(&mut buffer, results, k)
+        write_stats_to_buffer(&mut buffer, results, k, is_directed)
     } else {
         let mut buffer = BufWriter::new(stdout().lock());
-        write_stats_to_buffer(&mut buffer, results, k)
+        write_stats_to_buffer(&mut buffer, results, k, is_directed)
     }
 }
 
@@ -247,23 +802,155 @@ fn write_stats_to_buffer<W: Write>(
     buffer: &mut BufWriter<W>,
     results: &EnrichResult,
     k: usize,
+    is_directed: bool,
 ) -> Result<()> {
-    writeln!(buffer, "canon\tabundance\tmean\tstd\tzscore")?;
+    writeln!(
+        buffer,
+        "canon\tabundance\tconcentration\tmean_abundance\tstd_abundance\t\
+         mean_concentration\tstd_concentration\tzscore\tsp\tlog2fc\t\
+         p_over\tp_under\tq_over\tq_under"
+    )?;
     for idx in 0..results.len() {
         let subgraph = &results.subgraphs[idx];
         let adj = graph_to_flat_adj(subgraph, k);
-        let canon = write_graph6(adj, k, true);
+        let canon = write_graph6(adj, k, is_directed);
         let abundance = &results.abundances[idx];
-        let mean = &results.mean_random_frequency[idx];
-        let std = &results.std_random_frequency[idx];
+        let concentration = &results.concentrations[idx];
+        let mean = &results.mean_random_abundance[idx];
+        let std = &results.std_random_abundance[idx];
+        let mean_concentration = &results.mean_random_concentration[idx];
+        let std_concentration = &results.std_random_concentration[idx];
         let zscore = &results.zscores[idx];
-        writeln!(buffer, "{canon}\t{abundance}\t{mean}\t{std}\t{zscore}")?;
+        let sp = &results.significance_profile[idx];
+        let log2fc = &results.log2_fold_changes[idx];
+        let (p_over, p_under) = (&results.p_over[idx], &results.p_under[idx]);
+        let (q_over, q_under) = (&results.q_over[idx], &results.q_under[idx]);
+        writeln!(
+            buffer,
+            "{canon}\t{abundance}\t{concentration}\t{mean}\t{std}\t\
+             {mean_concentration}\t{std_concentration}\t{zscore}\t{sp}\t{log2fc}\t\
+             {p_over}\t{p_under}\t{q_over}\t{q_under}"
+        )?;
     }
     Ok(())
 }
 
+/// Write the graphlet degree vectors of all nodes as a wide table with a
+/// column per orbit, or as a `.npy` matrix if the output ends with `.npy`.
+///
+/// The output of a sharded run starts with the parameters of the run, and
+/// can therefore only be written as a table.
+pub fn write_gdv(
+    gdv: &Array2<u64>,
+    output: Option<String>,
+    no_header: bool,
+    shard: Option<&ShardHeader>,
+) -> Result<()> {
+    if let Some(output) = output {
+        if output.ends_with(".npy") && shard.is_some() {
+            bail!("ERROR: The output of a sharded run can not be written as a NumPy matrix.");
+        }
+        let mut buffer = File::create(&output).map(BufWriter::new)?;
+        eprintln!(">> Writing results to      : {}", &output);
+        if output.ends_with(".npy") {
+            write_npy_to_buffer(&mut buffer, gdv)
+        } else {
+            write_gdv_to_buffer(&mut buffer, gdv, no_header, shard)
+        }
+    } else {
+        let mut buffer = BufWriter::new(stdout().lock());
+        write_gdv_to_buffer(&mut buffer, gdv, no_header, shard)
+    }
+}
+
+fn write_gdv_to_buffer<W: Write>(
+    buffer: &mut BufWriter<W>,
+    gdv: &Array2<u64>,
+    no_header: bool,
+    shard: Option<&ShardHeader>,
+) -> Result<()> {
+    write_shard_header(buffer, shard)?;
+    if !no_header {
+        let orbits = join_tab((0..gdv.ncols()).map(|o| format!("orbit_{o}")));
+        writeln!(buffer, "node_idx\t{orbits}")?;
+    }
+    for (node_idx, row) in gdv.rows().into_iter().enumerate() {
+        writeln!(buffer, "{}\t{}", node_idx + 1, join_tab(row.iter()))?;
+    }
+    Ok(())
+}
+
+/// Write the edge orbit counts to a file or stdout
+///
+/// The matrix is written in the NumPy `.npy` format if the output ends with
+/// `.npy` (without the edges and orbit names), and as a wide table otherwise.
+pub fn write_edge_orbits(
+    result: &EdgeOrbitCounts,
+    output: Option<String>,
+    no_header: bool,
+) -> Result<()> {
+    if let Some(output) = output {
+        let mut buffer = File::create(&output).map(BufWriter::new)?;
+        eprintln!(">> Writing results to      : {}", &output);
+        if output.ends_with(".npy") {
+            write_npy_to_buffer(&mut buffer, result.matrix())
+        } else {
+            write_edge_orbits_to_buffer(&mut buffer, result, no_header)
+        }
+    } else {
+        let mut buffer = BufWriter::new(stdout().lock());
+        write_edge_orbits_to_buffer(&mut buffer, result, no_header)
+    }
+}
+
+fn write_edge_orbits_to_buffer<W: Write>(
+    buffer: &mut BufWriter<W>,
+    result: &EdgeOrbitCounts,
+    no_header: bool,
+) -> Result<()> {
+    if !no_header {
+        let orbits = join_tab(result.orbits().iter().map(|(label, orbit)| {
+            let canon = format_label(&label.words(), label.len(), result.is_directed());
+            format!("{canon}:{orbit}")
+        }));
+        writeln!(buffer, "source\ttarget\t{orbits}")?;
+    }
+    for (&(u, v), row) in result.edges().iter().zip(result.matrix().rows()) {
+        writeln!(buffer, "{}\t{}\t{}", u + 1, v + 1, join_tab(row.iter()))?;
+    }
+    Ok(())
+}
+
+/// Write a matrix in the NumPy `.npy` (version 1.0) format.
+fn write_npy_to_buffer<W: Write>(buffer: &mut BufWriter<W>, matrix: &Array2<u64>) -> Result<()> {
+    let (rows, cols) = matrix.dim();
+    let mut header =
+        format!("{{'descr': '<u8', 'fortran_order': False, 'shape': ({rows}, {cols}), }}");
+    // The magic string, version, and header length take 10 bytes and the
+    // header is padded with spaces and a newline to align the data to 64 bytes
+    let padding = 63 - (10 + header.len()) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+    buffer.write_all(b"\x93NUMPY\x01\x00")?;
+    buffer.write_all(&(header.len() as u16).to_le_bytes())?;
+    buffer.write_all(header.as_bytes())?;
+    for value in matrix.iter() {
+        buffer.write_all(&value.to_le_bytes())?;
+    }
+    buffer.flush()?;
+    Ok(())
+}
+
+/// Joins values with tabs.
+fn join_tab<T: ToString>(values: impl Iterator<Item = T>) -> String {
+    values
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
 /// Write a graph to a file
-pub fn write_graph(graph: &Graph<(), (), Directed>, output: Option<String>) -> Result<()> {
+pub fn write_graph<Ty: EdgeType>(graph: &Graph<(), (), Ty>, output: Option<String>) -> Result<()> {
     if let Some(filepath) = output {
         let mut buffer = File::create(filepath).map(BufWriter::new)?;
         write_graph_to_buffer(&mut buffer, graph)
@@ -273,9 +960,9 @@ pub fn write_graph(graph: &Graph<(), (), Directed>, output: Option<String>) -> R
     }
 }
 
-pub fn write_graph_to_buffer<W: Write>(
+pub fn write_graph_to_buffer<W: Write, Ty: EdgeType>(
     buffer: &mut BufWriter<W>,
-    graph: &Graph<(), (), Directed>,
+    graph: &Graph<(), (), Ty>,
 ) -> Result<()> {
     for edge_idx in graph.edge_indices() {
         let (u, v) = graph.edge_endpoints(edge_idx).unwrap();
@@ -288,7 +975,7 @@ pub fn write_graph_to_buffer<W: Write>(
 mod testing {
     use petgraph::Undirected;
 
-    use crate::enumerate::group_subgraphs;
+    use crate::enumerate::{group_subgraphs, EnumResult};
 
     use super::*;
     use std::io::Cursor;
@@ -336,6 +1023,60 @@ mod testing {
         assert!(graph.contains_edge(8.into(), 2.into()));
     }
 
+    #[test]
+    fn write_stats_graph6() {
+        // A triangle
+        let results = EnrichResult {
+            subgraphs: vec![vec![0b011 << 61, 0b101 << 61, 0b110 << 61]],
+            abundances: vec![2.0],
+            concentrations: vec![1.0],
+            mean_random_abundance: vec![1.0],
+            std_random_abundance: vec![0.5],
+            mean_random_concentration: vec![0.75],
+            std_random_concentration: vec![0.25],
+            zscores: vec![2.0],
+            significance_profile: vec![1.0],
+            log2_fold_changes: vec![0.5],
+            p_over: vec![0.25],
+            p_under: vec![1.0],
+            q_over: vec![0.25],
+            q_under: vec![1.0],
+        };
+        let write = |is_directed| {
+            let mut buffer = BufWriter::new(Vec::new());
+            write_stats_to_buffer(&mut buffer, &results, 3, is_directed).unwrap();
+            String::from_utf8(buffer.into_inner().unwrap()).unwrap()
+        };
+        assert_eq!(
+            write(false).lines().nth(1),
+            Some("Bw\t2\t1\t1\t0.5\t0.75\t0.25\t2\t1\t0.5\t0.25\t1\t0.25\t1")
+        );
+        assert!(write(true).lines().nth(1).unwrap().starts_with('&'));
+    }
+
+    #[test]
+    fn write_counts_header() {
+        // A triangle
+        let label = Label::from(vec![0b011 << 61, 0b101 << 61, 0b110 << 61]);
+        let counts: Counts = [(label, 2)].into_iter().collect();
+        let results = vec![(3, EnumResult::new(counts.clone(), 2))];
+        let write = |with_header| {
+            let mut counts_buffer = BufWriter::new(Vec::new());
+            write_counts_to_buffer(&mut counts_buffer, &counts, 3, false, with_header, None)
+                .unwrap();
+            let mut size_buffer = BufWriter::new(Vec::new());
+            write_size_counts_to_buffer(&mut size_buffer, &results, false, with_header, None)
+                .unwrap();
+            [counts_buffer, size_buffer]
+                .map(|buffer| String::from_utf8(buffer.into_inner().unwrap()).unwrap())
+        };
+        assert_eq!(write(false), ["Bw\t2\n", "3\tBw\t2\n"]);
+        assert_eq!(
+            write(true),
+            ["canon\tcount\nBw\t2\n", "k\tcanon\tcount\n3\tBw\t2\n"]
+        );
+    }
+
     #[test]
     fn read_zero_index() {
         let internal = "0\t1\n1\t2\n2\t0\n";
@@ -382,6 +1123,205 @@ mod testing {
         assert!(!graph.contains_edge(0.into(), 0.into()));
     }
 
+    #[test]
+    fn read_bitgraph() {
+        let filepath = "example/example.txt";
+        let graph = load_numeric_graph::<Directed>(filepath, false).unwrap();
+        let bitgraph = load_numeric_bitgraph::<Directed>(filepath, false).unwrap();
+        assert_eq!(bitgraph.node_count(), graph.node_count());
+        assert_eq!(bitgraph.edge_count(), graph.edge_count());
+        assert!(bitgraph.is_directed);
+        for edge in graph.edge_indices() {
+            let (u, v) = graph.edge_endpoints(edge).unwrap();
+            assert!(bitgraph.is_connected_directed(u.index(), v.index()));
+        }
+    }
+
+    #[test]
+    fn read_bitgraph_with_loops() {
+        let internal = "1\t2\n2\t3\n3\t1\n1\t1";
+        let mut buffer = Cursor::new(internal);
+        let graph = load_numeric_bitgraph_from_buffer::<_, Undirected>(&mut buffer, true).unwrap();
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(graph.edge_count(), 4);
+        assert!(graph.is_connected(0, 0));
+        assert!(!graph.is_directed);
+    }
+
+    #[test]
+    fn read_node_colors() {
+        let internal = "1\t2\n3 1\n\n";
+        let mut buffer = Cursor::new(internal);
+        let colors = load_node_colors_from_buffer(&mut buffer, 4).unwrap();
+        assert_eq!(colors, vec![2, 0, 1, 0]);
+    }
+
+    #[test]
+    fn read_node_colors_invalid() {
+        for internal in ["5\t1", "0\t1", "1\tred", "1"] {
+            let mut buffer = Cursor::new(internal);
+            assert!(load_node_colors_from_buffer(&mut buffer, 4).is_err());
+        }
+    }
+
+    #[test]
+    fn format_colored_label() {
+        let label = [0, 0, 0, 2, 5, 5];
+        assert_eq!(format_label(&label, 3, false), "B?\t2,5,5");
+        assert_eq!(format_label(&label[..3], 3, false), "B?");
+    }
+
+    #[test]
+    fn read_typed_bitgraph() {
+        let internal = "1\t2\t0\n2\t3\t1\n3\t1\t2\n";
+        let mut buffer = Cursor::new(internal);
+        let graph =
+            load_numeric_typed_bitgraph_from_buffer::<_, Directed>(&mut buffer, false).unwrap();
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(graph.edge_color(0, 1), Some(0));
+        assert_eq!(graph.edge_color(1, 2), Some(1));
+        assert_eq!(graph.edge_color(2, 0), Some(2));
+        assert_eq!(graph.edge_color(1, 0), None);
+        assert_eq!(graph.max_edge_color(), Some(2));
+    }
+
+    #[test]
+    fn read_typed_bitgraph_invalid() {
+        for internal in ["1\t2\n", "1\t2\tx\n", "1\t2\t0\n2\t1\t1\n"] {
+            let mut buffer = Cursor::new(internal);
+            assert!(
+                load_numeric_typed_bitgraph_from_buffer::<_, Undirected>(&mut buffer, false)
+                    .is_err()
+            );
+        }
+        // Antiparallel edges may have different types in directed graphs
+        let mut buffer = Cursor::new("1\t2\t0\n2\t1\t1\n");
+        assert!(load_numeric_typed_bitgraph_from_buffer::<_, Directed>(&mut buffer, false).is_ok());
+    }
+
+    #[test]
+    fn format_typed_label() {
+        // A directed path 0 -> 1 -> 2 with edge types 3 and 0
+        let tag = TYPE_ROW_TAG;
+        let label = [
+            1 << 62,
+            1 << 61,
+            0,
+            tag | (4 << EDGE_CODE_BITS),
+            tag | (1 << (2 * EDGE_CODE_BITS)),
+            tag,
+            5,
+            5,
+            6,
+        ];
+        assert_eq!(
+            label_header(Some(&label.to_vec().into()), 3),
+            "canon\tcolors\tedge_types"
+        );
+        assert_eq!(format_label(&label, 3, true), "&BP?\t5,5,6\t3,0");
+        assert_eq!(
+            label_header(Some(&label[..6].into()), 3),
+            "canon\tedge_types"
+        );
+        assert_eq!(format_label(&label[..6], 3, true), "&BP?\t3,0");
+    }
+
+    #[test]
+    fn write_selected_instances() {
+        use std::{cell::RefCell, rc::Rc};
+
+        let filepath = "example/example.txt";
+        let graph = load_numeric_graph::<Undirected>(filepath, false).unwrap();
+        let output = std::env::temp_dir().join("memoesu_write_selected_instances.tsv");
+        let output_str = output.to_str().unwrap().to_string();
+
+        // Only the triangle (`Bw`) is selected
+        let motifs = Some(vec!["Bw".to_string()]);
+        let writer = instance_writer(Some(output_str), false, false, motifs).unwrap();
+        let writer = Rc::new(RefCell::new(Some(writer)));
+        let mut esu = crate::enumerate::Esu::new(3, &graph).unwrap();
+        {
+            let writer = writer.clone();
+            esu.set_instances(move |instance| {
+                writer.borrow_mut().as_mut().unwrap().write(instance)
+            });
+        }
+        esu.enumerate();
+        let written = writer.take().unwrap().finish().unwrap();
+        assert_eq!(written, 1);
+
+        let contents = std::fs::read_to_string(&output).unwrap();
+        let lines = contents.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "nodes\tcanon\tcanonical_nodes");
+        assert_eq!(lines.len(), 2);
+        let fields = lines[1].split('\t').collect::<Vec<_>>();
+        assert_eq!(fields[1], "Bw");
+        let mut nodes = fields[0].split(',').collect::<Vec<_>>();
+        let mut canonical = fields[2].split(',').collect::<Vec<_>>();
+        nodes.sort_unstable();
+        canonical.sort_unstable();
+        assert_eq!(nodes, canonical);
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn write_gdv_table() {
+        let gdv = Array2::from_shape_vec((2, 3), vec![1, 0, 2, 3, 4, 0]).unwrap();
+        let mut buffer = BufWriter::new(Vec::new());
+        write_gdv_to_buffer(&mut buffer, &gdv, false, None).unwrap();
+        let output = String::from_utf8(buffer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            "node_idx\torbit_0\torbit_1\torbit_2\n1\t1\t0\t2\n2\t3\t4\t0\n"
+        );
+    }
+
+    #[test]
+    fn write_edge_orbit_table() {
+        let graph = BitGraph::from_edges(3, &[(0, 1), (1, 2)], false);
+        let result = crate::edge_orbit::edge_orbit_counts(graph, 3, None).unwrap();
+        let mut buffer = BufWriter::new(Vec::new());
+        write_edge_orbits_to_buffer(&mut buffer, &result, false).unwrap();
+        let output = String::from_utf8(buffer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            "source\ttarget\tA_:0\tBW:0\n1\t2\t1\t1\n2\t3\t1\t1\n"
+        );
+    }
+
+    #[test]
+    fn write_gdv_npy() {
+        let gdv = Array2::from_shape_vec((2, 3), vec![1, 0, 2, 3, 4, 0]).unwrap();
+        let mut buffer = BufWriter::new(Vec::new());
+        write_npy_to_buffer(&mut buffer, &gdv).unwrap();
+        let output = buffer.into_inner().unwrap();
+        assert_eq!(&output[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([output[8], output[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&output[10..10 + header_len]).unwrap();
+        assert!(header.contains("'shape': (2, 3)"));
+        assert!(header.ends_with('\n'));
+        let data = &output[10 + header_len..];
+        assert_eq!(data.len(), 6 * 8);
+        assert_eq!(u64::from_le_bytes(data[32..40].try_into().unwrap()), 4);
+    }
+
+    #[test]
+    fn read_missing_column() {
+        let internal = "1\t2\n2\n3\t1\n";
+        let mut buffer = Cursor::new(internal);
+        let graph = load_numeric_graph_from_buffer::<Cursor<&str>, Directed>(&mut buffer, false);
+        assert!(graph.is_err());
+    }
+
+    #[test]
+    fn read_non_numeric() {
+        let internal = "1\t2\n2\tB\n3\t1\n";
+        let mut buffer = Cursor::new(internal);
+        let graph = load_numeric_graph_from_buffer::<Cursor<&str>, Directed>(&mut buffer, false);
+        assert!(graph.is_err());
+    }
+
     #[test]
     fn test_groups_io() {
         // 2 -> 1
@@ -389,11 +1329,21 @@ mod testing {
         // 4 -> 1
         let internal = "2\t1\n3\t1\n4\t1";
         let mut buffer = Cursor::new(internal);
-        let graph = load_numeric_graph_from_buffer::<Cursor<&str>, Directed>(&mut buffer, false).unwrap();
-        let results = group_subgraphs(&graph, 3);
+        let graph =
+            load_numeric_graph_from_buffer::<Cursor<&str>, Directed>(&mut buffer, false).unwrap();
+        let results = group_subgraphs(&graph, 3).unwrap();
         let output = Cursor::new(Vec::new());
         let mut output_buffer = BufWriter::new(output);
-        write_groups_to_buffer(&mut output_buffer, results.groups(), 3, true, true).unwrap();
+        write_groups_to_buffer(
+            &mut output_buffer,
+            results.groups(),
+            3,
+            true,
+            true,
+            None,
+            None,
+        )
+        .unwrap();
         let string_buffer = std::str::from_utf8(output_buffer.buffer()).unwrap();
         println!("{}", string_buffer);
         assert!(string_buffer.contains("4\t&BC_\t1\t1\t2"));
@@ -411,11 +1361,21 @@ mod testing {
         // 4 -> 1
         let internal = "2\t1\n3\t1\n4\t1";
         let mut buffer = Cursor::new(internal);
-        let graph = load_numeric_graph_from_buffer::<Cursor<&str>, Directed>(&mut buffer, false).unwrap();
-        let results = group_subgraphs(&graph, 3);
+        let graph =
+            load_numeric_graph_from_buffer::<Cursor<&str>, Directed>(&mut buffer, false).unwrap();
+        let results = group_subgraphs(&graph, 3).unwrap();
         let output = Cursor::new(Vec::new());
         let mut output_buffer = BufWriter::new(output);
-        write_groups_to_buffer(&mut output_buffer, results.groups(), 3, true, false).unwrap();
+        write_groups_to_buffer(
+            &mut output_buffer,
+            results.groups(),
+            3,
+            true,
+            false,
+            None,
+            None,
+        )
+        .unwrap();
         let string_buffer = std::str::from_utf8(output_buffer.buffer()).unwrap();
         println!("{}", string_buffer);
         assert!(string_buffer.contains("node_idx\tcanon\tlabel\torbit\tabundance\n"));
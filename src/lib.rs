@@ -0,0 +1,51 @@
+//! # memoesu
+//!
+//! Fast subgraph enumeration on graphs using a memoized parallel ESU algorithm.
+//!
+//! This crate exposes the same machinery used by the `memoesu` binary:
+//!
+//! * [`enumerate`] - serial ([`Esu`]) and parallel ([`ParEsu`]) subgraph enumeration,
+//!   RAND-ESU sampling ([`Sampling`]), as well as node-level subgraph membership and
+//!   orbit grouping. The enumerators are generic over the [`Canonicalizer`] backend
+//!   (nauty, Traces, or the pure Rust [`Exhaustive`] search).
+//! * [`enrichment`] - motif enrichment against degree-preserving random graphs.
+//! * [`gdv`] - graphlet degree vectors over the orbits of the graphlets with up to 5 nodes.
+//! * [`edge_orbit`] - edge-level counts of the automorphism orbits of edges within each subgraph.
+//! * [`switching`] - degree-preserving random graph generation using the switch model.
+//! * [`io`] - loaders for numeric edgelists and writers for the result tables.
+//! * [`merge`] - summing the result tables of runs split into shards of the roots.
+//!
+//! All fallible entry points return [`anyhow::Result`] rather than panicking.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use memoesu::{enumerate_subgraphs, io::load_numeric_graph};
+//! use petgraph::Directed;
+//!
+//! let graph = load_numeric_graph::<Directed>("example/example.txt", false)?;
+//! let results = enumerate_subgraphs(&graph, 3)?;
+//! println!("found {} subgraphs", results.total_subgraphs());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod edge_orbit;
+pub mod enrichment;
+pub mod enumerate;
+pub mod gdv;
+pub mod io;
+pub mod merge;
+pub mod switching;
+
+pub use enrichment::{enrichment, EnrichResult};
+pub use enumerate::{
+    enumerate_subgraph_sizes, enumerate_subgraphs, group_subgraphs, non_induced_counts,
+    parallel_enumerate_subgraph_sizes, parallel_enumerate_subgraphs, parallel_group_subgraphs,
+    BitGraph, Canonicalizer, Checkpoint, Color, Counts, DefaultCanonicalizer, EdgeColor,
+    EnumResult, Estimate, Estimates, Esu, Exhaustive, GroupInfo, GroupResult, Groups,
+    InclusionMatrix, Instance, Label, MemoCache, NautyGraph, Neighbors, NodeLabel, Orbit, ParEsu,
+    Progress, Query, Representation, Sampling, Shard, SizeResults,
+};
+#[cfg(feature = "nauty")]
+pub use enumerate::{DenseNauty, SparseNauty, Traces};
+pub use switching::switching;
@@ -1,36 +1,357 @@
 mod cli;
-mod enrichment;
-mod enumerate;
-mod io;
-mod switching;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use clap::Parser;
-use cli::Cli;
-use enrichment::enrichment;
-use enumerate::{enumerate_subgraphs, parallel_enumerate_subgraphs};
-use io::FormatGraph;
+use cli::{Backend, Cli};
+use memoesu::{
+    edge_orbit, enrichment, gdv,
+    io::{self, FormatGraph},
+    merge::{self, ShardHeader, ShardOutput},
+    non_induced_counts, switching, BitGraph, Canonicalizer, Checkpoint, Color,
+    DefaultCanonicalizer, EnumResult, Esu, Exhaustive, GroupResult, Instance, MemoCache, ParEsu,
+    Progress, Query, Sampling, Shard, SizeResults,
+};
+#[cfg(feature = "nauty")]
+use memoesu::{DenseNauty, SparseNauty, Traces};
 use petgraph::{Directed, EdgeType, Undirected};
+use rayon::ThreadPool;
+use std::{
+    collections::HashSet,
+    io::Write,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use crate::enumerate::group_subgraphs;
+/// Build the RAND-ESU sampling parameters if sampling probabilities were provided.
+fn build_sampling(sample_probs: Option<Vec<f64>>, seed: Option<usize>) -> Result<Option<Sampling>> {
+    sample_probs
+        .map(|probs| Sampling::new(probs, seed.unwrap_or_else(rand::random) as u64))
+        .transpose()
+}
+
+/// Load the graph, including the edge types if the edges are typed.
+fn load_graph<Ty: EdgeType>(
+    filepath: &str,
+    include_loops: bool,
+    edge_types: bool,
+) -> Result<BitGraph> {
+    if edge_types {
+        io::load_numeric_typed_bitgraph::<Ty>(filepath, include_loops)
+    } else {
+        io::load_numeric_bitgraph::<Ty>(filepath, include_loops)
+    }
+}
+
+/// Load the node colors if a color file was provided.
+fn load_colors(filepath: Option<String>, num_nodes: usize) -> Result<Option<Vec<Color>>> {
+    let Some(filepath) = filepath else {
+        return Ok(None);
+    };
+    let colors = io::load_node_colors(&filepath, num_nodes)?;
+    let num_colors = colors.iter().collect::<HashSet<_>>().len();
+    eprintln!(">> Number of node colors   : {num_colors}");
+    Ok(Some(colors))
+}
+
+/// Log the RAND-ESU sampling parameters.
+fn log_sampling(sampling: &Sampling) {
+    let probs = sampling
+        .probs()
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    eprintln!(">> Sampling probabilities  : {probs}");
+    eprintln!(">> Using random seed       : {}", sampling.seed());
+}
+
+/// Build a thread pool for the enumeration if more than one thread is requested.
+///
+/// The pool is scoped to the enumeration rather than installed globally.
+fn thread_pool(num_threads: Option<usize>) -> Result<Option<Arc<ThreadPool>>> {
+    match num_threads {
+        Some(1) | None => Ok(None),
+        Some(num_threads) => {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()?;
+            Ok(Some(Arc::new(pool)))
+        }
+    }
+}
+
+/// Where to periodically write checkpoints and which checkpoint to resume from.
+#[derive(Default)]
+struct Checkpointing {
+    path: Option<(String, Duration)>,
+    resume: Option<Checkpoint>,
+}
+impl Checkpointing {
+    /// Load the checkpoint to resume from, which is also updated unless
+    /// another checkpoint file is given.
+    fn new(path: Option<String>, interval: f64, resume: Option<String>) -> Result<Self> {
+        let interval = Duration::try_from_secs_f64(interval)?;
+        let checkpoint = resume
+            .as_ref()
+            .map(|resume| {
+                let checkpoint = Checkpoint::load(resume)?;
+                eprintln!(">> Resuming from checkpoint: {resume}");
+                eprintln!(">> Finished roots          : {}", checkpoint.roots_done());
+                Ok::<_, anyhow::Error>(checkpoint)
+            })
+            .transpose()?;
+        Ok(Self {
+            path: path.or(resume).map(|path| (path, interval)),
+            resume: checkpoint,
+        })
+    }
+}
+
+/// Load the memo cache if a path is given, starting from an empty cache if
+/// the file does not exist yet.
+fn load_memo_cache(path: Option<&str>) -> Result<Option<MemoCache>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let cache = if std::path::Path::new(path).exists() {
+        MemoCache::load(path)?
+    } else {
+        MemoCache::default()
+    };
+    eprintln!(">> Memo cache entries      : {}", cache.len());
+    Ok(Some(cache))
+}
+
+/// Write the memo cache if a path is given.
+fn save_memo_cache(path: Option<&str>, cache: Option<MemoCache>) -> Result<()> {
+    if let (Some(path), Some(cache)) = (path, cache) {
+        eprintln!(
+            ">> Writing memo cache to   : {path} ({} entries)",
+            cache.len()
+        );
+        cache.save(path)?;
+    }
+    Ok(())
+}
+
+/// Log the shard of the roots if the run is sharded.
+fn log_shard(shard: Option<Shard>) {
+    if let Some(shard) = shard {
+        eprintln!(">> Shard                   : {shard}");
+    }
+}
+
+/// Log the progress of an enumeration.
+fn log_progress(progress: &Progress) {
+    eprintln!(">> Progress                : {progress}");
+}
+
+/// Log the fraction of the enumeration each thread spent exploring the ESU tree.
+fn log_utilization(utilization: &[f64]) {
+    for (thread, fraction) in utilization.iter().enumerate() {
+        eprintln!(
+            ">> Thread {thread:<3} utilization : {:.1}%",
+            fraction * 100.0
+        );
+    }
+}
+
+/// The configuration of an enumeration shared by the subcommands.
+#[derive(Default)]
+struct Settings {
+    min_size: Option<usize>,
+    query: Option<Query>,
+    colors: Option<Vec<Color>>,
+    sampling: Option<Sampling>,
+    progress: Option<Duration>,
+    shard: Option<Shard>,
+    checkpoint: Checkpointing,
+    memo: Option<MemoCache>,
+}
+
+/// The serial enumerator, or the parallel enumerator if more than one thread
+/// is requested.
+enum Enumerator<Ty: EdgeType + Sync, C: Canonicalizer = DefaultCanonicalizer> {
+    Serial(Box<Esu<Ty, C>>),
+    Parallel(Box<ParEsu<Ty, C>>),
+}
+
+/// Run the same code on either enumerator.
+macro_rules! on_enumerator {
+    ($enumerator:expr, $esu:ident => $body:expr) => {
+        match $enumerator {
+            Enumerator::Serial($esu) => $body,
+            Enumerator::Parallel($esu) => $body,
+        }
+    };
+}
+
+impl<Ty: EdgeType + Sync, C: Canonicalizer> Enumerator<Ty, C> {
+    /// Create the enumerator of the graph and apply the settings.
+    ///
+    /// The settings are applied in the order the enumerators require (e.g.
+    /// the node colors before resuming from a checkpoint).
+    fn new(
+        subgraph_size: usize,
+        graph: BitGraph,
+        num_threads: Option<usize>,
+        settings: Settings,
+    ) -> Result<Self> {
+        let mut enumerator = match thread_pool(num_threads)? {
+            None => Self::Serial(Box::new(Esu::with_canonicalizer(subgraph_size, graph)?)),
+            Some(pool) => {
+                // Run the enumeration in parallel on the thread pool.
+                let mut esu = ParEsu::with_canonicalizer(subgraph_size, graph)?;
+                esu.set_thread_pool(pool);
+                Self::Parallel(Box::new(esu))
+            }
+        };
+        on_enumerator!(&mut enumerator, esu => {
+            if let Some(min_size) = settings.min_size {
+                esu.set_min_size(min_size)?;
+            }
+            if let Some(query) = settings.query {
+                esu.set_query(query)?;
+            }
+            if let Some(colors) = settings.colors {
+                esu.set_colors(colors)?;
+            }
+            if let Some(sampling) = settings.sampling {
+                esu.set_sampling(sampling)?;
+            }
+            if let Some(interval) = settings.progress {
+                esu.set_progress(interval, log_progress);
+            }
+            if let Some(shard) = settings.shard {
+                esu.set_shard(shard);
+            }
+            if let Some((path, interval)) = settings.checkpoint.path {
+                esu.set_checkpoint(path, interval)?;
+            }
+            if let Some(resume) = settings.checkpoint.resume {
+                esu.resume(resume)?;
+            }
+            if let Some(memo) = settings.memo {
+                esu.set_memo_cache(memo)?;
+            }
+        });
+        Ok(enumerator)
+    }
+
+    fn set_instances<F: Fn(&Instance) + Send + Sync + 'static>(&mut self, callback: F) {
+        on_enumerator!(self, esu => esu.set_instances(callback))
+    }
+
+    fn enumerate(&mut self) {
+        on_enumerator!(self, esu => esu.enumerate())
+    }
+
+    fn identify_groups(&mut self) {
+        on_enumerator!(self, esu => esu.identify_groups())
+    }
+
+    /// Log the utilization of the threads of a parallel enumeration.
+    fn log_utilization(&self) {
+        if let Self::Parallel(esu) = self {
+            log_utilization(&esu.thread_utilization());
+        }
+    }
+
+    fn memo_cache(&self) -> MemoCache {
+        on_enumerator!(self, esu => esu.memo_cache())
+    }
+
+    fn result(self) -> EnumResult {
+        on_enumerator!(self, esu => esu.result())
+    }
+
+    fn size_results(self) -> SizeResults {
+        on_enumerator!(self, esu => esu.size_results())
+    }
+
+    fn group_results(self) -> GroupResult {
+        on_enumerator!(self, esu => esu.group_results())
+    }
+}
+
+/// The instance writer shared by the threads of an enumeration.
+type SharedWriter = Arc<Mutex<io::InstanceWriter<Box<dyn Write + Send>>>>;
+
+/// A callback writing every instance to the shared writer.
+///
+/// Once a panic while writing poisoned the writer the instances are skipped,
+/// and the error is returned by [`finish_instances`].
+fn instance_sink(writer: &SharedWriter) -> impl Fn(&Instance) + Send + Sync + 'static {
+    let writer = writer.clone();
+    move |instance: &Instance| {
+        if let Ok(mut writer) = writer.lock() {
+            writer.write(instance);
+        }
+    }
+}
+
+/// Finish writing the instances and return their number.
+///
+/// The enumerator (and its copies of the writer) must be dropped before.
+fn finish_instances(writer: SharedWriter) -> Result<usize> {
+    let writer = Arc::into_inner(writer)
+        .ok_or_else(|| anyhow!("ERROR: The instance writer is still shared."))?
+        .into_inner()
+        .map_err(|_| anyhow!("ERROR: Writing an instance panicked."))?;
+    writer.finish()
+}
+
+/// Count the subgraphs of every size with the canonicalizer `C`, and return
+/// the memo cache of the enumeration if `save_memo` is set.
+fn count_subgraphs<Ty: EdgeType + Sync, C: Canonicalizer>(
+    subgraph_size: usize,
+    graph: BitGraph,
+    num_threads: Option<usize>,
+    settings: Settings,
+    save_memo: bool,
+) -> Result<(SizeResults, Option<MemoCache>)> {
+    let mut esu = Enumerator::<Ty, C>::new(subgraph_size, graph, num_threads, settings)?;
+    esu.enumerate();
+    esu.log_utilization();
+    let memo = save_memo.then(|| esu.memo_cache());
+    Ok((esu.size_results(), memo))
+}
 
 /// Enumerate the subgraphs of a given size in a graph.
+#[allow(clippy::too_many_arguments)]
 fn submodule_enumerate<Ty: EdgeType + Sync>(
     filepath: &str,
     subgraph_size: usize,
+    min_size: Option<usize>,
+    non_induced: bool,
     output: Option<String>,
+    colors: Option<String>,
+    edge_types: bool,
     num_threads: Option<usize>,
     include_loops: bool,
     is_directed: bool,
+    with_header: bool,
+    sample_probs: Option<Vec<f64>>,
+    seed: Option<usize>,
+    progress: Option<f64>,
+    checkpoint: Checkpointing,
+    shard: Option<Shard>,
+    memo_cache: Option<String>,
+    canonicalizer: Option<Backend>,
 ) -> Result<()> {
     // Load the graph.
-    let graph = io::load_numeric_graph::<Ty>(filepath, include_loops)?;
+    let graph = load_graph::<Ty>(filepath, include_loops, edge_types)?;
+    let (nodes, edges) = (graph.node_count(), graph.edge_count());
 
     eprintln!("----------------------------------------");
     eprintln!("Log");
     eprintln!("----------------------------------------");
     eprintln!(">> Number of nodes         : {}", graph.node_count());
     eprintln!(">> Number of edges         : {}", graph.edge_count());
+    eprintln!(">> Graph representation    : {:?}", graph.representation());
+    if let Some(max_edge_color) = graph.max_edge_color() {
+        eprintln!(">> Largest edge type       : {max_edge_color}");
+    }
     eprintln!(">> Including loops         : {include_loops}");
     eprintln!(
         ">> Graph edge type         : {}",
@@ -41,54 +362,266 @@ fn submodule_enumerate<Ty: EdgeType + Sync>(
         }
     );
 
+    if let Some(canonicalizer) = canonicalizer {
+        eprintln!(">> Canonicalizer           : {canonicalizer:?}");
+    }
+    log_shard(shard);
+
+    let colors = load_colors(colors, graph.node_count())?;
+    let sampling = build_sampling(sample_probs, seed)?;
+    if let Some(sampling) = &sampling {
+        log_sampling(sampling);
+    }
+    let is_sampled = sampling.is_some();
+    if non_induced && (is_sampled || colors.is_some() || graph.is_typed() || include_loops) {
+        bail!(
+            "ERROR: Non-induced counts are not supported for sampling, node colors, edge types, or loops."
+        );
+    }
+    if non_induced && canonicalizer.is_some_and(|backend| !backend.is_default()) {
+        bail!("ERROR: Non-induced counts are only supported with the default canonicalizer.");
+    }
+    let memo = load_memo_cache(memo_cache.as_deref())?;
+
     // Enumerate the subgraphs.
     let now = std::time::Instant::now();
 
-    let results = match num_threads {
-        Some(1) | None => enumerate_subgraphs(&graph, subgraph_size),
-        Some(num_threads) => {
-            // Build a thread pool and use it to enumerate the subgraphs.
-            rayon::ThreadPoolBuilder::new()
-                .num_threads(num_threads)
-                .build_global()?;
+    let settings = Settings {
+        min_size,
+        colors,
+        sampling,
+        progress: progress.map(Duration::try_from_secs_f64).transpose()?,
+        shard,
+        checkpoint,
+        memo,
+        ..Default::default()
+    };
 
-            // Run the enumeration in parallel.
-            parallel_enumerate_subgraphs(&graph, subgraph_size)
+    // The enumeration is generic over the canonicalizer, so the backend only
+    // chooses its type.
+    macro_rules! enumerate_with {
+        ($backend:ty) => {
+            count_subgraphs::<Ty, $backend>(
+                subgraph_size,
+                graph,
+                num_threads,
+                settings,
+                memo_cache.is_some(),
+            )?
+        };
+    }
+    let (results, memo) = match canonicalizer {
+        None => enumerate_with!(DefaultCanonicalizer),
+        #[cfg(feature = "nauty")]
+        Some(Backend::DenseNauty) => enumerate_with!(DenseNauty),
+        #[cfg(feature = "nauty")]
+        Some(Backend::SparseNauty) => enumerate_with!(SparseNauty),
+        #[cfg(feature = "nauty")]
+        Some(Backend::Traces) => enumerate_with!(Traces),
+        Some(Backend::Exhaustive) => enumerate_with!(Exhaustive),
+    };
+    save_memo_cache(memo_cache.as_deref(), memo)?;
+
+    for (k, result) in results.iter() {
+        if min_size.is_some() {
+            eprintln!(">> Subgraph size           : {k}");
+        }
+        eprintln!(">> Total subgraphs         : {}", result.total_subgraphs());
+        eprintln!(">> Unique subgraphs        : {}", result.unique_subgraphs());
+        if let Some(estimates) = result.estimates() {
+            let (lower, upper) = estimates.total().confidence_interval();
+            eprintln!(
+                ">> Estimated subgraphs     : {:.1} (95% CI: {lower:.1} - {upper:.1})",
+                estimates.total().value
+            );
+        }
+    }
+    // eprintln!(">> Duplicate calculations  : {}", results.num_duplicates());
+    eprintln!(">> Finished enumeration in : {:?}", now.elapsed());
+
+    // Derive the non-induced counts from the induced counts of each size.
+    let results = if non_induced {
+        let now = std::time::Instant::now();
+        let results = results
+            .into_iter()
+            .map(|(k, result)| {
+                let counts = non_induced_counts(result.counts(), k, is_directed)?;
+                Ok((k, EnumResult::new(counts, result.total_subgraphs())))
+            })
+            .collect::<Result<SizeResults>>()?;
+        eprintln!(">> Finished non-induced in : {:?}", now.elapsed());
+        results
+    } else {
+        results
+    };
+    eprintln!("----------------------------------------");
+
+    // Write the results to the output file.
+    let header = shard.map(|shard| ShardHeader {
+        output: if min_size.is_some() {
+            ShardOutput::SizeCounts
+        } else {
+            ShardOutput::Counts
+        },
+        shard,
+        min_size: min_size.unwrap_or(subgraph_size),
+        k: subgraph_size,
+        is_directed,
+        non_induced,
+        nodes,
+        edges,
+    });
+    // Only sampled counts have a header by default, as they add columns
+    let with_header = with_header || is_sampled;
+    if min_size.is_some() {
+        io::write_size_counts(&results, output, is_directed, with_header, header.as_ref())?;
+    } else if let Some((_, result)) = results.first() {
+        if let Some(estimates) = result.estimates() {
+            io::write_sampled_counts(
+                result.counts(),
+                estimates,
+                subgraph_size,
+                output,
+                is_directed,
+            )?;
+        } else {
+            io::write_counts(
+                result.counts(),
+                subgraph_size,
+                output,
+                is_directed,
+                with_header,
+                header.as_ref(),
+            )?;
         }
+    }
+
+    Ok(())
+}
+
+/// Enumerate the subgraphs of a given size in a graph.
+#[allow(clippy::too_many_arguments)]
+fn submodule_groups<Ty: EdgeType + Sync>(
+    filepath: &str,
+    subgraph_size: usize,
+    output: Option<String>,
+    colors: Option<String>,
+    edge_types: bool,
+    num_threads: Option<usize>,
+    include_loops: bool,
+    is_directed: bool,
+    no_header: bool,
+    sample_probs: Option<Vec<f64>>,
+    seed: Option<usize>,
+    shard: Option<Shard>,
+    memo_cache: Option<String>,
+) -> Result<()> {
+    // Load the graph.
+    let graph = load_graph::<Ty>(filepath, include_loops, edge_types)?;
+    let (nodes, edges) = (graph.node_count(), graph.edge_count());
+
+    eprintln!("----------------------------------------");
+    eprintln!("Log");
+    eprintln!("----------------------------------------");
+    eprintln!(">> Number of nodes         : {}", graph.node_count());
+    eprintln!(">> Number of edges         : {}", graph.edge_count());
+    eprintln!(">> Graph representation    : {:?}", graph.representation());
+    if let Some(max_edge_color) = graph.max_edge_color() {
+        eprintln!(">> Largest edge type       : {max_edge_color}");
+    }
+    eprintln!(">> Including loops         : {include_loops}");
+    eprintln!(
+        ">> Graph edge type         : {}",
+        if is_directed {
+            "directed"
+        } else {
+            "undirected"
+        }
+    );
+
+    log_shard(shard);
+
+    let colors = load_colors(colors, graph.node_count())?;
+    let sampling = build_sampling(sample_probs, seed)?;
+    if let Some(sampling) = &sampling {
+        log_sampling(sampling);
+    }
+
+    let memo = load_memo_cache(memo_cache.as_deref())?;
+
+    // Enumerate the subgraphs.
+    let now = std::time::Instant::now();
+    let settings = Settings {
+        colors,
+        sampling,
+        shard,
+        memo,
+        ..Default::default()
     };
+    let mut esu = Enumerator::<Ty>::new(subgraph_size, graph, num_threads, settings)?;
+    esu.identify_groups();
+    let memo = memo_cache.is_some().then(|| esu.memo_cache());
+    let results = esu.group_results();
 
+    save_memo_cache(memo_cache.as_deref(), memo)?;
     eprintln!(">> Total subgraphs         : {}", results.total_subgraphs());
     eprintln!(
         ">> Unique subgraphs        : {}",
         results.unique_subgraphs()
     );
-    // eprintln!(">> Duplicate calculations  : {}", results.num_duplicates());
     eprintln!(">> Finished enumeration in : {:?}", now.elapsed());
     eprintln!("----------------------------------------");
 
     // Write the results to the output file.
-    io::write_counts(results.counts(), subgraph_size, output, is_directed)?;
+    let header = shard.map(|shard| ShardHeader {
+        output: ShardOutput::Groups,
+        shard,
+        min_size: subgraph_size,
+        k: subgraph_size,
+        is_directed,
+        non_induced: false,
+        nodes,
+        edges,
+    });
+    io::write_groups(
+        results.groups(),
+        subgraph_size,
+        output,
+        is_directed,
+        no_header,
+        results.estimates().map(|e| e.inclusion_probability()),
+        header.as_ref(),
+    )?;
 
     Ok(())
 }
 
-/// Enumerate the subgraphs of a given size in a graph.
-fn submodule_groups<Ty: EdgeType + Sync>(
+/// Stream every subgraph occurrence of a given size in a graph.
+#[allow(clippy::too_many_arguments)]
+fn submodule_instances<Ty: EdgeType + Sync>(
     filepath: &str,
     subgraph_size: usize,
     output: Option<String>,
+    motifs: Option<Vec<String>>,
+    colors: Option<String>,
+    edge_types: bool,
+    num_threads: Option<usize>,
     include_loops: bool,
     is_directed: bool,
     no_header: bool,
 ) -> Result<()> {
     // Load the graph.
-    let graph = io::load_numeric_graph::<Ty>(filepath, include_loops)?;
+    let graph = load_graph::<Ty>(filepath, include_loops, edge_types)?;
 
     eprintln!("----------------------------------------");
     eprintln!("Log");
     eprintln!("----------------------------------------");
     eprintln!(">> Number of nodes         : {}", graph.node_count());
     eprintln!(">> Number of edges         : {}", graph.edge_count());
+    eprintln!(">> Graph representation    : {:?}", graph.representation());
+    if let Some(max_edge_color) = graph.max_edge_color() {
+        eprintln!(">> Largest edge type       : {max_edge_color}");
+    }
     eprintln!(">> Including loops         : {include_loops}");
     eprintln!(
         ">> Graph edge type         : {}",
@@ -98,21 +631,251 @@ fn submodule_groups<Ty: EdgeType + Sync>(
             "undirected"
         }
     );
+    if let Some(motifs) = &motifs {
+        eprintln!(">> Selected motifs         : {}", motifs.join(","));
+    }
+
+    let colors = load_colors(colors, graph.node_count())?;
+
+    // The instances are written while the subgraphs are enumerated.
+    let writer = io::instance_writer(output, is_directed, no_header, motifs)?;
+    let writer = Arc::new(Mutex::new(writer));
+    let sink = instance_sink(&writer);
 
     // Enumerate the subgraphs.
     let now = std::time::Instant::now();
-    let results = group_subgraphs(&graph, subgraph_size);
+    let settings = Settings {
+        colors,
+        ..Default::default()
+    };
+    let mut esu = Enumerator::<Ty>::new(subgraph_size, graph, num_threads, settings)?;
+    esu.set_instances(sink);
+    esu.enumerate();
+    let results = esu.result();
+
+    // The enumerators (and their copies of the writer) are dropped with the results.
+    let written = finish_instances(writer)?;
 
     eprintln!(">> Total subgraphs         : {}", results.total_subgraphs());
     eprintln!(
         ">> Unique subgraphs        : {}",
         results.unique_subgraphs()
     );
+    eprintln!(">> Written instances       : {written}");
+    eprintln!(">> Finished enumeration in : {:?}", now.elapsed());
+    eprintln!("----------------------------------------");
+
+    Ok(())
+}
+
+/// Count (and optionally locate) only the target motifs in a graph.
+fn submodule_query<Ty: EdgeType + Sync>(
+    filepath: &str,
+    motifs: Vec<String>,
+    output: Option<String>,
+    instances: Option<String>,
+    num_threads: Option<usize>,
+    include_loops: bool,
+    is_directed: bool,
+) -> Result<()> {
+    // Load the graph and the target motifs.
+    let graph = io::load_numeric_bitgraph::<Ty>(filepath, include_loops)?;
+    let query = Query::from_graph6(&motifs, is_directed)?;
+    let subgraph_size = query.max_size();
+
+    eprintln!("----------------------------------------");
+    eprintln!("Log");
+    eprintln!("----------------------------------------");
+    eprintln!(">> Number of nodes         : {}", graph.node_count());
+    eprintln!(">> Number of edges         : {}", graph.edge_count());
+    eprintln!(">> Graph representation    : {:?}", graph.representation());
+    eprintln!(">> Including loops         : {include_loops}");
+    eprintln!(
+        ">> Graph edge type         : {}",
+        if is_directed {
+            "directed"
+        } else {
+            "undirected"
+        }
+    );
+    eprintln!(">> Target motifs           : {}", motifs.join(","));
+
+    // The instances of the targets are written while the subgraphs are enumerated.
+    let writer = instances
+        .map(|instances| io::instance_writer(Some(instances), is_directed, false, None))
+        .transpose()?
+        .map(|writer| Arc::new(Mutex::new(writer)));
+    let sink = writer.as_ref().map(instance_sink);
+
+    // Enumerate the subgraphs.
+    let now = std::time::Instant::now();
+    let settings = Settings {
+        query: Some(query.clone()),
+        ..Default::default()
+    };
+    let mut esu = Enumerator::<Ty>::new(subgraph_size, graph, num_threads, settings)?;
+    if let Some(sink) = sink {
+        esu.set_instances(sink);
+    }
+    esu.enumerate();
+    let results = esu.size_results();
+
+    // The enumerators (and their copies of the writer) are dropped with the results.
+    if let Some(writer) = writer {
+        let written = finish_instances(writer)?;
+        eprintln!(">> Written instances       : {written}");
+    }
+
+    // Look up the count of every target in the results of its size.
+    let counts = query
+        .labels()
+        .zip(query.sizes())
+        .map(|(label, k)| {
+            results
+                .iter()
+                .find(|(size, _)| *size == k)
+                .and_then(|(_, result)| result.counts().get(label))
+                .copied()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+    let total = results
+        .iter()
+        .map(|(_, r)| r.total_subgraphs())
+        .sum::<usize>();
+    eprintln!(">> Total target subgraphs  : {total}");
+    eprintln!(">> Finished enumeration in : {:?}", now.elapsed());
+    eprintln!("----------------------------------------");
+
+    // Write the results to the output file.
+    io::write_query_counts(&motifs, &counts, output)?;
+
+    Ok(())
+}
+
+/// Count the edge orbits of all edges of a graph.
+fn submodule_edge_orbits<Ty: EdgeType>(
+    filepath: &str,
+    subgraph_size: usize,
+    output: Option<String>,
+    num_threads: Option<usize>,
+    include_loops: bool,
+    no_header: bool,
+) -> Result<()> {
+    // Load the graph.
+    let graph = io::load_numeric_bitgraph::<Ty>(filepath, include_loops)?;
+
+    eprintln!("----------------------------------------");
+    eprintln!("Log");
+    eprintln!("----------------------------------------");
+    eprintln!(">> Number of nodes         : {}", graph.node_count());
+    eprintln!(">> Number of edges         : {}", graph.edge_count());
+    eprintln!(">> Graph representation    : {:?}", graph.representation());
+    eprintln!(">> Including loops         : {include_loops}");
+
+    // Count the edge orbits of every edge.
+    let now = std::time::Instant::now();
+    let pool = thread_pool(num_threads)?;
+    let result = edge_orbit::edge_orbit_counts(graph, subgraph_size, pool)?;
+    eprintln!(">> Number of edge orbits   : {}", result.orbits().len());
+    eprintln!(">> Finished enumeration in : {:?}", now.elapsed());
+    eprintln!("----------------------------------------");
+
+    // Write the results to the output file.
+    io::write_edge_orbits(&result, output, no_header)?;
+
+    Ok(())
+}
+
+/// Count the graphlet degree vectors of all nodes of an undirected graph.
+fn submodule_gdv(
+    filepath: &str,
+    subgraph_size: usize,
+    output: Option<String>,
+    num_threads: Option<usize>,
+    no_header: bool,
+    shard: Option<Shard>,
+) -> Result<()> {
+    // Load the graph (graphlets have no loops).
+    let graph = io::load_numeric_bitgraph::<Undirected>(filepath, false)?;
+    let (nodes, edges) = (graph.node_count(), graph.edge_count());
+
+    eprintln!("----------------------------------------");
+    eprintln!("Log");
+    eprintln!("----------------------------------------");
+    eprintln!(">> Number of nodes         : {}", graph.node_count());
+    eprintln!(">> Number of edges         : {}", graph.edge_count());
+    eprintln!(">> Graph representation    : {:?}", graph.representation());
+    eprintln!(
+        ">> Number of orbits        : {}",
+        gdv::num_orbits(subgraph_size)
+    );
+    log_shard(shard);
+    if shard.is_some() && output.as_deref().is_some_and(|o| o.ends_with(".npy")) {
+        bail!("ERROR: The output of a sharded run can not be written as a NumPy matrix.");
+    }
+
+    // Count the orbits of every node.
+    let now = std::time::Instant::now();
+    let pool = thread_pool(num_threads)?;
+    let gdv = gdv::graphlet_degree_vectors(graph, subgraph_size, shard, pool)?;
     eprintln!(">> Finished enumeration in : {:?}", now.elapsed());
     eprintln!("----------------------------------------");
 
     // Write the results to the output file.
-    io::write_groups(results.groups(), subgraph_size, output, is_directed, no_header)?;
+    let header = shard.map(|shard| ShardHeader {
+        output: ShardOutput::Gdv,
+        shard,
+        min_size: 2,
+        k: subgraph_size,
+        is_directed: false,
+        non_induced: false,
+        nodes,
+        edges,
+    });
+    io::write_gdv(&gdv, output, no_header, header.as_ref())?;
+
+    Ok(())
+}
+
+/// Sum the outputs of all shards of a run.
+fn submodule_merge(inputs: &[String], output: Option<String>) -> Result<()> {
+    let now = std::time::Instant::now();
+    let merged = merge::merge_shards(inputs)?;
+    let header = merged.header();
+
+    eprintln!("----------------------------------------");
+    eprintln!("Log");
+    eprintln!("----------------------------------------");
+    eprintln!(">> Number of shards        : {}", header.shard.count());
+    eprintln!(">> Number of nodes         : {}", header.nodes);
+    eprintln!(">> Number of edges         : {}", header.edges);
+    eprintln!(
+        ">> Graph edge type         : {}",
+        if header.is_directed {
+            "directed"
+        } else {
+            "undirected"
+        }
+    );
+    if header.min_size != header.k {
+        eprintln!(">> Minimum subgraph size   : {}", header.min_size);
+    }
+    eprintln!(">> Subgraph size           : {}", header.k);
+    match header.output {
+        ShardOutput::Counts | ShardOutput::SizeCounts => {
+            eprintln!(">> Total subgraphs         : {}", merged.total());
+            eprintln!(">> Unique subgraphs        : {}", merged.len());
+        }
+        ShardOutput::Groups | ShardOutput::Gdv => {
+            eprintln!(">> Merged rows             : {}", merged.len());
+        }
+    }
+    eprintln!(">> Finished merging in     : {:?}", now.elapsed());
+    eprintln!("----------------------------------------");
+
+    // Write the results to the output file.
+    merged.write(output)?;
 
     Ok(())
 }
@@ -140,14 +903,14 @@ fn submodule_format(input: &str, prefix: &str, filter_loops: bool) -> Result<()>
     Ok(())
 }
 
-fn submodule_switch(
+fn submodule_switch<Ty: EdgeType>(
     filepath: &str,
     output: Option<String>,
     q: usize,
     seed: Option<usize>,
 ) -> Result<()> {
     // Load the graph.
-    let graph = io::load_numeric_graph(filepath, false)?;
+    let graph = io::load_numeric_graph::<Ty>(filepath, false)?;
 
     // Set the seed if not provided
     let seed = seed.unwrap_or_else(rand::random);
@@ -161,7 +924,7 @@ fn submodule_switch(
 
     // Switch the graph.
     let now = std::time::Instant::now();
-    let switched_graph = switching::switching(&graph, q, seed);
+    let switched_graph = switching(&graph, q, seed)?;
     eprintln!(">> Finished switching in   : {:?}", now.elapsed());
 
     // Validate the switched graph.
@@ -174,18 +937,34 @@ fn submodule_switch(
     Ok(())
 }
 
-fn submodule_enrichment(
+#[allow(clippy::too_many_arguments)]
+fn submodule_enrichment<Ty: EdgeType + Sync>(
     filepath: &str,
     subgraph_size: usize,
     output: Option<String>,
-    _num_threads: Option<usize>,
+    num_threads: Option<usize>,
     random_graphs: usize,
     q: usize,
     seed: Option<usize>,
+    sample_probs: Option<Vec<f64>>,
+    parallel_enumeration: bool,
+    memo_cache: Option<String>,
 ) -> Result<()> {
-    let graph = io::load_numeric_graph(filepath, false)?;
-    let results = enrichment(&graph, subgraph_size, random_graphs, q, seed);
-    io::write_stats(&results, subgraph_size, output)?;
+    let graph = io::load_numeric_graph::<Ty>(filepath, false)?;
+    let mut memo = load_memo_cache(memo_cache.as_deref())?;
+    let results = enrichment(
+        &graph,
+        subgraph_size,
+        random_graphs,
+        q,
+        seed,
+        sample_probs.as_deref(),
+        thread_pool(num_threads)?,
+        parallel_enumeration,
+        memo.as_mut(),
+    )?;
+    save_memo_cache(memo_cache.as_deref(), memo)?;
+    io::write_stats(&results, subgraph_size, output, Ty::is_directed())?;
     Ok(())
 }
 
@@ -195,43 +974,226 @@ fn main() -> Result<()> {
         cli::Mode::Enumerate {
             input,
             output,
+            colors,
+            edge_types,
             subgraph_size,
+            min_size,
+            non_induced,
             threads,
             include_loops,
             undirected,
+            header,
+            sample_probs,
+            seed,
+            progress,
+            checkpoint,
+            checkpoint_interval,
+            resume,
+            shard,
+            memo_cache,
+            canonicalizer,
         } => {
+            let checkpoint = Checkpointing::new(checkpoint, checkpoint_interval, resume)?;
             if undirected {
                 submodule_enumerate::<Undirected>(
                     &input,
                     subgraph_size,
+                    min_size,
+                    non_induced,
                     output,
+                    colors,
+                    edge_types,
                     threads,
                     include_loops,
                     false,
+                    header,
+                    sample_probs,
+                    seed,
+                    progress,
+                    checkpoint,
+                    shard,
+                    memo_cache,
+                    canonicalizer,
                 )
             } else {
                 submodule_enumerate::<Directed>(
                     &input,
                     subgraph_size,
+                    min_size,
+                    non_induced,
                     output,
+                    colors,
+                    edge_types,
                     threads,
                     include_loops,
                     true,
+                    header,
+                    sample_probs,
+                    seed,
+                    progress,
+                    checkpoint,
+                    shard,
+                    memo_cache,
+                    canonicalizer,
                 )
             }
         }
         cli::Mode::Groups {
             input,
             output,
+            colors,
+            edge_types,
             subgraph_size,
+            threads,
             include_loops,
             undirected,
             no_header,
+            sample_probs,
+            seed,
+            shard,
+            memo_cache,
         } => {
             if undirected {
-                submodule_groups::<Undirected>(&input, subgraph_size, output, include_loops, false, no_header)
+                submodule_groups::<Undirected>(
+                    &input,
+                    subgraph_size,
+                    output,
+                    colors,
+                    edge_types,
+                    threads,
+                    include_loops,
+                    false,
+                    no_header,
+                    sample_probs,
+                    seed,
+                    shard,
+                    memo_cache,
+                )
             } else {
-                submodule_groups::<Directed>(&input, subgraph_size, output, include_loops, true, no_header)
+                submodule_groups::<Directed>(
+                    &input,
+                    subgraph_size,
+                    output,
+                    colors,
+                    edge_types,
+                    threads,
+                    include_loops,
+                    true,
+                    no_header,
+                    sample_probs,
+                    seed,
+                    shard,
+                    memo_cache,
+                )
+            }
+        }
+        cli::Mode::Instances {
+            input,
+            output,
+            subgraph_size,
+            motifs,
+            threads,
+            colors,
+            edge_types,
+            include_loops,
+            undirected,
+            no_header,
+        } => {
+            if undirected {
+                submodule_instances::<Undirected>(
+                    &input,
+                    subgraph_size,
+                    output,
+                    motifs,
+                    colors,
+                    edge_types,
+                    threads,
+                    include_loops,
+                    false,
+                    no_header,
+                )
+            } else {
+                submodule_instances::<Directed>(
+                    &input,
+                    subgraph_size,
+                    output,
+                    motifs,
+                    colors,
+                    edge_types,
+                    threads,
+                    include_loops,
+                    true,
+                    no_header,
+                )
+            }
+        }
+        cli::Mode::Query {
+            input,
+            output,
+            motifs,
+            instances,
+            threads,
+            include_loops,
+            undirected,
+        } => {
+            if undirected {
+                submodule_query::<Undirected>(
+                    &input,
+                    motifs,
+                    output,
+                    instances,
+                    threads,
+                    include_loops,
+                    false,
+                )
+            } else {
+                submodule_query::<Directed>(
+                    &input,
+                    motifs,
+                    output,
+                    instances,
+                    threads,
+                    include_loops,
+                    true,
+                )
+            }
+        }
+        cli::Mode::Gdv {
+            input,
+            output,
+            subgraph_size,
+            threads,
+            no_header,
+            shard,
+        } => submodule_gdv(&input, subgraph_size, output, threads, no_header, shard),
+        cli::Mode::Merge { input, output } => submodule_merge(&input, output),
+        cli::Mode::EdgeOrbits {
+            input,
+            output,
+            subgraph_size,
+            threads,
+            include_loops,
+            undirected,
+            no_header,
+        } => {
+            if undirected {
+                submodule_edge_orbits::<Undirected>(
+                    &input,
+                    subgraph_size,
+                    output,
+                    threads,
+                    include_loops,
+                    no_header,
+                )
+            } else {
+                submodule_edge_orbits::<Directed>(
+                    &input,
+                    subgraph_size,
+                    output,
+                    threads,
+                    include_loops,
+                    no_header,
+                )
             }
         }
         cli::Mode::Format {
@@ -244,23 +1206,54 @@ fn main() -> Result<()> {
             output,
             q,
             seed,
-        } => submodule_switch(&input, output, q, seed),
+            undirected,
+        } => {
+            if undirected {
+                submodule_switch::<Undirected>(&input, output, q, seed)
+            } else {
+                submodule_switch::<Directed>(&input, output, q, seed)
+            }
+        }
         cli::Mode::Enrich {
             input,
             output,
             subgraph_size,
             threads,
+            undirected,
             random_graphs,
             q,
             seed,
-        } => submodule_enrichment(
-            &input,
-            subgraph_size,
-            output,
-            threads,
-            random_graphs,
-            q,
-            seed,
-        ),
+            sample_probs,
+            parallel_enumeration,
+            memo_cache,
+        } => {
+            if undirected {
+                submodule_enrichment::<Undirected>(
+                    &input,
+                    subgraph_size,
+                    output,
+                    threads,
+                    random_graphs,
+                    q,
+                    seed,
+                    sample_probs,
+                    parallel_enumeration,
+                    memo_cache,
+                )
+            } else {
+                submodule_enrichment::<Directed>(
+                    &input,
+                    subgraph_size,
+                    output,
+                    threads,
+                    random_graphs,
+                    q,
+                    seed,
+                    sample_probs,
+                    parallel_enumeration,
+                    memo_cache,
+                )
+            }
+        }
     }
 }
@@ -0,0 +1,522 @@
+//! Merging the outputs of sharded runs.
+//!
+//! A run restricted to a [`Shard`] of the roots writes its parameters as a
+//! leading `#memoesu` line, so that the tables of all shards of a run can be
+//! validated and summed into the table of the full run.
+
+use crate::{enumerate::Shard, io};
+use anyhow::{bail, Context, Result};
+use hashbrown::HashMap;
+use ndarray::Array2;
+use std::{
+    cmp::Ordering,
+    fmt,
+    fs::File,
+    io::{stdout, BufRead, BufReader, BufWriter, Write},
+    str::FromStr,
+};
+
+/// The first field of the parameter line of a sharded output.
+const PREFIX: &str = "#memoesu";
+
+/// The kind of table written by a sharded run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardOutput {
+    /// The counts of each subgraph of a single size.
+    Counts,
+    /// The counts of each subgraph of a range of sizes.
+    SizeCounts,
+    /// The subgraph membership and orbit position of each node.
+    Groups,
+    /// The graphlet degree vector of each node.
+    Gdv,
+}
+impl ShardOutput {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Counts => "counts",
+            Self::SizeCounts => "size_counts",
+            Self::Groups => "groups",
+            Self::Gdv => "gdv",
+        }
+    }
+
+    /// The start of the column header line (if any) of the output.
+    fn columns_prefix(&self) -> &'static str {
+        match self {
+            Self::Counts => "canon\t",
+            Self::SizeCounts => "k\tcanon\t",
+            Self::Groups | Self::Gdv => "node_idx\t",
+        }
+    }
+
+    /// The number of leading columns that identify a row, the remaining
+    /// columns are summed.
+    fn key_columns(&self, num_columns: usize) -> usize {
+        match self {
+            Self::Gdv => 1,
+            _ => num_columns - 1,
+        }
+    }
+}
+
+impl FromStr for ShardOutput {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "counts" => Ok(Self::Counts),
+            "size_counts" => Ok(Self::SizeCounts),
+            "groups" => Ok(Self::Groups),
+            "gdv" => Ok(Self::Gdv),
+            _ => bail!("ERROR: Unknown sharded output {s:?}."),
+        }
+    }
+}
+
+/// The parameters of a sharded run.
+///
+/// All shards of a run share the same parameters apart from the index of
+/// their shard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardHeader {
+    pub output: ShardOutput,
+    pub shard: Shard,
+    pub min_size: usize,
+    pub k: usize,
+    pub is_directed: bool,
+    pub non_induced: bool,
+    pub nodes: usize,
+    pub edges: usize,
+}
+impl ShardHeader {
+    /// Whether the other header belongs to another shard of the same run.
+    fn matches(&self, other: &Self) -> bool {
+        self.shard.count() == other.shard.count()
+            && Self {
+                shard: other.shard,
+                ..self.clone()
+            } == *other
+    }
+}
+
+impl fmt::Display for ShardHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{PREFIX}\toutput={}\tshard={}\tmin_size={}\tk={}\tdirected={}\tnon_induced={}\tnodes={}\tedges={}",
+            self.output.as_str(),
+            self.shard,
+            self.min_size,
+            self.k,
+            self.is_directed,
+            self.non_induced,
+            self.nodes,
+            self.edges
+        )
+    }
+}
+
+impl FromStr for ShardHeader {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let mut fields = line.trim_end().split('\t');
+        if fields.next() != Some(PREFIX) {
+            bail!("ERROR: Expected the parameters of a sharded run on the first line.");
+        }
+        let params = fields
+            .map(|field| {
+                field.split_once('=').with_context(|| {
+                    format!("ERROR: Expected a parameter as key=value but found {field:?}.")
+                })
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        let get = |key: &str| {
+            params
+                .get(key)
+                .copied()
+                .with_context(|| format!("ERROR: Missing the {key} parameter of the sharded run."))
+        };
+        let parse_bool = |key: &str| -> Result<bool> {
+            get(key)?
+                .parse()
+                .with_context(|| format!("ERROR: Could not parse the {key} parameter."))
+        };
+        let parse_usize = |key: &str| -> Result<usize> {
+            get(key)?
+                .parse()
+                .with_context(|| format!("ERROR: Could not parse the {key} parameter."))
+        };
+        Ok(Self {
+            output: get("output")?.parse()?,
+            shard: get("shard")?.parse()?,
+            min_size: parse_usize("min_size")?,
+            k: parse_usize("k")?,
+            is_directed: parse_bool("directed")?,
+            non_induced: parse_bool("non_induced")?,
+            nodes: parse_usize("nodes")?,
+            edges: parse_usize("edges")?,
+        })
+    }
+}
+
+/// Write the parameter line of a sharded run (if any) to the output.
+pub(crate) fn write_shard_header<W: Write>(
+    buffer: &mut W,
+    shard: Option<&ShardHeader>,
+) -> Result<()> {
+    if let Some(shard) = shard {
+        writeln!(buffer, "{shard}")?;
+    }
+    Ok(())
+}
+
+/// The table written by a single shard.
+struct ShardTable {
+    header: ShardHeader,
+    columns: Option<String>,
+    rows: Vec<(Vec<String>, Vec<u64>)>,
+}
+
+/// Read the table written by a single shard.
+fn read_shard<B: BufRead>(buffer: &mut B) -> Result<ShardTable> {
+    let mut lines = buffer.lines();
+    let header = match lines.next() {
+        Some(line) => line?.parse::<ShardHeader>()?,
+        None => bail!("ERROR: The file is empty."),
+    };
+    let mut columns = None;
+    let mut rows = Vec::new();
+    for (line_idx, line) in lines.enumerate() {
+        let line = line?;
+        if line.starts_with(header.output.columns_prefix()) {
+            columns = Some(line);
+            continue;
+        }
+        let fields = line.split('\t').collect::<Vec<_>>();
+        if fields.len() < 2 {
+            bail!(
+                "ERROR: Expected at least two columns on line {}.",
+                line_idx + 2
+            );
+        }
+        let (key, values) = fields.split_at(header.output.key_columns(fields.len()));
+        let values = values
+            .iter()
+            .map(|value| value.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| {
+                format!(
+                    "ERROR: Could not parse the counts on line {} as integers.",
+                    line_idx + 2
+                )
+            })?;
+        rows.push((key.iter().map(|s| s.to_string()).collect(), values));
+    }
+    Ok(ShardTable {
+        header,
+        columns,
+        rows,
+    })
+}
+
+/// The summed tables of all shards of a run.
+pub struct MergedShards {
+    header: ShardHeader,
+    columns: Option<String>,
+    rows: Vec<(Vec<String>, Vec<u64>)>,
+}
+impl MergedShards {
+    /// The parameters shared by all shards.
+    pub fn header(&self) -> &ShardHeader {
+        &self.header
+    }
+
+    /// The number of rows of the merged table.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Whether the merged table has no rows.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The sum of the counts of all subgraphs (or all node positions for
+    /// groups and graphlet degree vectors).
+    pub fn total(&self) -> u64 {
+        self.rows.iter().flat_map(|(_, values)| values).sum()
+    }
+
+    /// Write the merged table to a file or stdout in the same format as an
+    /// unsharded run.
+    ///
+    /// Graphlet degree vectors are written as a NumPy matrix if the output
+    /// ends with `.npy`.
+    pub fn write(&self, output: Option<String>) -> Result<()> {
+        if self.header.output == ShardOutput::Gdv {
+            let width = self.rows.first().map_or(0, |(_, values)| values.len());
+            let gdv = Array2::from_shape_vec(
+                (self.rows.len(), width),
+                self.rows
+                    .iter()
+                    .flat_map(|(_, values)| values.clone())
+                    .collect(),
+            )?;
+            return io::write_gdv(&gdv, output, self.columns.is_none(), None);
+        }
+        if let Some(output) = output {
+            let mut buffer = File::create(&output).map(BufWriter::new)?;
+            eprintln!(">> Writing results to      : {}", &output);
+            self.write_to_buffer(&mut buffer)
+        } else {
+            let mut buffer = BufWriter::new(stdout().lock());
+            self.write_to_buffer(&mut buffer)
+        }
+    }
+
+    fn write_to_buffer<W: Write>(&self, buffer: &mut BufWriter<W>) -> Result<()> {
+        if let Some(columns) = &self.columns {
+            writeln!(buffer, "{columns}")?;
+        }
+        for (key, values) in self.rows.iter() {
+            let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            writeln!(buffer, "{}\t{}", key.join("\t"), values.join("\t"))?;
+        }
+        buffer.flush()?;
+        Ok(())
+    }
+}
+
+/// Sum the tables written by all shards of a run.
+///
+/// The shards must have been run with the same parameters, and every shard
+/// of the run must be given exactly once.
+pub fn merge_shards(inputs: &[String]) -> Result<MergedShards> {
+    let tables = inputs
+        .iter()
+        .map(|input| {
+            let file = File::open(input)
+                .with_context(|| format!("ERROR: Unable to open shard: {input}"))?;
+            read_shard(&mut BufReader::new(file))
+                .with_context(|| format!("ERROR: Unable to read shard: {input}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    merge_tables(inputs, tables)
+}
+
+fn merge_tables(inputs: &[String], tables: Vec<ShardTable>) -> Result<MergedShards> {
+    let Some(first) = tables.first() else {
+        bail!("ERROR: At least one shard is required.");
+    };
+    let header = first.header.clone();
+
+    // Every shard of the same run must be present exactly once
+    let mut seen = vec![None; header.shard.count()];
+    for (input, table) in inputs.iter().zip(tables.iter()) {
+        if !header.matches(&table.header) {
+            bail!(
+                "ERROR: The parameters of {input} do not match those of {}:\n{}\n{}",
+                inputs[0],
+                table.header,
+                header
+            );
+        }
+        let index = table.header.shard.index();
+        if let Some(other) = seen[index - 1].replace(input) {
+            bail!(
+                "ERROR: Shard {} is given twice ({other} and {input}).",
+                table.header.shard
+            );
+        }
+    }
+    let missing = (1..=header.shard.count())
+        .filter(|&index| seen[index - 1].is_none())
+        .map(|index| index.to_string())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        bail!(
+            "ERROR: Missing shards {} of {}.",
+            missing.join(","),
+            header.shard.count()
+        );
+    }
+
+    // Sum the rows of all shards
+    let mut columns = None;
+    let mut merged: HashMap<Vec<String>, Vec<u64>> = HashMap::new();
+    for table in tables {
+        // Shards without any subgraphs may lack the color and edge type columns
+        if columns.is_none() || !table.rows.is_empty() {
+            columns = columns.or(table.columns);
+        }
+        for (key, values) in table.rows {
+            let sums = merged.entry(key).or_insert_with(|| vec![0; values.len()]);
+            if sums.len() != values.len() {
+                bail!("ERROR: The shards have a different number of columns.");
+            }
+            sums.iter_mut()
+                .zip(values)
+                .for_each(|(sum, value)| *sum += value);
+        }
+    }
+
+    // Sort the rows in the same order as an unsharded run
+    let mut rows = merged.into_iter().collect::<Vec<_>>();
+    match header.output {
+        ShardOutput::Counts => rows.sort_by(|a, b| a.1.cmp(&b.1).then(compare_keys(&a.0, &b.0))),
+        ShardOutput::SizeCounts => rows.sort_by(|a, b| {
+            compare_keys(&a.0[..1], &b.0[..1])
+                .then(a.1.cmp(&b.1))
+                .then(compare_keys(&a.0, &b.0))
+        }),
+        ShardOutput::Groups | ShardOutput::Gdv => rows.sort_by(|a, b| compare_keys(&a.0, &b.0)),
+    }
+    Ok(MergedShards {
+        header,
+        columns,
+        rows,
+    })
+}
+
+/// Compare the key columns of two rows, numerically where both are integers.
+fn compare_keys(a: &[String], b: &[String]) -> Ordering {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => a.cmp(b),
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use std::io::Cursor;
+
+    fn header(output: ShardOutput, index: usize, count: usize) -> ShardHeader {
+        ShardHeader {
+            output,
+            shard: Shard::new(index, count).unwrap(),
+            min_size: 3,
+            k: 3,
+            is_directed: true,
+            non_induced: false,
+            nodes: 9,
+            edges: 9,
+        }
+    }
+
+    fn table(header: &ShardHeader, body: &str) -> ShardTable {
+        read_shard(&mut Cursor::new(format!("{header}\n{body}"))).unwrap()
+    }
+
+    fn names(count: usize) -> Vec<String> {
+        (1..=count).map(|i| format!("shard{i}.tsv")).collect()
+    }
+
+    #[test]
+    fn shard_header_roundtrip() {
+        let header = header(ShardOutput::SizeCounts, 2, 4);
+        let parsed = header.to_string().parse::<ShardHeader>().unwrap();
+        assert_eq!(parsed, header);
+        assert!("canon\tcount".parse::<ShardHeader>().is_err());
+        assert!("#memoesu\toutput=counts\tshard=1/2"
+            .parse::<ShardHeader>()
+            .is_err());
+    }
+
+    #[test]
+    fn merge_count_shards() {
+        let first = header(ShardOutput::Counts, 1, 2);
+        let second = header(ShardOutput::Counts, 2, 2);
+        let tables = vec![
+            table(&first, "canon\tcount\n&BP_\t1\n&BC_\t5\n"),
+            table(&second, "&BC_\t2\n&BW_\t4\n"),
+        ];
+        let merged = merge_tables(&names(2), tables).unwrap();
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged.total(), 12);
+        let mut buffer = BufWriter::new(Vec::new());
+        merged.write_to_buffer(&mut buffer).unwrap();
+        let output = String::from_utf8(buffer.into_inner().unwrap()).unwrap();
+        assert_eq!(output, "canon\tcount\n&BP_\t1\n&BW_\t4\n&BC_\t7\n");
+    }
+
+    #[test]
+    fn merge_group_shards() {
+        let first = header(ShardOutput::Groups, 1, 2);
+        let second = header(ShardOutput::Groups, 2, 2);
+        let columns = "node_idx\tcanon\tlabel\torbit\tabundance\n";
+        let tables = vec![
+            table(
+                &first,
+                &format!("{columns}10\t&BC_\t0\t0\t1\n2\t&BC_\t1\t1\t2\n"),
+            ),
+            table(&second, &format!("{columns}2\t&BC_\t1\t1\t3\n")),
+        ];
+        let merged = merge_tables(&names(2), tables).unwrap();
+        let mut buffer = BufWriter::new(Vec::new());
+        merged.write_to_buffer(&mut buffer).unwrap();
+        let output = String::from_utf8(buffer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            format!("{columns}2\t&BC_\t1\t1\t5\n10\t&BC_\t0\t0\t1\n")
+        );
+    }
+
+    #[test]
+    fn merge_gdv_shards() {
+        let first = header(ShardOutput::Gdv, 1, 2);
+        let second = header(ShardOutput::Gdv, 2, 2);
+        let tables = vec![
+            table(&first, "1\t1\t0\n2\t2\t1\n"),
+            table(&second, "1\t0\t3\n2\t1\t1\n"),
+        ];
+        let merged = merge_tables(&names(2), tables).unwrap();
+        assert!(merged.columns.is_none());
+        assert_eq!(
+            merged.rows,
+            vec![
+                (vec!["1".to_string()], vec![1, 3]),
+                (vec!["2".to_string()], vec![3, 2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_shards() {
+        let first = header(ShardOutput::Counts, 1, 2);
+        let second = header(ShardOutput::Counts, 2, 2);
+
+        // Missing and duplicate shards
+        let tables = vec![table(&first, "&BC_\t1\n")];
+        assert!(merge_tables(&names(1), tables).is_err());
+        let tables = vec![table(&first, "&BC_\t1\n"), table(&first, "&BC_\t1\n")];
+        assert!(merge_tables(&names(2), tables).is_err());
+
+        // Different subgraph sizes, edge types, and number of shards
+        let other = ShardHeader {
+            k: 4,
+            ..second.clone()
+        };
+        let tables = vec![table(&first, "&BC_\t1\n"), table(&other, "&BC_\t1\n")];
+        assert!(merge_tables(&names(2), tables).is_err());
+        let other = ShardHeader {
+            is_directed: false,
+            ..second.clone()
+        };
+        let tables = vec![table(&first, "&BC_\t1\n"), table(&other, "&BC_\t1\n")];
+        assert!(merge_tables(&names(2), tables).is_err());
+        let other = header(ShardOutput::Counts, 2, 3);
+        let tables = vec![table(&first, "&BC_\t1\n"), table(&other, "&BC_\t1\n")];
+        assert!(merge_tables(&names(2), tables).is_err());
+
+        // Files without parameters or counts
+        assert!(read_shard(&mut Cursor::new("&BC_\t1\n")).is_err());
+        assert!(read_shard(&mut Cursor::new(format!("{second}\n&BC_\tx\n"))).is_err());
+    }
+}
@@ -1,18 +1,25 @@
+use anyhow::{bail, Result};
 use hashbrown::{HashMap, HashSet};
 use petgraph::{
     graph::{EdgeIndex, NodeIndex},
-    Directed, Graph,
+    EdgeType, Graph,
 };
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaChaRng;
 
+/// Number of consecutive rejected switches after which the graph is
+/// considered to have no valid switches left.
+const MAX_REJECTED_SWITCHES: usize = 100_000;
+
 /// Data structure for storing the edges of a graph.
 ///
 /// This is used to quickly check if an edge exists between two nodes.
 ///
 /// Organized such that the first key is the source node and the second key is the target node.
+/// The edges of undirected graphs are stored in both directions.
 struct NodeMap {
     map: HashMap<NodeIndex, HashSet<NodeIndex>>,
+    is_directed: bool,
 }
 impl NodeMap {
     fn contains_edge(&self, x: NodeIndex, y: NodeIndex) -> bool {
@@ -27,11 +34,20 @@ impl NodeMap {
         if let Some(xmap) = self.map.get_mut(&x) {
             xmap.remove(&y);
         }
+        if !self.is_directed {
+            if let Some(ymap) = self.map.get_mut(&y) {
+                ymap.remove(&x);
+            }
+        }
     }
 
     fn add_edge(&mut self, x: NodeIndex, y: NodeIndex) {
         let nbh = self.map.entry(x).or_insert_with(HashSet::new);
         nbh.insert(y);
+        if !self.is_directed {
+            let nbh = self.map.entry(y).or_insert_with(HashSet::new);
+            nbh.insert(x);
+        }
     }
 }
 
@@ -41,6 +57,11 @@ impl NodeMap {
 /// The switch is not performed if the resulting graph would have a self-loop
 /// or if duplicate edges are created.
 ///
+/// Undirected graphs use the double edge swap, which replaces the edges
+/// `{x1, x2}` and `{y1, y2}` by either `{x1, y2}` and `{y1, x2}` or
+/// `{x1, y1}` and `{x2, y2}` (chosen at random), so that all degree
+/// preserving rewirings can be reached.
+///
 /// More about this model can be found in:
 /// 1. On the uniform generation of random graphs with prescribed degree sequences, https://arxiv.org/abs/cond-mat/0312028
 /// 2. Kavosh: a new algorithm for finding network motifs, https://bmcbioinformatics.biomedcentral.com/articles/10.1186/1471-2105-10-318
@@ -49,15 +70,24 @@ impl NodeMap {
 /// * `graph` - The graph to create a random graph from.
 /// * `q` - The number of operations to perform (total = q * num_edges).
 /// * `seed` - The seed for the random number generator.
-pub fn switching(
-    graph: &Graph<(), (), Directed>,
+///
+/// # Errors
+/// Returns an error if the graph has fewer than two edges or if no valid
+/// switch could be found after a large number of consecutive attempts.
+pub fn switching<Ty: EdgeType>(
+    graph: &Graph<(), (), Ty>,
     q: usize,
     seed: usize,
-) -> Graph<(), (), Directed> {
+) -> Result<Graph<(), (), Ty>> {
+    if graph.edge_count() < 2 {
+        bail!("ERROR: Switching requires a graph with at least two edges.");
+    }
+
     let mut rgraph = graph.clone();
     let mut node_map = build_map(&rgraph);
     let mut rng = ChaChaRng::seed_from_u64(seed as u64);
     let mut num_switches = 0;
+    let mut num_rejected = 0;
     let num_operations = graph.edge_count() * q;
 
     while num_switches < num_operations {
@@ -66,32 +96,46 @@ pub fn switching(
 
         // Get the nodes of the edges.
         let (x1, x2) = rgraph.edge_endpoints(idx).unwrap();
-        let (y1, y2) = rgraph.edge_endpoints(jdx).unwrap();
+        let (mut y1, mut y2) = rgraph.edge_endpoints(jdx).unwrap();
+
+        // Orient the second undirected edge at random.
+        if !Ty::is_directed() && rng.gen::<bool>() {
+            std::mem::swap(&mut y1, &mut y2);
+        }
 
         // Check if the switch is valid and continue if not.
         if is_invalid_switch(&node_map, x1, x2, y1, y2) {
+            num_rejected += 1;
+            if num_rejected >= MAX_REJECTED_SWITCHES {
+                bail!("ERROR: Could not find a valid switch after {num_rejected} attempts.");
+            }
             continue;
         }
 
         // Perform the switch.
         perform_switch(&mut rgraph, &mut node_map, idx, jdx, x1, x2, y1, y2);
         num_switches += 1;
+        num_rejected = 0;
     }
-    rgraph
+    Ok(rgraph)
 }
 
-fn build_map(graph: &Graph<(), (), Directed>) -> NodeMap {
-    let mut map = HashMap::with_capacity(graph.node_count());
+fn build_map<Ty: EdgeType>(graph: &Graph<(), (), Ty>) -> NodeMap {
+    let mut node_map = NodeMap {
+        map: HashMap::with_capacity(graph.node_count()),
+        is_directed: Ty::is_directed(),
+    };
     for edge in graph.edge_indices() {
         let (x, y) = graph.edge_endpoints(edge).unwrap();
-        let nbh = map.entry(x).or_insert_with(HashSet::new);
-        nbh.insert(y);
+        node_map.add_edge(x, y);
     }
-    NodeMap { map }
+    node_map
 }
 
 // Check if there already exists an edge from x1 => y2 or from y1 => x2.
 // Check if this switch would create a loop.
+// Check if an undirected switch would leave the edges unchanged or move a loop
+// (which would change the degrees).
 // If so, we cannot perform the switch.
 fn is_invalid_switch(
     node_map: &NodeMap,
@@ -100,7 +144,9 @@ fn is_invalid_switch(
     y1: NodeIndex,
     y2: NodeIndex,
 ) -> bool {
-    would_duplicate(node_map, x1, x2, y1, y2) || would_loop(x1, x2, y1, y2)
+    would_duplicate(node_map, x1, x2, y1, y2)
+        || would_loop(x1, x2, y1, y2)
+        || (!node_map.is_directed && (x1 == y1 || x1 == x2 || y1 == y2))
 }
 
 /// Checks if the switch would create duplicate edges.
@@ -136,8 +182,8 @@ fn sample_edges<R: Rng>(rng: &mut R, num_edges: usize) -> (EdgeIndex, EdgeIndex)
 /// The two edges are removed in the reverse order of their indices
 /// to avoid invalidating the indices.
 #[allow(clippy::too_many_arguments)]
-fn perform_switch(
-    graph: &mut Graph<(), (), Directed>,
+fn perform_switch<Ty: EdgeType>(
+    graph: &mut Graph<(), (), Ty>,
     node_map: &mut NodeMap,
     idx: EdgeIndex,
     jdx: EdgeIndex,
@@ -172,7 +218,7 @@ fn perform_switch(
 mod testing {
 
     use super::*;
-    use petgraph::Direction::Outgoing;
+    use petgraph::{visit::EdgeRef, Directed, Direction::Outgoing, Undirected};
 
     fn example_graph() -> Graph<(), (), Directed> {
         let edges = [
@@ -186,7 +232,7 @@ mod testing {
             (7, 2),
             (2, 8),
         ];
-        Graph::from_edges(&edges)
+        Graph::from_edges(edges)
     }
 
     #[test]
@@ -276,7 +322,7 @@ mod testing {
         let graph = example_graph();
         let q = 100;
         let seed = rand::random();
-        let random_graph = switching(&graph, q, seed);
+        let random_graph = switching(&graph, q, seed).unwrap();
 
         assert_eq!(graph.node_count(), random_graph.node_count());
         assert_eq!(graph.edge_count(), random_graph.edge_count());
@@ -287,4 +333,63 @@ mod testing {
             )
         }
     }
+
+    #[test]
+    fn test_undirected_switching() {
+        let graph = example_graph().into_edge_type::<Undirected>();
+        let random_graph = switching(&graph, 100, 7).unwrap();
+        assert_ne!(
+            random_graph
+                .edge_references()
+                .map(|e| (e.source(), e.target()))
+                .collect::<Vec<_>>(),
+            graph
+                .edge_references()
+                .map(|e| (e.source(), e.target()))
+                .collect::<Vec<_>>(),
+        );
+        for n in graph.node_indices() {
+            assert_eq!(
+                graph.neighbors(n).count(),
+                random_graph.neighbors(n).count()
+            );
+        }
+
+        // No loops or multi-edges (in either direction) are created
+        let mut edges = HashSet::new();
+        for edge in random_graph.edge_references() {
+            let (u, v) = (edge.source(), edge.target());
+            assert_ne!(u, v);
+            assert!(edges.insert((u.min(v), u.max(v))));
+        }
+    }
+
+    #[test]
+    fn test_undirected_invalid_switch() {
+        let graph = example_graph().into_edge_type::<Undirected>();
+        let node_map = build_map(&graph);
+        let [n0, n1, n2, n3, n4] = [0, 1, 2, 3, 4].map(NodeIndex::new);
+        assert!(node_map.contains_edge(n1, n0));
+
+        // {0, 4} and {1, 3} would become {0, 3}, which exists as {3, 0}
+        assert!(is_invalid_switch(&node_map, n0, n4, n1, n3));
+        // {0, 4} and {0, 1} share an endpoint
+        assert!(is_invalid_switch(&node_map, n0, n4, n0, n1));
+        // {0, 4} and {2, 8} become {0, 8} and {2, 4}
+        let n8 = NodeIndex::new(8);
+        assert!(!is_invalid_switch(&node_map, n0, n4, n2, n8));
+    }
+
+    #[test]
+    fn test_switching_too_few_edges() {
+        let graph: Graph<(), (), Directed> = Graph::from_edges([(0, 1)]);
+        assert!(switching(&graph, 3, 0).is_err());
+    }
+
+    #[test]
+    fn test_switching_no_valid_switch() {
+        // The only possible switch would create a loop.
+        let graph: Graph<(), (), Directed> = Graph::from_edges([(0, 1), (1, 0)]);
+        assert!(switching(&graph, 3, 0).is_err());
+    }
 }